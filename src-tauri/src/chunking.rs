@@ -0,0 +1,427 @@
+use crate::error::{AppError, AppResult};
+use crate::file_processor::TextChunk;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokenizers::Tokenizer;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Characters per token assumed when a model's tokenizer isn't available
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Hugging Face tokenizers of Ollama embedding models, by model name without its tag
+const MODEL_TOKENIZERS: &[(&str, &str)] = &[
+    ("nomic-embed-text", "nomic-ai/nomic-embed-text-v1.5"),
+    ("mxbai-embed-large", "mixedbread-ai/mxbai-embed-large-v1"),
+    ("all-minilm", "sentence-transformers/all-MiniLM-L6-v2"),
+    ("snowflake-arctic-embed", "Snowflake/snowflake-arctic-embed-l"),
+    ("bge-m3", "BAAI/bge-m3"),
+    ("bge-large", "BAAI/bge-large-en-v1.5"),
+    ("paraphrase-multilingual", "sentence-transformers/paraphrase-multilingual-MiniLM-L12-v2"),
+];
+
+/// Tokenizers loaded so far, by Hugging Face repo, so each is fetched and parsed once
+static TOKENIZERS: OnceLock<Mutex<HashMap<&'static str, Arc<Tokenizer>>>> = OnceLock::new();
+
+/// Byte range of a piece of text
+type Span = (usize, usize);
+
+/// How extracted text is cut into chunks
+pub trait ChunkingStrategy: Send + Sync {
+    /// Split text into chunks with exact offsets into it
+    fn chunk(&self, text: &str) -> Vec<TextChunk>;
+
+    /// Size of `text` in the strategy's unit: graphemes, sentences or tokens
+    fn measure(&self, text: &str) -> usize;
+
+    /// Largest chunk size, in the same unit
+    ///
+    /// Structure-aware chunkers (CSV rows, Markdown sections) pack their own
+    /// blocks up to this size using `measure`.
+    fn limit(&self) -> usize;
+}
+
+/// A chunking strategy and its settings, as chosen per model or per upload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum ChunkingConfig {
+    /// Runs of `chunk_size` graphemes, cut wherever they end
+    FixedWidth {
+        chunk_size: usize,
+        #[serde(default)]
+        overlap: usize,
+    },
+    /// Up to `chunk_size` graphemes, split at paragraphs, then sentences, then words
+    Recursive {
+        chunk_size: usize,
+        #[serde(default)]
+        overlap: usize,
+    },
+    /// `window` sentences per chunk, repeating `overlap` of them in the next
+    SentenceWindow {
+        window: usize,
+        #[serde(default)]
+        overlap: usize,
+    },
+    /// Up to `max_tokens` tokens as the embedding model's tokenizer counts them
+    TokenCount {
+        max_tokens: usize,
+        #[serde(default)]
+        overlap: usize,
+    },
+}
+
+impl ChunkingConfig {
+    /// Reject sizes that can't make progress through the text
+    pub fn validate(&self) -> AppResult<()> {
+        let (size, overlap, unit) = match *self {
+            ChunkingConfig::FixedWidth { chunk_size, overlap } => (chunk_size, overlap, "chunk size"),
+            ChunkingConfig::Recursive { chunk_size, overlap } => (chunk_size, overlap, "chunk size"),
+            ChunkingConfig::SentenceWindow { window, overlap } => (window, overlap, "sentence window"),
+            ChunkingConfig::TokenCount { max_tokens, overlap } => (max_tokens, overlap, "token limit"),
+        };
+
+        if size == 0 {
+            return Err(AppError::InvalidChunking(format!("The {} must be at least 1", unit)));
+        }
+        if overlap >= size {
+            return Err(AppError::InvalidChunking(format!(
+                "Overlap ({}) must be smaller than the {} ({})",
+                overlap, unit, size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Build the strategy, loading the embedding model's tokenizer if it counts tokens
+    ///
+    /// Loading a tokenizer may download it, so call this off the async runtime.
+    /// Fails with `AppError::Tokenizer` when the tokenizer can't be fetched.
+    pub fn strategy(&self, embedding_model: Option<&str>) -> AppResult<Box<dyn ChunkingStrategy>> {
+        self.validate()?;
+
+        Ok(match *self {
+            ChunkingConfig::FixedWidth { chunk_size, overlap } => Box::new(FixedWidth { chunk_size, overlap }),
+            ChunkingConfig::Recursive { chunk_size, overlap } => Box::new(Recursive { chunk_size, overlap }),
+            ChunkingConfig::SentenceWindow { window, overlap } => Box::new(SentenceWindow { window, overlap }),
+            ChunkingConfig::TokenCount { max_tokens, overlap } => Box::new(TokenCount {
+                max_tokens,
+                overlap,
+                tokens: match embedding_model {
+                    Some(model) => TokenCounter::for_embedding_model(model)?,
+                    None => TokenCounter::Estimate,
+                },
+            }),
+        })
+    }
+}
+
+/// Counts tokens the way an embedding model will
+pub enum TokenCounter {
+    Model(Arc<Tokenizer>),
+    /// Rough count from word boundaries, for models without a known tokenizer
+    Estimate,
+}
+
+impl TokenCounter {
+    /// Tokenizer of an Ollama embedding model, downloaded on first use and
+    /// kept for the rest of the session
+    ///
+    /// Unknown models are estimated.
+    pub fn for_embedding_model(model: &str) -> AppResult<Self> {
+        let name = model.split(':').next().unwrap_or(model);
+        let Some(&(_, repo)) = MODEL_TOKENIZERS.iter().find(|(n, _)| *n == name) else {
+            return Ok(TokenCounter::Estimate);
+        };
+
+        let cache = TOKENIZERS.get_or_init(Default::default);
+        if let Some(tokenizer) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(repo) {
+            return Ok(TokenCounter::Model(tokenizer.clone()));
+        }
+
+        // Loaded without holding the lock, so other models aren't held up by a download
+        let mut tokenizer = Tokenizer::from_pretrained(repo, None)
+            .map_err(|e| AppError::Tokenizer(format!("{}: {}", model, e)))?;
+        // Count the whole text; the model's own limit is applied when embedding
+        tokenizer.with_padding(None);
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| AppError::Tokenizer(format!("{}: {}", model, e)))?;
+
+        let tokenizer = Arc::new(tokenizer);
+        cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(repo, tokenizer.clone());
+        Ok(TokenCounter::Model(tokenizer))
+    }
+
+    /// Byte spans of the tokens in `text`
+    fn tokens(&self, text: &str) -> Vec<Span> {
+        match self {
+            TokenCounter::Model(tokenizer) => match tokenizer.encode(text, false) {
+                Ok(encoding) => encoding.get_offsets().iter().copied().filter(|(s, e)| s < e).collect(),
+                Err(_) => TokenCounter::Estimate.tokens(text),
+            },
+            // One token per word or punctuation mark, long words one per four characters
+            TokenCounter::Estimate => text
+                .split_word_bound_indices()
+                .filter(|(_, word)| !word.trim().is_empty())
+                .flat_map(|(start, word)| {
+                    let mut bounds: Vec<usize> =
+                        word.char_indices().map(|(i, _)| i).step_by(CHARS_PER_TOKEN).collect();
+                    bounds.push(word.len());
+                    bounds.windows(2).map(|w| (start + w[0], start + w[1])).collect::<Vec<_>>()
+                })
+                .collect(),
+        }
+    }
+}
+
+struct FixedWidth {
+    chunk_size: usize,
+    overlap: usize,
+}
+
+impl ChunkingStrategy for FixedWidth {
+    fn chunk(&self, text: &str) -> Vec<TextChunk> {
+        let graphemes: Vec<(Span, usize)> = text
+            .grapheme_indices(true)
+            .map(|(i, g)| ((i, i + g.len()), 1))
+            .collect();
+        to_chunks(text, pack(&graphemes, self.chunk_size, self.overlap), false)
+    }
+
+    fn measure(&self, text: &str) -> usize {
+        text.graphemes(true).count()
+    }
+
+    fn limit(&self) -> usize {
+        self.chunk_size
+    }
+}
+
+/// Separator levels tried in turn by the recursive splitter
+const SPLIT_LEVELS: usize = 5;
+
+struct Recursive {
+    chunk_size: usize,
+    overlap: usize,
+}
+
+impl Recursive {
+    /// Split `span` at `level`, descending a level for any piece still too big
+    ///
+    /// Pieces that fit are packed together with their siblings only, so a
+    /// chunk never straddles a paragraph that had to be split further.
+    fn spans(&self, text: &str, span: Span, level: usize) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut siblings = Vec::new();
+        for piece in split_at_level(text, span, level) {
+            let size = self.measure(&text[piece.0..piece.1]);
+            if size <= self.chunk_size || level + 1 >= SPLIT_LEVELS {
+                siblings.push((piece, size));
+            } else {
+                spans.extend(pack(&siblings, self.chunk_size, self.overlap));
+                siblings.clear();
+                spans.extend(self.spans(text, piece, level + 1));
+            }
+        }
+        spans.extend(pack(&siblings, self.chunk_size, self.overlap));
+        spans
+    }
+}
+
+impl ChunkingStrategy for Recursive {
+    fn chunk(&self, text: &str) -> Vec<TextChunk> {
+        to_chunks(text, self.spans(text, (0, text.len()), 0), true)
+    }
+
+    fn measure(&self, text: &str) -> usize {
+        text.graphemes(true).count()
+    }
+
+    fn limit(&self) -> usize {
+        self.chunk_size
+    }
+}
+
+/// Contiguous pieces of `span`: paragraphs, lines, sentences, words, then graphemes
+fn split_at_level(text: &str, (start, end): Span, level: usize) -> Vec<Span> {
+    let slice = &text[start..end];
+    let mut cuts: Vec<usize> = match level {
+        0 => slice.match_indices("\n\n").map(|(i, s)| i + s.len()).collect(),
+        1 => slice.match_indices('\n').map(|(i, s)| i + s.len()).collect(),
+        2 => slice.split_sentence_bound_indices().map(|(i, _)| i).collect(),
+        3 => slice.split_word_bound_indices().map(|(i, _)| i).collect(),
+        _ => slice.grapheme_indices(true).map(|(i, _)| i).collect(),
+    };
+    cuts.push(0);
+    cuts.push(slice.len());
+    cuts.sort_unstable();
+    cuts.dedup();
+    cuts.windows(2).map(|w| (start + w[0], start + w[1])).collect()
+}
+
+struct SentenceWindow {
+    window: usize,
+    overlap: usize,
+}
+
+impl ChunkingStrategy for SentenceWindow {
+    fn chunk(&self, text: &str) -> Vec<TextChunk> {
+        let sentences: Vec<(Span, usize)> = text
+            .split_sentence_bound_indices()
+            .filter(|(_, sentence)| !sentence.trim().is_empty())
+            .map(|(i, sentence)| ((i, i + sentence.len()), 1))
+            .collect();
+        to_chunks(text, pack(&sentences, self.window, self.overlap), true)
+    }
+
+    fn measure(&self, text: &str) -> usize {
+        text.split_sentence_bounds().filter(|s| !s.trim().is_empty()).count()
+    }
+
+    fn limit(&self) -> usize {
+        self.window
+    }
+}
+
+struct TokenCount {
+    max_tokens: usize,
+    overlap: usize,
+    tokens: TokenCounter,
+}
+
+impl ChunkingStrategy for TokenCount {
+    fn chunk(&self, text: &str) -> Vec<TextChunk> {
+        let tokens: Vec<(Span, usize)> = self.tokens.tokens(text).into_iter().map(|t| (t, 1)).collect();
+        to_chunks(text, pack(&tokens, self.max_tokens, self.overlap), true)
+    }
+
+    fn measure(&self, text: &str) -> usize {
+        self.tokens.tokens(text).len()
+    }
+
+    fn limit(&self) -> usize {
+        self.max_tokens
+    }
+}
+
+/// Pack pieces in order into spans of at most `limit`, starting each span
+/// with up to `overlap` worth of the previous one's trailing pieces
+///
+/// A piece bigger than `limit` becomes a span of its own.
+fn pack(pieces: &[(Span, usize)], limit: usize, overlap: usize) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut window: VecDeque<(Span, usize)> = VecDeque::new();
+    let mut total = 0;
+
+    for &(piece, size) in pieces {
+        if let (Some(first), Some(last)) = (window.front(), window.back()) {
+            if total + size > limit {
+                spans.push((first.0 .0, last.0 .1));
+                while total > overlap || (total > 0 && total + size > limit) {
+                    let (_, dropped) = window.pop_front().expect("window holds the pieces counted in total");
+                    total -= dropped;
+                }
+            }
+        }
+        window.push_back((piece, size));
+        total += size;
+    }
+    if let (Some(first), Some(last)) = (window.front(), window.back()) {
+        spans.push((first.0 .0, last.0 .1));
+    }
+
+    spans
+}
+
+/// Turn byte spans into chunks, skipping blank ones
+///
+/// Spans are widened to grapheme boundaries, and with `trim` narrowed to
+/// leave out surrounding whitespace.
+fn to_chunks(text: &str, spans: Vec<Span>, trim: bool) -> Vec<TextChunk> {
+    let starts: Vec<usize> = text.grapheme_indices(true).map(|(i, _)| i).collect();
+    let grapheme_at = |byte: usize| starts.partition_point(|&s| s < byte);
+    let byte_of = |grapheme: usize| starts.get(grapheme).copied().unwrap_or(text.len());
+
+    let mut chunks = Vec::new();
+    for (start, end) in spans {
+        let slice = &text[start..end];
+        if slice.trim().is_empty() {
+            continue;
+        }
+        let (start, end) = match trim {
+            true => (end - slice.trim_start().len(), start + slice.trim_end().len()),
+            false => (start, end),
+        };
+
+        let (char_start, char_end) = (grapheme_at(start), grapheme_at(end));
+        let (byte_start, byte_end) = (byte_of(char_start), byte_of(char_end));
+        chunks.push(TextChunk {
+            text: text[byte_start..byte_end].to_string(),
+            index: chunks.len(),
+            char_start,
+            char_end,
+            byte_start,
+            byte_end,
+            page_start: None,
+            page_end: None,
+            section: None,
+            line_start: None,
+            line_end: None,
+            symbol: None,
+        });
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(config: ChunkingConfig, text: &str) -> Vec<String> {
+        let chunks = config.strategy(None).unwrap().chunk(text);
+        assert!(chunks.iter().all(|c| text[c.byte_start..c.byte_end] == c.text));
+        chunks.into_iter().map(|c| c.text).collect()
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ChunkingConfig::FixedWidth { chunk_size: 20, overlap: 5 }.validate().is_ok());
+        assert!(ChunkingConfig::FixedWidth { chunk_size: 20, overlap: 20 }.validate().is_err());
+        assert!(ChunkingConfig::Recursive { chunk_size: 0, overlap: 0 }.validate().is_err());
+        assert!(ChunkingConfig::SentenceWindow { window: 2, overlap: 3 }.strategy(None).is_err());
+
+        let config: ChunkingConfig = serde_json::from_str(r#"{"strategy":"token_count","max_tokens":256}"#).unwrap();
+        assert_eq!(config, ChunkingConfig::TokenCount { max_tokens: 256, overlap: 0 });
+    }
+
+    #[test]
+    fn test_strategies() {
+        let text = "First point here. Second point.\n\nA new paragraph follows.";
+
+        assert_eq!(
+            chunk(ChunkingConfig::FixedWidth { chunk_size: 20, overlap: 5 }, "abcdefghijklmnopqrstuvwxyz"),
+            vec!["abcdefghijklmnopqrst", "pqrstuvwxyz"]
+        );
+        assert_eq!(
+            chunk(ChunkingConfig::Recursive { chunk_size: 32, overlap: 0 }, text),
+            vec!["First point here. Second point.", "A new paragraph follows."]
+        );
+        assert_eq!(
+            chunk(ChunkingConfig::Recursive { chunk_size: 20, overlap: 0 }, text),
+            vec!["First point here.", "Second point.", "A new paragraph", "follows."]
+        );
+        assert_eq!(
+            chunk(ChunkingConfig::SentenceWindow { window: 2, overlap: 1 }, text),
+            vec!["First point here. Second point.", "Second point.\n\nA new paragraph follows."]
+        );
+        assert_eq!(
+            chunk(ChunkingConfig::TokenCount { max_tokens: 4, overlap: 1 }, "We go to the park. It is fun."),
+            vec!["We go to the", "the park. It", "It is fun."]
+        );
+    }
+}
@@ -0,0 +1,599 @@
+use crate::chunking::ChunkingStrategy;
+use crate::file_processor::TextChunk;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Programming languages recognised by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    TypeScript,
+    JavaScript,
+    Go,
+    Java,
+    Kotlin,
+    CSharp,
+    C,
+    Cpp,
+    Swift,
+    Scala,
+    Php,
+}
+
+impl Language {
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "rs" => Some(Language::Rust),
+            "py" | "pyi" => Some(Language::Python),
+            "ts" | "tsx" | "mts" | "cts" => Some(Language::TypeScript),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Language::JavaScript),
+            "go" => Some(Language::Go),
+            "java" => Some(Language::Java),
+            "kt" | "kts" => Some(Language::Kotlin),
+            "cs" => Some(Language::CSharp),
+            "c" | "h" => Some(Language::C),
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Some(Language::Cpp),
+            "swift" => Some(Language::Swift),
+            "scala" => Some(Language::Scala),
+            "php" => Some(Language::Php),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::TypeScript => "typescript",
+            Language::JavaScript => "javascript",
+            Language::Go => "go",
+            Language::Java => "java",
+            Language::Kotlin => "kotlin",
+            Language::CSharp => "csharp",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::Swift => "swift",
+            Language::Scala => "scala",
+            Language::Php => "php",
+        }
+    }
+
+    /// Keywords that introduce a named definition
+    fn definition_keywords(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &[
+                "fn", "struct", "enum", "trait", "impl", "mod", "union", "macro_rules", "type", "const", "static",
+            ],
+            Language::Python => &["def", "class"],
+            Language::TypeScript | Language::JavaScript => &[
+                "function", "class", "interface", "enum", "type", "namespace", "const", "let", "var",
+            ],
+            Language::Go => &["func", "type", "var", "const"],
+            Language::Java => &["class", "interface", "enum", "record"],
+            Language::Kotlin => &["fun", "class", "interface", "object", "val", "var"],
+            Language::CSharp => &["class", "struct", "interface", "enum", "record", "namespace"],
+            Language::C => &["struct", "enum", "union"],
+            Language::Cpp => &["class", "struct", "enum", "union", "namespace"],
+            Language::Swift => &["func", "class", "struct", "enum", "protocol", "extension", "var", "let"],
+            Language::Scala => &["def", "class", "object", "trait", "val", "var"],
+            Language::Php => &["function", "class", "interface", "trait"],
+        }
+    }
+
+    /// Joins a member to the type it belongs to, e.g. "Parser::parse"
+    fn scope_separator(self) -> &'static str {
+        match self {
+            Language::Rust | Language::Cpp | Language::Php => "::",
+            _ => ".",
+        }
+    }
+
+    /// Python blocks are delimited by indentation, everything else by braces
+    fn uses_indentation(self) -> bool {
+        self == Language::Python
+    }
+}
+
+struct Line<'a> {
+    text: &'a str,
+    /// Brace depth at the start of the line, or indentation for Python
+    level: usize,
+    /// Brace depth at the end of the line
+    level_after: usize,
+    /// Whether the line starts inside a string, comment or open bracket
+    continued: bool,
+    char_start: usize,
+    char_end: usize,
+    byte_start: usize,
+    byte_end: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Lexical {
+    Code,
+    BlockComment,
+    Str { quote: char, triple: bool },
+}
+
+/// Split code into lines, tracking nesting through strings and comments
+fn scan(text: &str, language: Language) -> Vec<Line<'_>> {
+    let mut state = Lexical::Code;
+    let mut depth = 0usize;
+    let (mut graphemes, mut bytes) = (0, 0);
+    let mut lines = Vec::new();
+
+    for raw in text.split_inclusive('\n') {
+        let content = raw.trim_end_matches(['\r', '\n']);
+        let continued = state != Lexical::Code || (language.uses_indentation() && depth > 0);
+        let indent: usize = content
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum();
+        let level = if language.uses_indentation() { indent } else { depth };
+
+        let chars: Vec<char> = content.chars().collect();
+        let at = |i: usize| chars.get(i).copied();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match state {
+                Lexical::Code => match c {
+                    '#' if language.uses_indentation() => break,
+                    '/' if at(i + 1) == Some('/') && !language.uses_indentation() => break,
+                    '/' if at(i + 1) == Some('*') && !language.uses_indentation() => {
+                        state = Lexical::BlockComment;
+                        i += 1;
+                    }
+                    // A quote in Rust is a char literal only when it closes right away; otherwise a lifetime
+                    '\'' if language == Language::Rust => {
+                        if at(i + 1) == Some('\\') {
+                            i += chars[i + 2..].iter().position(|&c| c == '\'').map_or(chars.len(), |p| p + 2);
+                        } else if at(i + 2) == Some('\'') {
+                            i += 2;
+                        }
+                    }
+                    '"' | '\'' | '`' => {
+                        let triple = language.uses_indentation() && at(i + 1) == Some(c) && at(i + 2) == Some(c);
+                        if c != '`' || matches!(language, Language::TypeScript | Language::JavaScript | Language::Go) {
+                            state = Lexical::Str { quote: c, triple };
+                        }
+                        if triple {
+                            i += 2;
+                        }
+                    }
+                    '{' => depth += 1,
+                    '(' | '[' if language.uses_indentation() => depth += 1,
+                    '}' => depth = depth.saturating_sub(1),
+                    ')' | ']' if language.uses_indentation() => depth = depth.saturating_sub(1),
+                    _ => {}
+                },
+                Lexical::BlockComment => {
+                    if c == '*' && at(i + 1) == Some('/') {
+                        state = Lexical::Code;
+                        i += 1;
+                    }
+                }
+                Lexical::Str { quote, triple } => {
+                    if c == '\\' {
+                        i += 1;
+                    } else if c == quote && (!triple || (at(i + 1) == Some(quote) && at(i + 2) == Some(quote))) {
+                        state = Lexical::Code;
+                        if triple {
+                            i += 2;
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+        // Only triple-quoted strings, template literals and block comments run past the line end
+        if let Lexical::Str { quote, triple: false } = state {
+            if quote != '`' {
+                state = Lexical::Code;
+            }
+        }
+
+        lines.push(Line {
+            text: content,
+            level,
+            level_after: depth,
+            continued,
+            char_start: graphemes,
+            char_end: graphemes + content.graphemes(true).count(),
+            byte_start: bytes,
+            byte_end: bytes + content.len(),
+        });
+        graphemes += raw.graphemes(true).count();
+        bytes += raw.len();
+    }
+
+    lines
+}
+
+/// Comments, attributes and decorators, which belong to the definition after them
+fn is_trivia(line: &str) -> bool {
+    let line = line.trim_start();
+    ["//", "/*", "*", "#", "@"].iter().any(|prefix| line.starts_with(prefix))
+}
+
+/// Whether `line` starts a new unit after `prev`, the last non-blank line before it
+fn starts_unit(language: Language, prev: &Line, line: &Line, level: usize, blank_between: bool) -> bool {
+    if line.level != level || line.continued {
+        return false;
+    }
+
+    if language.uses_indentation() {
+        let first_word = line.text.split(|c: char| !c.is_alphanumeric()).find(|w| !w.is_empty());
+        let continues_block = matches!(first_word, Some("else" | "elif" | "except" | "finally"));
+        let after_trivia = prev.level == level && !prev.continued && is_trivia(prev.text);
+        !after_trivia && !continues_block
+    } else {
+        let ended = prev.text.trim_end().ends_with(['}', ';']) && !is_trivia(prev.text);
+        prev.level_after == level && (blank_between || ended)
+    }
+}
+
+/// Split lines into units at `level`: definitions with their leading comments,
+/// or runs of other statements
+fn units(lines: &[Line], range: Range<usize>, level: usize, language: Language) -> Vec<Range<usize>> {
+    let mut units = Vec::new();
+    let mut start = range.start;
+    let mut prev: Option<usize> = None;
+    let mut blank_between = false;
+
+    for i in range.clone() {
+        if lines[i].text.trim().is_empty() {
+            blank_between = true;
+            continue;
+        }
+        if let Some(p) = prev {
+            if starts_unit(language, &lines[p], &lines[i], level, blank_between) {
+                units.push(start..i);
+                start = i;
+            }
+        }
+        prev = Some(i);
+        blank_between = false;
+    }
+    if start < range.end {
+        units.push(start..range.end);
+    }
+
+    units
+}
+
+/// Members of a type or module, each named within it; None if it has fewer than two
+///
+/// The opening lines go with the first member and the closing brace with the last.
+fn members(lines: &[Line], unit: &Range<usize>, level: usize, language: Language) -> Option<Vec<(Range<usize>, usize)>> {
+    let (body, member_level) = if language.uses_indentation() {
+        let start = unit.clone().find(|&i| {
+            let line = &lines[i];
+            !line.continued && line.level > level && !line.text.trim().is_empty()
+        })?;
+        (start..unit.end, lines[start].level)
+    } else {
+        let open = unit.clone().find(|&i| lines[i].level_after > level)?;
+        let close = unit.clone().rev().find(|&i| !lines[i].text.trim().is_empty())?;
+        (open + 1..close.max(open + 1), level + 1)
+    };
+
+    let mut members = units(lines, body, member_level, language);
+    if members.len() < 2 {
+        return None;
+    }
+    members[0].start = unit.start;
+    if let Some(last) = members.last_mut() {
+        last.end = unit.end;
+    }
+    Some(members.into_iter().map(|m| (m, member_level)).collect())
+}
+
+/// Name of the definition a unit holds, e.g. "parse" or "Parser"
+fn symbol_name(lines: &[Line], unit: Range<usize>, level: usize, language: Language) -> Option<String> {
+    let header = unit
+        .map(|i| &lines[i])
+        .filter(|line| line.level == level && !line.continued && !is_trivia(line.text))
+        .map(|line| line.text.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let has_body = header.contains('{');
+    let header = match language.uses_indentation() {
+        true => header.as_str(),
+        false => header.split('{').next().unwrap_or(""),
+    };
+
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let words: Vec<(usize, &str)> = header
+        .split_word_bound_indices()
+        .filter(|(_, w)| w.chars().all(is_ident))
+        .collect();
+    let keywords = language.definition_keywords();
+
+    if let Some(mut k) = words.iter().position(|(_, w)| keywords.contains(w)) {
+        // `const fn`, `export const enum`: the last keyword in a run names the kind
+        while words.get(k + 1).is_some_and(|(_, w)| keywords.contains(w)) {
+            k += 1;
+        }
+        let (position, keyword) = words[k];
+        let rest = &header[position + keyword.len()..];
+        return match keyword {
+            "impl" => impl_target(rest),
+            "func" if language == Language::Go => go_func(rest),
+            _ => words.get(k + 1).map(|(_, w)| w.to_string()),
+        };
+    }
+
+    // Functions and methods without a keyword: the name before the parameter list
+    if language.uses_indentation() || !has_body {
+        return None;
+    }
+    let before = header[..header.find('(')?].trim_end();
+    let name = before
+        .rsplit(|c: char| !is_ident(c) && c != ':')
+        .next()?
+        .trim_matches(':');
+    let control = ["if", "for", "while", "switch", "catch", "return", "foreach", "using", "lock"];
+    (!name.is_empty() && !control.contains(&name)).then(|| name.to_string())
+}
+
+/// Type an `impl` block is for, without generics: `impl<T> Display for Wrapper<T>` → "Wrapper"
+fn impl_target(rest: &str) -> Option<String> {
+    let mut rest = rest.trim_start();
+    if rest.starts_with('<') {
+        let mut depth = 0;
+        let end = rest.char_indices().find_map(|(i, c)| {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i + 1)
+        })?;
+        rest = &rest[end..];
+    }
+    let target = rest.rsplit(" for ").next()?.split(" where").next()?;
+    let target = target.split('<').next()?.trim();
+    (!target.is_empty()).then(|| target.to_string())
+}
+
+/// Go function name, prefixed by its receiver type for methods: `func (p *Parser) Parse` → "Parser.Parse"
+fn go_func(rest: &str) -> Option<String> {
+    let rest = rest.trim_start();
+    let ident = |s: &str| -> Option<String> {
+        let name: String = s.trim_start().chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+        (!name.is_empty()).then_some(name)
+    };
+
+    match rest.strip_prefix('(') {
+        Some(receiver) => {
+            let (receiver, after) = receiver.split_once(')')?;
+            let receiver_type = receiver.split_whitespace().last()?.trim_start_matches('*');
+            let receiver_type = receiver_type.split('[').next()?;
+            Some(format!("{}.{}", receiver_type, ident(after)?))
+        }
+        None => ident(rest),
+    }
+}
+
+/// A run of lines to become (part of) a chunk, with the definitions in it
+struct Piece {
+    lines: Range<usize>,
+    symbols: Vec<String>,
+}
+
+struct Chunker<'a> {
+    text: &'a str,
+    lines: Vec<Line<'a>>,
+    language: Language,
+    strategy: &'a dyn ChunkingStrategy,
+}
+
+impl Chunker<'_> {
+    fn fits(&self, lines: &Range<usize>) -> bool {
+        let (first, last) = (&self.lines[lines.start], &self.lines[lines.end - 1]);
+        self.strategy.measure(&self.text[first.byte_start..last.byte_end]) <= self.strategy.limit()
+    }
+
+    /// Turn a unit into pieces, splitting it into members while it's too big
+    fn unit(&self, unit: Range<usize>, level: usize, parent: Option<&str>, pieces: &mut Vec<Piece>) {
+        let name = symbol_name(&self.lines, unit.clone(), level, self.language);
+        let symbol = match (parent, name) {
+            (Some(parent), Some(name)) => Some(format!("{}{}{}", parent, self.language.scope_separator(), name)),
+            (Some(parent), None) => Some(parent.to_string()),
+            (None, name) => name,
+        };
+
+        if self.fits(&unit) {
+            pieces.push(Piece { lines: unit, symbols: symbol.into_iter().collect() });
+        } else if let Some(members) = members(&self.lines, &unit, level, self.language) {
+            for (member, member_level) in members {
+                self.unit(member, member_level, symbol.as_deref(), pieces);
+            }
+        } else {
+            self.split_lines(unit, symbol, pieces);
+        }
+    }
+
+    /// Last resort for long definitions: whole lines packed up to the limit
+    fn split_lines(&self, unit: Range<usize>, symbol: Option<String>, pieces: &mut Vec<Piece>) {
+        let mut start = unit.start;
+        for end in unit.start + 1..=unit.end {
+            if end > start + 1 && !self.fits(&(start..end)) {
+                pieces.push(Piece { lines: start..end - 1, symbols: symbol.iter().cloned().collect() });
+                start = end - 1;
+            }
+        }
+        pieces.push(Piece { lines: start..unit.end, symbols: symbol.into_iter().collect() });
+    }
+}
+
+/// Chunk source code at definition boundaries
+///
+/// Top-level functions, types and impl blocks are packed into chunks up to
+/// the strategy's limit without ever splitting one. A definition that is too
+/// big on its own is split into its methods or members, then into whole
+/// lines. Chunks record the definitions they hold and their line range.
+pub fn chunk_code(text: &str, language: Language, strategy: &dyn ChunkingStrategy) -> Vec<TextChunk> {
+    let lines = scan(text, language);
+    let chunker = Chunker { text, lines, language, strategy };
+
+    let mut pieces = Vec::new();
+    for unit in units(&chunker.lines, 0..chunker.lines.len(), 0, language) {
+        chunker.unit(unit, 0, None, &mut pieces);
+    }
+
+    // Pack neighbouring pieces while they fit, leaving out blank lines at the edges
+    let mut packed: Vec<Piece> = Vec::new();
+    for mut piece in pieces {
+        let is_blank = |i: usize| chunker.lines[i].text.trim().is_empty();
+        while piece.lines.start < piece.lines.end && is_blank(piece.lines.start) {
+            piece.lines.start += 1;
+        }
+        while piece.lines.start < piece.lines.end && is_blank(piece.lines.end - 1) {
+            piece.lines.end -= 1;
+        }
+        if piece.lines.is_empty() {
+            continue;
+        }
+
+        match packed.last_mut() {
+            Some(last) if chunker.fits(&(last.lines.start..piece.lines.end)) => {
+                last.lines.end = piece.lines.end;
+                for symbol in piece.symbols {
+                    if last.symbols.last() != Some(&symbol) {
+                        last.symbols.push(symbol);
+                    }
+                }
+            }
+            _ => packed.push(piece),
+        }
+    }
+
+    packed
+        .into_iter()
+        .enumerate()
+        .map(|(index, piece)| {
+            let (first, last) = (&chunker.lines[piece.lines.start], &chunker.lines[piece.lines.end - 1]);
+            TextChunk {
+                text: text[first.byte_start..last.byte_end].to_string(),
+                index,
+                char_start: first.char_start,
+                char_end: last.char_end,
+                byte_start: first.byte_start,
+                byte_end: last.byte_end,
+                page_start: None,
+                page_end: None,
+                section: None,
+                line_start: Some(piece.lines.start as u32 + 1),
+                line_end: Some(piece.lines.end as u32),
+                symbol: (!piece.symbols.is_empty()).then(|| piece.symbols.join(", ")),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunking::ChunkingConfig;
+
+    fn chunk(text: &str, language: Language, chunk_size: usize) -> Vec<(String, u32, u32)> {
+        let strategy = ChunkingConfig::FixedWidth { chunk_size, overlap: 0 }.strategy(None).unwrap();
+        chunk_code(text, language, strategy.as_ref())
+            .into_iter()
+            .map(|c| {
+                assert_eq!(text[c.byte_start..c.byte_end], c.text);
+                (c.symbol.unwrap_or_default(), c.line_start.unwrap(), c.line_end.unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_rust() {
+        let source = r#"use std::fmt;
+
+/// A parser
+pub struct Parser<'a> {
+    input: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input }
+    }
+
+    /// Parse the input
+    pub fn parse(&self) -> Vec<char> {
+        let quote = '{';
+        self.input.chars().filter(|&c| c != quote).collect()
+    }
+}
+
+const fn limit() -> usize {
+    "}".len()
+}
+"#;
+
+        assert_eq!(
+            chunk(source, Language::Rust, 160),
+            vec![
+                ("Parser".to_string(), 1, 6),
+                ("Parser::new".to_string(), 8, 11),
+                ("Parser::parse".to_string(), 13, 18),
+                ("limit".to_string(), 20, 22),
+            ]
+        );
+        assert_eq!(chunk(source, Language::Rust, 1000), vec![("Parser, limit".to_string(), 1, 22)]);
+    }
+
+    #[test]
+    fn test_chunk_python() {
+        let source = r#"import os
+
+@dataclass
+class Config:
+    path: str
+
+    def load(self):
+        text = """
+def not_a_function():
+"""
+        return text
+
+    def save(self):
+        pass
+
+
+def main(
+    argv,
+):
+    print(Config("x").load())
+"#;
+
+        assert_eq!(
+            chunk(source, Language::Python, 90),
+            vec![
+                ("Config".to_string(), 1, 5),
+                ("Config.load".to_string(), 7, 11),
+                ("Config.save, main".to_string(), 13, 20),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_symbol_names() {
+        let name = |header: &str, language| {
+            let lines = scan(header, language);
+            symbol_name(&lines, 0..lines.len(), 0, language)
+        };
+        assert_eq!(name("impl<T: Debug> fmt::Display for Wrapper<T> {", Language::Rust).as_deref(), Some("Wrapper"));
+        assert_eq!(name("func (s *Server) Start(ctx context.Context) error {", Language::Go).as_deref(), Some("Server.Start"));
+        assert_eq!(name("export const handler = async (req) => {", Language::TypeScript).as_deref(), Some("handler"));
+        assert_eq!(name("public static int Count(List<int> items) {", Language::CSharp).as_deref(), Some("Count"));
+        assert_eq!(name("void Widget::draw(Canvas &c) const {", Language::Cpp).as_deref(), Some("Widget::draw"));
+        assert_eq!(name("if (ready) {", Language::Java), None);
+    }
+}
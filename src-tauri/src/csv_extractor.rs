@@ -0,0 +1,119 @@
+use crate::error::{AppResult, ExtractionError};
+use crate::file_processor::{labelled_row, ExtractedText, TextLocation};
+use csv::ReaderBuilder;
+use std::collections::HashSet;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Candidate delimiters; on a tie the later one wins, so comma is the default
+const DELIMITERS: &[u8] = b"|\t;,";
+
+/// Lines sampled when detecting the delimiter
+const SNIFF_LINES: usize = 20;
+
+/// Pick the delimiter that splits the sample into the most consistent columns
+pub fn detect_delimiter(text: &str) -> u8 {
+    let sample = text.lines().take(SNIFF_LINES).collect::<Vec<_>>().join("\n");
+
+    DELIMITERS
+        .iter()
+        .copied()
+        .max_by_key(|&delimiter| {
+            let widths: Vec<usize> = ReaderBuilder::new()
+                .delimiter(delimiter)
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(sample.as_bytes())
+                .records()
+                .filter_map(Result::ok)
+                .map(|record| record.len())
+                .collect();
+            let first = widths.first().copied().unwrap_or(0);
+            let consistent = widths.iter().filter(|&&w| w == first).count();
+            (first > 1, consistent, first)
+        })
+        .unwrap_or(b',')
+}
+
+/// Whether a first row reads as column names: distinct, non-empty and non-numeric
+fn looks_like_header(cells: &[String]) -> bool {
+    let is_number = |cell: &str| cell.replace(',', "").parse::<f64>().is_ok();
+    let distinct = cells.iter().collect::<HashSet<_>>().len() == cells.len();
+    distinct && cells.iter().all(|cell| !cell.is_empty() && !is_number(cell))
+}
+
+/// Convert CSV into one line per record
+///
+/// With a header row, records are written as `column: value` pairs so every
+/// chunk carries its column names; without one, cells are joined by " | ".
+/// Each line is marked with the source line its record starts on.
+pub fn csv_to_text(text: &str) -> AppResult<ExtractedText> {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut reader = ReaderBuilder::new()
+        .delimiter(detect_delimiter(text))
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| ExtractionError::malformed("CSV", e))?;
+        // Count lines up to the record itself; the reader's own count skips blank lines
+        let byte = record.position().map_or(0, |p| p.byte() as usize);
+        let start = text.len() - text[byte..].trim_start_matches(['\r', '\n']).len();
+        let line = text[..start].matches('\n').count() as u32 + 1;
+        let cells: Vec<String> = record.iter().map(|cell| cell.trim().to_string()).collect();
+        if cells.iter().any(|cell| !cell.is_empty()) {
+            rows.push((line, cells));
+        }
+    }
+
+    let header = match rows.first() {
+        Some((_, cells)) if rows.len() > 1 && looks_like_header(cells) => Some(rows.remove(0).1),
+        _ => None,
+    };
+
+    let mut extracted = ExtractedText::default();
+    let mut graphemes = 0;
+    for (line, cells) in &rows {
+        let rendered = match &header {
+            Some(header) => labelled_row(header, cells),
+            None => cells.iter().filter(|c| !c.is_empty()).cloned().collect::<Vec<_>>().join(" | "),
+        };
+        extracted.locations.push(TextLocation {
+            char_start: graphemes,
+            page: None,
+            section: None,
+            line: Some(*line),
+        });
+        graphemes += rendered.graphemes(true).count() + 1;
+        extracted.text.push_str(&rendered);
+        extracted.text.push('\n');
+    }
+
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_delimiter() {
+        assert_eq!(detect_delimiter("name;city\nAda;London\nAlan;Wilmslow"), b';');
+        assert_eq!(detect_delimiter("a\tb\tc\n1\t2\t3"), b'\t');
+        assert_eq!(detect_delimiter("\"Smith, J\",42\n\"Doe, A\",37"), b',');
+        assert_eq!(detect_delimiter("just one column\nof text"), b',');
+    }
+
+    #[test]
+    fn test_csv_to_text() {
+        let extracted = csv_to_text("\u{feff}name;city;born\nAda;London;1815\n\nAlan;;1912\n").unwrap();
+        assert_eq!(extracted.text, "name: Ada; city: London; born: 1815\nname: Alan; born: 1912\n");
+        let lines: Vec<_> = extracted.locations.iter().map(|l| (l.char_start, l.line)).collect();
+        assert_eq!(lines, vec![(0, Some(2)), (36, Some(4))]);
+
+        // Numeric first rows are data, not headers
+        let extracted = csv_to_text("1,2\n3,4\n").unwrap();
+        assert_eq!(extracted.text, "1 | 2\n3 | 4\n");
+    }
+}
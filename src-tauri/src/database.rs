@@ -1,6 +1,9 @@
 use crate::error::{AppError, AppResult};
+use crate::chunking::ChunkingConfig;
+use crate::reranker::RerankSettings;
 use serde::{Deserialize, Serialize};
 use sqlx::{sqlite::SqlitePool, Row};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use uuid::Uuid;
 
@@ -80,6 +83,9 @@ pub struct ChatMessage {
     pub role: String, // 'user', 'assistant', 'system'
     pub content: String,
     pub created_at: String,
+    /// Knowledge-base passages the message was grounded on
+    #[serde(default)]
+    pub sources: Vec<MessageSource>,
 }
 
 /// New chat message
@@ -88,6 +94,53 @@ pub struct NewChatMessage {
     pub session_id: String,
     pub role: String,
     pub content: String,
+    #[serde(default)]
+    pub sources: Vec<MessageSource>,
+}
+
+/// Passage cited by a chat message (a RAG context passage without its text)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSource {
+    pub file_name: String,
+    pub training_data_id: String,
+    pub chunk_index: i32,
+    #[serde(default)]
+    pub char_start: i32,
+    #[serde(default)]
+    pub char_end: i32,
+    #[serde(default)]
+    pub byte_start: i32,
+    #[serde(default)]
+    pub byte_end: i32,
+    #[serde(default)]
+    pub page_start: Option<i32>,
+    #[serde(default)]
+    pub page_end: Option<i32>,
+    #[serde(default)]
+    pub section: Option<String>,
+    /// Source lines (or CSV rows) the passage spans
+    #[serde(default)]
+    pub line_start: Option<i32>,
+    #[serde(default)]
+    pub line_end: Option<i32>,
+    /// Programming language and symbols of a code passage
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub score: f32,
+}
+
+/// Per-model retrieval settings, stored as JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelSettings {
+    #[serde(default)]
+    pub rerank: RerankSettings,
+    /// How uploads are chunked unless the upload picks a strategy
+    /// (None keeps fixed-width chunks of the upload's chunk size)
+    #[serde(default)]
+    pub chunking: Option<ChunkingConfig>,
 }
 
 /// Database service for SQLite
@@ -199,6 +252,86 @@ impl Database {
         .await
         .map_err(|e| AppError::Storage(format!("Failed to create chat_messages table: {}", e)))?;
 
+        // Message sources table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS message_sources (
+                message_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                file_name TEXT NOT NULL,
+                training_data_id TEXT NOT NULL,
+                chunk_index INTEGER NOT NULL,
+                char_start INTEGER NOT NULL,
+                char_end INTEGER NOT NULL,
+                byte_start INTEGER NOT NULL,
+                byte_end INTEGER NOT NULL,
+                page_start INTEGER,
+                page_end INTEGER,
+                section TEXT,
+                line_start INTEGER,
+                line_end INTEGER,
+                language TEXT,
+                symbol TEXT,
+                score REAL NOT NULL,
+                PRIMARY KEY (message_id, position),
+                FOREIGN KEY (message_id) REFERENCES chat_messages(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AppError::Storage(format!("Failed to create message_sources table: {}", e)))?;
+
+        // Code and CSV citations, added after message sources were first stored
+        self.add_missing_columns(
+            "message_sources",
+            &[
+                ("line_start", "INTEGER"),
+                ("line_end", "INTEGER"),
+                ("language", "TEXT"),
+                ("symbol", "TEXT"),
+            ],
+        )
+        .await?;
+
+        // Model settings table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS model_settings (
+                model_id TEXT PRIMARY KEY NOT NULL,
+                settings TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (model_id) REFERENCES models(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AppError::Storage(format!("Failed to create model_settings table: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Add columns that a table created by an older version of the app lacks
+    async fn add_missing_columns(&self, table: &str, columns: &[(&str, &str)]) -> AppResult<()> {
+        let existing: Vec<String> = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| AppError::Storage(format!("Failed to read {} columns: {}", table, e)))?
+            .iter()
+            .map(|row| row.get("name"))
+            .collect();
+
+        for (name, sql_type) in columns {
+            if existing.iter().any(|column| column == name) {
+                continue;
+            }
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, name, sql_type))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| AppError::Storage(format!("Failed to add {}.{}: {}", table, name, e)))?;
+        }
+
         Ok(())
     }
 
@@ -371,6 +504,44 @@ impl Database {
         })
     }
 
+    /// Get the training data record of a file, adding one if the model has none yet
+    ///
+    /// Records are keyed on the file's path, so re-uploading a file reuses its
+    /// record while two files that share a name get one each.
+    pub async fn get_or_add_training_data(&self, data: NewTrainingData) -> AppResult<TrainingData> {
+        let row = sqlx::query("SELECT * FROM training_data WHERE model_id = ? AND file_path = ? ORDER BY created_at ASC LIMIT 1")
+            .bind(&data.model_id)
+            .bind(&data.file_path)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| AppError::Storage(format!("Failed to get training data: {}", e)))?;
+
+        match row {
+            Some(row) => Ok(TrainingData {
+                id: row.get("id"),
+                model_id: row.get("model_id"),
+                file_name: row.get("file_name"),
+                file_path: row.get("file_path"),
+                file_type: row.get("file_type"),
+                chunks_count: row.get("chunks_count"),
+                created_at: row.get("created_at"),
+            }),
+            None => self.add_training_data(data).await,
+        }
+    }
+
+    /// Record how many chunks a training data file was split into
+    pub async fn set_training_data_chunks(&self, id: &str, chunks_count: i32) -> AppResult<()> {
+        sqlx::query("UPDATE training_data SET chunks_count = ? WHERE id = ?")
+            .bind(chunks_count)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AppError::Storage(format!("Failed to update training data: {}", e)))?;
+
+        Ok(())
+    }
+
     /// List training data for a model
     pub async fn list_training_data(&self, model_id: &str) -> AppResult<Vec<TrainingData>> {
         let rows = sqlx::query("SELECT * FROM training_data WHERE model_id = ? ORDER BY created_at DESC")
@@ -513,6 +684,39 @@ impl Database {
         .await
         .map_err(|e| AppError::Storage(format!("Failed to add chat message: {}", e)))?;
 
+        for (position, source) in message.sources.iter().enumerate() {
+            sqlx::query(
+                r#"
+                INSERT INTO message_sources (
+                    message_id, position, file_name, training_data_id, chunk_index,
+                    char_start, char_end, byte_start, byte_end, page_start, page_end, section,
+                    line_start, line_end, language, symbol, score
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&id)
+            .bind(position as i64)
+            .bind(&source.file_name)
+            .bind(&source.training_data_id)
+            .bind(source.chunk_index)
+            .bind(source.char_start)
+            .bind(source.char_end)
+            .bind(source.byte_start)
+            .bind(source.byte_end)
+            .bind(source.page_start)
+            .bind(source.page_end)
+            .bind(&source.section)
+            .bind(source.line_start)
+            .bind(source.line_end)
+            .bind(&source.language)
+            .bind(&source.symbol)
+            .bind(source.score)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AppError::Storage(format!("Failed to add message source: {}", e)))?;
+        }
+
         // Update session's updated_at
         let session_now = chrono::Utc::now().to_rfc3339();
         sqlx::query("UPDATE chat_sessions SET updated_at = ? WHERE id = ?")
@@ -528,6 +732,7 @@ impl Database {
             role: message.role,
             content: message.content,
             created_at: now,
+            sources: message.sources,
         })
     }
 
@@ -539,17 +744,98 @@ impl Database {
             .await
             .map_err(|e| AppError::Storage(format!("Failed to get chat messages: {}", e)))?;
 
+        let source_rows = sqlx::query(
+            r#"
+            SELECT s.* FROM message_sources s
+            JOIN chat_messages m ON m.id = s.message_id
+            WHERE m.session_id = ?
+            ORDER BY s.message_id, s.position
+            "#,
+        )
+        .bind(session_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::Storage(format!("Failed to get message sources: {}", e)))?;
+
+        let mut sources: HashMap<String, Vec<MessageSource>> = HashMap::new();
+        for row in source_rows {
+            sources
+                .entry(row.get("message_id"))
+                .or_default()
+                .push(MessageSource {
+                    file_name: row.get("file_name"),
+                    training_data_id: row.get("training_data_id"),
+                    chunk_index: row.get("chunk_index"),
+                    char_start: row.get("char_start"),
+                    char_end: row.get("char_end"),
+                    byte_start: row.get("byte_start"),
+                    byte_end: row.get("byte_end"),
+                    page_start: row.get("page_start"),
+                    page_end: row.get("page_end"),
+                    section: row.get("section"),
+                    line_start: row.get("line_start"),
+                    line_end: row.get("line_end"),
+                    language: row.get("language"),
+                    symbol: row.get("symbol"),
+                    score: row.get("score"),
+                });
+        }
+
         let messages = rows
             .into_iter()
-            .map(|row| ChatMessage {
-                id: row.get("id"),
-                session_id: row.get("session_id"),
-                role: row.get("role"),
-                content: row.get("content"),
-                created_at: row.get("created_at"),
+            .map(|row| {
+                let id: String = row.get("id");
+                ChatMessage {
+                    sources: sources.remove(&id).unwrap_or_default(),
+                    id,
+                    session_id: row.get("session_id"),
+                    role: row.get("role"),
+                    content: row.get("content"),
+                    created_at: row.get("created_at"),
+                }
             })
             .collect();
 
         Ok(messages)
     }
+
+    // ============ MODEL SETTINGS ============
+
+    /// Get settings for a model (defaults if none have been saved)
+    pub async fn get_model_settings(&self, model_id: &str) -> AppResult<ModelSettings> {
+        let row = sqlx::query("SELECT settings FROM model_settings WHERE model_id = ?")
+            .bind(model_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| AppError::Storage(format!("Failed to get model settings: {}", e)))?;
+
+        match row {
+            Some(row) => {
+                let settings: String = row.get("settings");
+                Ok(serde_json::from_str(&settings)?)
+            }
+            None => Ok(ModelSettings::default()),
+        }
+    }
+
+    /// Save settings for a model
+    pub async fn save_model_settings(&self, model_id: &str, settings: &ModelSettings) -> AppResult<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO model_settings (model_id, settings, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(model_id) DO UPDATE SET settings = excluded.settings, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(model_id)
+        .bind(serde_json::to_string(settings)?)
+        .bind(&now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AppError::Storage(format!("Failed to save model settings: {}", e)))?;
+
+        Ok(())
+    }
 }
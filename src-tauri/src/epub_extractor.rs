@@ -0,0 +1,353 @@
+use crate::error::{AppError, AppResult, ExtractionError};
+use crate::file_processor::{DocumentMetadata, ExtractedText, TextLocation};
+use crate::html_extractor;
+use crate::zip_package::{attr, parent_dir, resolve_href, ZipPackage};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use scraper::{Html, Selector};
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A manifest entry of the OPF package
+struct ManifestItem {
+    /// Path inside the archive
+    path: String,
+    media_type: String,
+    properties: String,
+}
+
+/// The parts of the OPF package needed to read the book in order
+#[derive(Default)]
+struct Package {
+    title: Option<String>,
+    manifest: HashMap<String, ManifestItem>,
+    /// Manifest ids of the linear spine, in reading order
+    spine: Vec<String>,
+    /// Manifest id of the EPUB 2 NCX table of contents
+    ncx: Option<String>,
+}
+
+/// Convert an EPUB into plain text, one chapter after another in spine order
+///
+/// Each chapter starts a section named after its table-of-contents entry,
+/// falling back to its first heading and then to "Chapter N". Headings inside
+/// a chapter become sub-sections, e.g. "Chapter 4: The Storm > Aftermath".
+pub fn epub_to_text(data: &[u8]) -> AppResult<ExtractedText> {
+    let mut archive = ZipPackage::open(data)?;
+
+    let container = archive.read("META-INF/container.xml")?;
+    let opf_path = rootfile_path(&container)?;
+    let package = parse_package(&archive.read(&opf_path)?, parent_dir(&opf_path))?;
+    let toc = table_of_contents(&mut archive, &package);
+
+    let mut text = String::new();
+    let mut locations = Vec::new();
+    let mut graphemes = 0;
+    let mut chapter_number = 0;
+
+    for id in &package.spine {
+        let Some(item) = package.manifest.get(id) else { continue };
+        if !item.media_type.contains("html") {
+            continue;
+        }
+        let Ok(content) = archive.read(&item.path) else { continue };
+
+        let chapter = html_extractor::html_to_text(&content);
+        if chapter.text.trim().is_empty() {
+            continue;
+        }
+        chapter_number += 1;
+
+        let title = toc
+            .get(&item.path)
+            .cloned()
+            .or_else(|| chapter.locations.first().and_then(|l| l.section.clone()))
+            .unwrap_or_else(|| format!("Chapter {}", chapter_number));
+
+        if !text.is_empty() {
+            text.push_str("\n\n");
+            graphemes += 2;
+        }
+        locations.push(TextLocation {
+            char_start: graphemes,
+            page: None,
+            section: Some(title.clone()),
+            line: None,
+        });
+        for location in chapter.locations {
+            // A heading opening the chapter is already covered by the chapter's own marker
+            if location.char_start == 0 && location.section.as_ref() == Some(&title) {
+                continue;
+            }
+            let section = location.section.map(|path| {
+                if path == title || path.starts_with(&format!("{} > ", title)) {
+                    path
+                } else {
+                    format!("{} > {}", title, path)
+                }
+            });
+            locations.push(TextLocation {
+                char_start: graphemes + location.char_start,
+                page: None,
+                section,
+                line: None,
+            });
+        }
+
+        graphemes += chapter.text.graphemes(true).count();
+        text.push_str(&chapter.text);
+    }
+
+    Ok(ExtractedText {
+        text,
+        locations,
+        metadata: DocumentMetadata {
+            title: package.title,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+}
+
+fn epub_error(e: impl std::fmt::Display) -> AppError {
+    ExtractionError::malformed("EPUB", e).into()
+}
+
+/// Path of the OPF package named by META-INF/container.xml
+fn rootfile_path(container: &str) -> AppResult<String> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event().map_err(epub_error)? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = attr(&e, b"full-path") {
+                    return Ok(path);
+                }
+            }
+            Event::Eof => return Err(epub_error("container.xml names no package file")),
+            _ => {}
+        }
+    }
+}
+
+fn parse_package(opf: &str, base_dir: &str) -> AppResult<Package> {
+    let mut reader = Reader::from_str(opf);
+    let mut package = Package::default();
+    let mut in_title = false;
+
+    loop {
+        match reader.read_event().map_err(epub_error)? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"title" if package.title.is_none() => in_title = true,
+                b"item" => {
+                    if let (Some(id), Some(href)) = (attr(&e, b"id"), attr(&e, b"href")) {
+                        package.manifest.insert(
+                            id,
+                            ManifestItem {
+                                path: resolve_href(base_dir, &href),
+                                media_type: attr(&e, b"media-type").unwrap_or_default(),
+                                properties: attr(&e, b"properties").unwrap_or_default(),
+                            },
+                        );
+                    }
+                }
+                b"spine" => package.ncx = attr(&e, b"toc"),
+                // Non-linear items (footnote popups, answer keys) sit outside the reading order
+                b"itemref" if attr(&e, b"linear").as_deref() != Some("no") => {
+                    package.spine.extend(attr(&e, b"idref"));
+                }
+                _ => {}
+            },
+            Event::Text(t) if in_title => {
+                let title = t.unescape().map_err(epub_error)?;
+                let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !title.is_empty() {
+                    package.title = Some(title);
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"title" => in_title = false,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if package.spine.is_empty() {
+        return Err(epub_error("package has an empty spine"));
+    }
+    Ok(package)
+}
+
+/// Chapter titles keyed by archive path, from the EPUB 3 nav document or the EPUB 2 NCX
+///
+/// Only the first entry pointing into each file is kept, so a chapter is
+/// named by its own entry rather than one of its sub-sections.
+fn table_of_contents(archive: &mut ZipPackage, package: &Package) -> HashMap<String, String> {
+    let mut toc = HashMap::new();
+
+    let nav = package
+        .manifest
+        .values()
+        .find(|item| item.properties.split_whitespace().any(|p| p == "nav"));
+    if let Some(nav) = nav {
+        if let Ok(content) = archive.read(&nav.path) {
+            for (href, title) in nav_entries(&content) {
+                toc.entry(resolve_href(parent_dir(&nav.path), &href)).or_insert(title);
+            }
+        }
+    }
+
+    let ncx = package.ncx.as_ref().and_then(|id| package.manifest.get(id));
+    if let (true, Some(ncx)) = (toc.is_empty(), ncx) {
+        if let Ok(content) = archive.read(&ncx.path) {
+            for (src, title) in ncx_entries(&content) {
+                toc.entry(resolve_href(parent_dir(&ncx.path), &src)).or_insert(title);
+            }
+        }
+    }
+
+    toc
+}
+
+/// Links of the `epub:type="toc"` nav element, in document order
+fn nav_entries(xhtml: &str) -> Vec<(String, String)> {
+    let document = Html::parse_document(xhtml);
+    let navs = Selector::parse("nav").expect("valid selector");
+    let links = Selector::parse("a[href]").expect("valid selector");
+
+    let toc_nav = document
+        .select(&navs)
+        .find(|nav| nav.value().attr("epub:type") == Some("toc"))
+        .or_else(|| document.select(&navs).next());
+
+    toc_nav
+        .map(|nav| {
+            nav.select(&links)
+                .filter_map(|a| {
+                    let title = a.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ");
+                    let href = a.value().attr("href")?.to_string();
+                    (!title.is_empty()).then_some((href, title))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `navPoint` labels and targets of an NCX file, in document order
+fn ncx_entries(ncx: &str) -> Vec<(String, String)> {
+    let mut reader = Reader::from_str(ncx);
+    let mut entries = Vec::new();
+    let mut in_label = false;
+    let mut label = String::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) if e.local_name().as_ref() == b"navLabel" => {
+                in_label = true;
+                label.clear();
+            }
+            Ok(Event::End(e)) if e.local_name().as_ref() == b"navLabel" => in_label = false,
+            Ok(Event::Text(t)) if in_label => {
+                if let Ok(text) = t.unescape() {
+                    label.push_str(&text);
+                }
+            }
+            Ok(Event::Start(e) | Event::Empty(e)) if e.local_name().as_ref() == b"content" => {
+                let title = label.split_whitespace().collect::<Vec<_>>().join(" ");
+                if let (Some(src), false) = (attr(&e, b"src"), title.is_empty()) {
+                    entries.push((src, title));
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    fn build_epub(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    const CONTAINER: &str = r#"<?xml version="1.0"?>
+        <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+            <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+        </container>"#;
+
+    #[test]
+    fn test_epub_to_text() {
+        let opf = r#"<?xml version="1.0"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>The Long Voyage</dc:title></metadata>
+                <manifest>
+                    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                    <item id="c2" href="text/ch%202.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="c1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="notes" href="text/notes.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="cover" href="cover.jpg" media-type="image/jpeg"/>
+                </manifest>
+                <spine><itemref idref="c1"/><itemref idref="cover"/><itemref idref="c2"/><itemref idref="notes" linear="no"/></spine>
+            </package>"#;
+        let nav = r#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+                <nav epub:type="toc"><ol>
+                    <li><a href="text/ch1.xhtml">Chapter 1: Departure</a></li>
+                    <li><a href="text/ch1.xhtml#harbour">The Harbour</a></li>
+                </ol></nav>
+            </body></html>"#;
+        let epub = build_epub(&[
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", CONTAINER),
+            ("OEBPS/content.opf", opf),
+            ("OEBPS/nav.xhtml", nav),
+            ("OEBPS/text/ch1.xhtml", "<html><body><p>We set sail.</p><h2 id=\"harbour\">The Harbour</h2><p>Gulls.</p></body></html>"),
+            ("OEBPS/text/ch 2.xhtml", "<html><body><h1>Storm</h1><p>Waves.</p></body></html>"),
+            ("OEBPS/text/notes.xhtml", "<html><body><p>Footnotes</p></body></html>"),
+        ]);
+
+        let extracted = epub_to_text(&epub).unwrap();
+
+        assert_eq!(extracted.metadata.title.as_deref(), Some("The Long Voyage"));
+        assert_eq!(extracted.text, "We set sail.\n\n## The Harbour\n\nGulls.\n\n# Storm\n\nWaves.");
+
+        let sections: Vec<_> = extracted
+            .locations
+            .iter()
+            .map(|l| (l.char_start, l.section.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![
+                (0, "Chapter 1: Departure"),
+                (14, "Chapter 1: Departure > The Harbour"),
+                (38, "Storm"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ncx_entries() {
+        let ncx = r#"<ncx><navMap>
+                <navPoint id="p1"><navLabel><text>Chapter 4: The Storm</text></navLabel><content src="../text/ch4.xhtml#start"/></navPoint>
+            </navMap></ncx>"#;
+        assert_eq!(
+            ncx_entries(ncx),
+            vec![("../text/ch4.xhtml#start".to_string(), "Chapter 4: The Storm".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_missing_container() {
+        let epub = build_epub(&[("mimetype", "application/epub+zip")]);
+        assert!(epub_to_text(&epub).is_err());
+    }
+}
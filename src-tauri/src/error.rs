@@ -17,6 +17,35 @@ pub enum AppError {
     #[error("LanceDB error: {0}")]
     LanceDB(String),
 
+    #[error("Embedding model mismatch: this knowledge base was built with '{table_model}' but '{requested_model}' was used")]
+    EmbeddingModelMismatch {
+        table_model: String,
+        requested_model: String,
+    },
+
+    #[error("Embedding dimension mismatch: this knowledge base was built with '{table_model}' ({expected} dimensions) but '{requested_model}' produced {actual} dimensions")]
+    EmbeddingDimensionMismatch {
+        table_model: String,
+        requested_model: String,
+        expected: i32,
+        actual: i32,
+    },
+
+    #[error("Incompatible export: {0}")]
+    IncompatibleExport(String),
+
+    #[error("Invalid chunking settings: {0}")]
+    InvalidChunking(String),
+
+    #[error("Tokenizer unavailable for {0}")]
+    Tokenizer(String),
+
+    #[error("Vector maintenance is already running")]
+    MaintenanceRunning,
+
+    #[error("Extraction failed: {0}")]
+    Extraction(#[from] ExtractionError),
+
     #[error("Network error: {0}")]
     Network(String),
 
@@ -36,4 +65,44 @@ pub enum AppError {
     Unknown(String),
 }
 
+/// Why text couldn't be extracted from a file
+#[derive(Error, Debug)]
+pub enum ExtractionError {
+    #[error("unsupported file type: {0}")]
+    UnsupportedType(String),
+
+    #[error("file content is not {expected}")]
+    ContentMismatch { expected: String },
+
+    #[error("file is {size_mb:.1} MB, over the {max_mb} MB limit")]
+    FileTooLarge { size_mb: f64, max_mb: u64 },
+
+    #[error("document expands to more than {max_bytes} bytes")]
+    DecompressedTooLarge { max_bytes: u64 },
+
+    #[error("document has {pages} pages, over the {max_pages} page limit")]
+    TooManyPages { pages: usize, max_pages: u32 },
+
+    #[error("gave up after {0} seconds")]
+    Timeout(u64),
+
+    #[error("{0} is encrypted")]
+    Encrypted(String),
+
+    #[error("malformed {format}: {reason}")]
+    Malformed { format: String, reason: String },
+
+    #[error("no text could be extracted from {0}")]
+    NoText(String),
+}
+
+impl ExtractionError {
+    pub fn malformed(format: &str, reason: impl std::fmt::Display) -> Self {
+        Self::Malformed {
+            format: format.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
 pub type AppResult<T> = Result<T, AppError>;
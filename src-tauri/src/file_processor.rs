@@ -1,7 +1,20 @@
-use crate::error::{AppError, AppResult};
-use lopdf::Document;
+use crate::error::{AppError, AppResult, ExtractionError};
+use crate::chunking::{ChunkingConfig, ChunkingStrategy};
+use crate::code_chunker::{self, Language};
+use crate::csv_extractor;
+use crate::epub_extractor;
+use crate::html_extractor;
+use crate::markdown_chunker;
+use crate::office_extractor;
+use crate::pdf_extractor;
+use crate::zip_package::{self, ZipPackage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 use unicode_segmentation::UnicodeSegmentation;
 
 /// Supported file types
@@ -12,6 +25,14 @@ pub enum FileType {
     Txt,
     Md,
     Csv,
+    Html,
+    Epub,
+    Pptx,
+    Xlsx,
+    Odt,
+    Ods,
+    Odp,
+    Code(Language),
 }
 
 impl FileType {
@@ -23,9 +44,87 @@ impl FileType {
             "txt" => Ok(FileType::Txt),
             "md" => Ok(FileType::Md),
             "csv" => Ok(FileType::Csv),
-            _ => Err(AppError::Unknown(format!("Unsupported file type: {}", ext))),
+            "html" | "htm" => Ok(FileType::Html),
+            "epub" => Ok(FileType::Epub),
+            "pptx" => Ok(FileType::Pptx),
+            "xlsx" | "xlsm" => Ok(FileType::Xlsx),
+            "odt" => Ok(FileType::Odt),
+            "ods" => Ok(FileType::Ods),
+            "odp" => Ok(FileType::Odp),
+            other => Language::from_extension(other)
+                .map(FileType::Code)
+                .ok_or_else(|| ExtractionError::UnsupportedType(ext.to_string()).into()),
         }
     }
+
+    /// Detect file type from a path's extension
+    pub fn of_path(file_path: &Path) -> AppResult<Self> {
+        let extension = file_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| ExtractionError::UnsupportedType("no file extension".to_string()))?;
+
+        Self::from_extension(extension)
+    }
+
+    /// Detect file type from content, falling back to the path's extension
+    ///
+    /// PDFs and zip-based documents are recognised by their signature, so a
+    /// renamed file still goes to the right extractor. Text formats have no
+    /// signature and go by extension, but binary content is refused for them,
+    /// as is a binary extension on content that doesn't match it.
+    pub fn detect(data: &[u8], file_path: &Path) -> AppResult<Self> {
+        if let Some(file_type) = Self::sniff(data) {
+            return Ok(file_type);
+        }
+
+        let file_type = Self::of_path(file_path)?;
+        let expected = match file_type {
+            FileType::Pdf => "PDF".to_string(),
+            _ if file_type.is_zip() => format!("{:?}", file_type).to_uppercase(),
+            // Text never contains NUL bytes, which binary formats are full of
+            _ if data.iter().take(8192).any(|&b| b == 0) => "text".to_string(),
+            _ => return Ok(file_type),
+        };
+        Err(ExtractionError::ContentMismatch { expected }.into())
+    }
+
+    /// File type given by a file's magic bytes, for the binary formats
+    fn sniff(data: &[u8]) -> Option<Self> {
+        // Readers accept a PDF header anywhere in the first kilobyte
+        if data[..data.len().min(1024)].windows(5).any(|w| w == b"%PDF-") {
+            return Some(FileType::Pdf);
+        }
+        if !data.starts_with(b"PK\x03\x04") {
+            return None;
+        }
+
+        // EPUB and OpenDocument name their media type in a `mimetype` entry
+        let mut package = ZipPackage::open(data).ok()?;
+        match package.read("mimetype").ok().as_deref().map(str::trim) {
+            Some("application/epub+zip") => return Some(FileType::Epub),
+            Some("application/vnd.oasis.opendocument.text") => return Some(FileType::Odt),
+            Some("application/vnd.oasis.opendocument.spreadsheet") => return Some(FileType::Ods),
+            Some("application/vnd.oasis.opendocument.presentation") => return Some(FileType::Odp),
+            _ => {}
+        }
+        [
+            ("word/document.xml", FileType::Docx),
+            ("ppt/presentation.xml", FileType::Pptx),
+            ("xl/workbook.xml", FileType::Xlsx),
+        ]
+        .into_iter()
+        .find(|(part, _)| package.contains(part))
+        .map(|(_, file_type)| file_type)
+    }
+
+    /// Whether the format is a zip package
+    fn is_zip(self) -> bool {
+        matches!(
+            self,
+            FileType::Docx | FileType::Epub | FileType::Pptx | FileType::Xlsx | FileType::Odt | FileType::Ods | FileType::Odp
+        )
+    }
 }
 
 /// Text chunk with metadata
@@ -33,164 +132,451 @@ impl FileType {
 pub struct TextChunk {
     pub text: String,
     pub index: usize,
+    /// Grapheme offsets into the extracted text
     pub char_start: usize,
     pub char_end: usize,
+    /// Byte offsets into the extracted text
+    pub byte_start: usize,
+    pub byte_end: usize,
+    /// PDF pages the chunk spans (1-based)
+    pub page_start: Option<u32>,
+    pub page_end: Option<u32>,
+    /// Nearest preceding heading or chapter
+    pub section: Option<String>,
+    /// Source lines (or CSV rows) the chunk spans (1-based)
+    pub line_start: Option<u32>,
+    pub line_end: Option<u32>,
+    /// Functions, types or impl blocks in a code chunk, e.g. "Parser::parse"
+    pub symbol: Option<String>,
+}
+
+impl TextChunk {
+    /// Text sent to the embedding model
+    pub fn embedding_text(&self) -> String {
+        embedding_text(&self.text, self.section.as_deref().or(self.symbol.as_deref()))
+    }
+}
+
+/// Prefix chunk text with its heading path or code symbol, e.g. "Setup > Linux\n\nRun the installer"
+///
+/// The path is embedded along with the text so a chunk is found by the
+/// section it belongs to, but it is not part of the stored chunk text.
+pub fn embedding_text(text: &str, section: Option<&str>) -> String {
+    match section {
+        Some(section) => format!("{}\n\n{}", section, text),
+        None => text.to_string(),
+    }
+}
+
+/// Marks where a page, section or source line begins in extracted text
+///
+/// A marker applies from `char_start` until the next marker of the same kind.
+#[derive(Debug, Clone, Default)]
+pub struct TextLocation {
+    pub char_start: usize,
+    pub page: Option<u32>,
+    pub section: Option<String>,
+    pub line: Option<u32>,
+}
+
+/// Text of one page of a paged document (PDF)
+#[derive(Debug, Clone, Serialize)]
+pub struct PageText {
+    /// 1-based page number
+    pub number: u32,
+    pub text: String,
+}
+
+/// Document-level properties found during extraction
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Creation date as RFC 3339
+    pub created: Option<String>,
+    /// Number of pages, for paged formats
+    pub page_count: Option<u32>,
+    /// Programming language of source code, e.g. "rust"
+    pub language: Option<String>,
+}
+
+/// Extracted text with page and section markers
+#[derive(Debug, Clone, Default)]
+pub struct ExtractedText {
+    pub text: String,
+    pub locations: Vec<TextLocation>,
+    pub metadata: DocumentMetadata,
+    /// Each page's text, for paged formats
+    pub pages: Vec<PageText>,
+}
+
+/// Headings enclosing the current position while walking a document
+#[derive(Debug, Default)]
+pub struct HeadingPath {
+    stack: Vec<(u8, String)>,
+}
+
+impl HeadingPath {
+    /// Enter a heading at `level` (1 = top) and return the full path, e.g. "Setup > Linux"
+    pub fn enter(&mut self, level: u8, title: &str) -> String {
+        while self.stack.last().is_some_and(|(l, _)| *l >= level) {
+            self.stack.pop();
+        }
+        self.stack.push((level, title.to_string()));
+        self.stack
+            .iter()
+            .map(|(_, title)| title.as_str())
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+/// Builds extracted text line by line, recording heading markers as it goes
+///
+/// Headings are written as markdown `#` lines so structure survives into
+/// the plain text, and each one starts a section named by its heading path.
+#[derive(Debug, Default)]
+pub struct TextBuilder {
+    text: String,
+    /// Length of `text` in graphemes
+    graphemes: usize,
+    locations: Vec<TextLocation>,
+    headings: HeadingPath,
+}
+
+impl TextBuilder {
+    /// Append a line, skipping it if blank
+    pub fn line(&mut self, line: &str) {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            return;
+        }
+        self.write(line);
+        self.write("\n");
+    }
+
+    /// End the current paragraph with a blank line
+    pub fn blank_line(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with("\n\n") {
+            self.write("\n");
+        }
+    }
+
+    /// Start a section with a heading at `level` (1 = top)
+    pub fn heading(&mut self, level: u8, title: &str) {
+        let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        if title.is_empty() {
+            return;
+        }
+
+        self.blank_line();
+        self.locations.push(TextLocation {
+            char_start: self.graphemes,
+            page: None,
+            section: Some(self.headings.enter(level, &title)),
+            line: None,
+        });
+        self.write(&format!("{} {}\n", "#".repeat(level.max(1) as usize), title));
+        self.blank_line();
+    }
+
+    pub fn finish(self, metadata: DocumentMetadata) -> ExtractedText {
+        ExtractedText {
+            text: self.text.trim_end().to_string(),
+            locations: self.locations,
+            metadata,
+            ..Default::default()
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        self.graphemes += text.graphemes(true).count();
+        self.text.push_str(text);
+    }
+}
+
+/// Render a table row as `Header: value` pairs, e.g. "Item: RAM; Minimum: 8 GB"
+///
+/// Empty cells are left out; cells without a header are kept as bare values.
+pub fn labelled_row(header: &[String], cells: &[String]) -> String {
+    cells
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(i, value)| match header.get(i).filter(|h| !h.is_empty()) {
+            Some(label) => format!("{}: {}", label, value),
+            None => value.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Bounds on the work extraction may do for one file
+///
+/// These guard against decompression bombs and pathological PDFs; any field
+/// left out takes its default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractionLimits {
+    /// Largest file accepted, in MB
+    pub max_file_size_mb: u64,
+    /// Total size the parts of a zip-based document may expand to
+    pub max_decompressed_bytes: u64,
+    /// Most pages read from a PDF
+    pub max_pages: u32,
+    /// Time allowed for extracting one file
+    pub timeout_secs: u64,
+}
+
+impl Default for ExtractionLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size_mb: 100,
+            max_decompressed_bytes: 500 * 1024 * 1024,
+            max_pages: 3000,
+            timeout_secs: 120,
+        }
+    }
 }
 
 /// File processing service
-pub struct FileProcessor;
+#[derive(Clone)]
+pub struct FileProcessor {
+    limits: ExtractionLimits,
+}
 
 impl FileProcessor {
     pub fn new() -> Self {
-        Self
+        Self::with_limits(ExtractionLimits::default())
+    }
+
+    pub fn with_limits(limits: ExtractionLimits) -> Self {
+        Self { limits }
     }
 
     /// Extract text from a file based on its type
     pub fn extract_text(&self, file_path: &Path) -> AppResult<String> {
-        // Get file extension
-        let extension = file_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .ok_or_else(|| AppError::Unknown("No file extension".to_string()))?;
-
-        let file_type = FileType::from_extension(extension)?;
+        Ok(self.extract_document(file_path)?.text)
+    }
 
-        match file_type {
-            FileType::Pdf => self.extract_pdf(file_path),
-            FileType::Docx => self.extract_docx(file_path),
-            FileType::Txt | FileType::Md | FileType::Csv => self.extract_plain_text(file_path),
-        }
+    /// Extract text along with page and section markers
+    pub fn extract_document(&self, file_path: &Path) -> AppResult<ExtractedText> {
+        Ok(self.extract(file_path)?.1)
     }
 
-    /// Extract text from PDF
-    fn extract_pdf(&self, file_path: &Path) -> AppResult<String> {
-        let doc = Document::load(file_path)
-            .map_err(|e| AppError::Unknown(format!("Failed to load PDF: {}", e)))?;
+    /// Check a file's size, detect its type and extract it within the time limit
+    ///
+    /// A parser stuck on a pathological file can't be interrupted, so it is
+    /// left to finish on its own thread and its result is dropped. Waiting for
+    /// it blocks the caller, so async code should call in via `spawn_blocking`.
+    fn extract(&self, file_path: &Path) -> AppResult<(FileType, ExtractedText)> {
+        let max_mb = self.limits.max_file_size_mb;
+        if !self.validate_file_size(file_path, max_mb)? {
+            let size_mb = self.get_file_size(file_path)? as f64 / (1024.0 * 1024.0);
+            return Err(ExtractionError::FileTooLarge { size_mb, max_mb }.into());
+        }
 
-        let mut text = String::new();
+        let file_data = fs::read(file_path)
+            .map_err(|e| AppError::Io(e))?;
+        let file_type = FileType::detect(&file_data, file_path)?;
+        if file_type.is_zip() {
+            zip_package::check_expanded_size(&file_data, self.limits.max_decompressed_bytes)?;
+        }
 
-        // Get the number of pages
-        let pages = doc.get_pages();
+        let (sender, receiver) = mpsc::channel();
+        let processor = self.clone();
+        thread::spawn(move || {
+            // The receiver is gone once the caller has given up
+            let _ = sender.send(processor.extract_data(&file_data, file_type));
+        });
+
+        let timeout = self.limits.timeout_secs;
+        match receiver.recv_timeout(Duration::from_secs(timeout)) {
+            Ok(extracted) => Ok((file_type, extracted?)),
+            Err(RecvTimeoutError::Timeout) => Err(ExtractionError::Timeout(timeout).into()),
+            Err(RecvTimeoutError::Disconnected) => Err(ExtractionError::malformed(
+                &format!("{:?} file", file_type),
+                "the extractor crashed",
+            )
+            .into()),
+        }
+    }
 
-        for (page_num, _) in pages.iter() {
-            if let Ok(page_content) = doc.extract_text(&[*page_num]) {
-                text.push_str(&page_content);
-                text.push('\n');
+    fn extract_data(&self, file_data: &[u8], file_type: FileType) -> AppResult<ExtractedText> {
+        match file_type {
+            FileType::Pdf => self.extract_pdf(file_data),
+            FileType::Html => self.extract_html(file_data),
+            FileType::Epub => self.extract_epub(file_data),
+            FileType::Docx | FileType::Pptx | FileType::Xlsx | FileType::Odt | FileType::Ods | FileType::Odp => {
+                self.extract_office(file_data, file_type)
+            }
+            FileType::Csv => csv_extractor::csv_to_text(&self.extract_plain_text(file_data)?),
+            FileType::Md => {
+                let text = self.extract_plain_text(file_data)?;
+                Ok(ExtractedText {
+                    locations: markdown_chunker::heading_locations(&text),
+                    text,
+                    ..Default::default()
+                })
             }
+            FileType::Code(language) => Ok(ExtractedText {
+                text: self.extract_plain_text(file_data)?,
+                metadata: DocumentMetadata {
+                    language: Some(language.name().to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            FileType::Txt => Ok(ExtractedText {
+                text: self.extract_plain_text(file_data)?,
+                ..Default::default()
+            }),
         }
+    }
 
-        if text.trim().is_empty() {
-            return Err(AppError::Unknown(
-                "No text could be extracted from PDF".to_string(),
-            ));
+    /// Extract text from PDF, page by page
+    fn extract_pdf(&self, file_data: &[u8]) -> AppResult<ExtractedText> {
+        let extracted = pdf_extractor::pdf_to_text(file_data, self.limits.max_pages)?;
+        if extracted.text.trim().is_empty() {
+            return Err(ExtractionError::NoText("PDF".to_string()).into());
         }
 
-        Ok(text)
+        Ok(extracted)
     }
 
-    /// Extract text from DOCX
-    fn extract_docx(&self, file_path: &Path) -> AppResult<String> {
-        let file_data = fs::read(file_path)
-            .map_err(|e| AppError::Io(e))?;
+    /// Extract text from HTML, keeping headings, lists and tables readable
+    fn extract_html(&self, file_data: &[u8]) -> AppResult<ExtractedText> {
+        let extracted = html_extractor::html_to_text(&String::from_utf8_lossy(file_data));
+        if extracted.text.trim().is_empty() {
+            return Err(ExtractionError::NoText("HTML".to_string()).into());
+        }
 
-        let docx = docx_rs::read_docx(&file_data)
-            .map_err(|e| AppError::Unknown(format!("Failed to read DOCX: {}", e)))?;
-
-        let mut text = String::new();
-
-        // Extract text from document body
-        for child in &docx.document.children {
-            match child {
-                docx_rs::DocumentChild::Paragraph(para) => {
-                    for child in &para.children {
-                        if let docx_rs::ParagraphChild::Run(run) = child {
-                            for child in &run.children {
-                                if let docx_rs::RunChild::Text(txt) = child {
-                                    text.push_str(&txt.text);
-                                }
-                            }
-                        }
-                    }
-                    text.push('\n');
-                }
-                _ => {}
-            }
+        Ok(extracted)
+    }
+
+    /// Extract text from an EPUB, chapter by chapter in reading order
+    fn extract_epub(&self, file_data: &[u8]) -> AppResult<ExtractedText> {
+        let extracted = epub_extractor::epub_to_text(file_data)?;
+        if extracted.text.trim().is_empty() {
+            return Err(ExtractionError::NoText("EPUB".to_string()).into());
         }
 
-        if text.trim().is_empty() {
-            return Err(AppError::Unknown(
-                "No text could be extracted from DOCX".to_string(),
-            ));
+        Ok(extracted)
+    }
+
+    /// Extract text from Word documents, presentations, spreadsheets and OpenDocument files
+    fn extract_office(&self, file_data: &[u8], file_type: FileType) -> AppResult<ExtractedText> {
+        let extracted = match file_type {
+            FileType::Docx => office_extractor::docx_to_text(file_data)?,
+            FileType::Pptx => office_extractor::pptx_to_text(file_data)?,
+            FileType::Xlsx => office_extractor::spreadsheet_to_text(file_data, "docProps/core.xml")?,
+            FileType::Ods => office_extractor::spreadsheet_to_text(file_data, "meta.xml")?,
+            FileType::Odt => office_extractor::odt_to_text(file_data)?,
+            FileType::Odp => office_extractor::odp_to_text(file_data)?,
+            _ => unreachable!("not an office format: {:?}", file_type),
+        };
+        if extracted.text.trim().is_empty() {
+            return Err(ExtractionError::NoText(format!("{:?} file", file_type)).into());
         }
 
-        Ok(text)
+        Ok(extracted)
     }
 
-    /// Extract text from plain text files (TXT, MD, CSV)
-    fn extract_plain_text(&self, file_path: &Path) -> AppResult<String> {
-        fs::read_to_string(file_path)
-            .map_err(|e| AppError::Io(e))
+    /// Decode plain text files (TXT, MD, CSV, source code)
+    fn extract_plain_text(&self, file_data: &[u8]) -> AppResult<String> {
+        String::from_utf8(file_data.to_vec())
+            .map_err(|_| ExtractionError::malformed("text file", "not valid UTF-8").into())
     }
 
-    /// Chunk text into smaller pieces
+    /// Chunk text into fixed-width pieces of `chunk_size` graphemes
     pub fn chunk_text(
         &self,
         text: &str,
         chunk_size: usize,
         overlap: usize,
-    ) -> Vec<TextChunk> {
-        let mut chunks = Vec::new();
-
-        // Use grapheme clusters for proper unicode handling
-        let graphemes: Vec<&str> = text.graphemes(true).collect();
-
-        if graphemes.is_empty() {
-            return chunks;
-        }
-
-        let mut start = 0;
-        let mut chunk_index = 0;
-
-        while start < graphemes.len() {
-            let end = std::cmp::min(start + chunk_size, graphemes.len());
-
-            // Extract chunk
-            let chunk_graphemes = &graphemes[start..end];
-            let chunk_text: String = chunk_graphemes.iter().map(|&s| s).collect();
-
-            // Skip empty chunks
-            if !chunk_text.trim().is_empty() {
-                chunks.push(TextChunk {
-                    text: chunk_text,
-                    index: chunk_index,
-                    char_start: start,
-                    char_end: end,
-                });
-                chunk_index += 1;
-            }
+    ) -> AppResult<Vec<TextChunk>> {
+        let strategy = ChunkingConfig::FixedWidth { chunk_size, overlap }.strategy(None)?;
+        Ok(strategy.chunk(text))
+    }
 
-            // Move to next chunk with overlap
-            if end >= graphemes.len() {
-                break;
+    /// Chunk text at line boundaries, packing whole lines up to the strategy's limit
+    ///
+    /// Used for record-per-line text such as CSV, where a chunk must never
+    /// split a row. A line over the limit becomes a chunk of its own.
+    pub fn chunk_lines(&self, text: &str, strategy: &dyn ChunkingStrategy) -> Vec<TextChunk> {
+        let mut chunks: Vec<TextChunk> = Vec::new();
+        // Grapheme and byte spans of the chunk being filled, plus its size
+        let mut current: Option<(usize, usize, usize, usize, usize)> = None;
+        let (mut graphemes, mut bytes) = (0, 0);
+
+        let mut flush = |span: (usize, usize, usize, usize, usize)| {
+            let (char_start, byte_start, char_end, byte_end, _) = span;
+            chunks.push(TextChunk {
+                text: text[byte_start..byte_end].to_string(),
+                index: chunks.len(),
+                char_start,
+                char_end,
+                byte_start,
+                byte_end,
+                page_start: None,
+                page_end: None,
+                section: None,
+                line_start: None,
+                line_end: None,
+                symbol: None,
+            });
+        };
+
+        for line in text.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\r', '\n']);
+            let (start, byte_start) = (graphemes, bytes);
+            let end = start + content.graphemes(true).count();
+            graphemes += line.graphemes(true).count();
+            bytes += line.len();
+            if content.trim().is_empty() {
+                continue;
             }
 
-            start += chunk_size - overlap;
+            let size = strategy.measure(content);
+            current = match current {
+                Some((s, bs, _, _, used)) if used + size <= strategy.limit() => {
+                    Some((s, bs, end, byte_start + content.len(), used + size))
+                }
+                full => {
+                    if let Some(span) = full {
+                        flush(span);
+                    }
+                    Some((start, byte_start, end, byte_start + content.len(), size))
+                }
+            };
+        }
+        if let Some(span) = current {
+            flush(span);
         }
 
         chunks
     }
 
     /// Process a file: extract text and chunk it
+    ///
+    /// CSV, Markdown and source code keep their records, sections and
+    /// definitions whole, sized by the strategy's measure; everything else is
+    /// split by the strategy itself.
     pub fn process_file(
         &self,
         file_path: &Path,
-        chunk_size: usize,
-        overlap: usize,
-    ) -> AppResult<(String, Vec<TextChunk>)> {
-        let text = self.extract_text(file_path)?;
-        let chunks = self.chunk_text(&text, chunk_size, overlap);
-
-        Ok((text, chunks))
+        strategy: &dyn ChunkingStrategy,
+    ) -> AppResult<(ExtractedText, Vec<TextChunk>)> {
+        let (file_type, extracted) = self.extract(file_path)?;
+        let mut chunks = match file_type {
+            FileType::Csv => self.chunk_lines(&extracted.text, strategy),
+            FileType::Md => markdown_chunker::chunk_markdown(&extracted.text, strategy),
+            FileType::Code(language) => code_chunker::chunk_code(&extracted.text, language, strategy),
+            _ => strategy.chunk(&extracted.text),
+        };
+        locate_chunks(&mut chunks, &extracted.locations);
+
+        Ok((extracted, chunks))
     }
 
     /// Get file size in bytes
@@ -200,6 +586,13 @@ impl FileProcessor {
         Ok(metadata.len())
     }
 
+    /// Hex-encoded SHA-256 of a file's contents
+    pub fn hash_file(&self, file_path: &Path) -> AppResult<String> {
+        let file_data = fs::read(file_path)
+            .map_err(|e| AppError::Io(e))?;
+        Ok(hex::encode(Sha256::digest(&file_data)))
+    }
+
     /// Validate file size (max 10MB by default)
     pub fn validate_file_size(&self, file_path: &Path, max_size_mb: u64) -> AppResult<bool> {
         let size_bytes = self.get_file_size(file_path)?;
@@ -210,12 +603,7 @@ impl FileProcessor {
 
     /// Get file info
     pub fn get_file_info(&self, file_path: &Path) -> AppResult<FileInfo> {
-        let extension = file_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .ok_or_else(|| AppError::Unknown("No file extension".to_string()))?;
-
-        let file_type = FileType::from_extension(extension)?;
+        let file_type = FileType::of_path(file_path)?;
         let size_bytes = self.get_file_size(file_path)?;
 
         let file_name = file_path
@@ -233,6 +621,42 @@ impl FileProcessor {
     }
 }
 
+/// Fill in each chunk's pages, section and lines from extraction markers
+pub fn locate_chunks(chunks: &mut [TextChunk], locations: &[TextLocation]) {
+    let page_at = |pos: usize| {
+        locations
+            .iter()
+            .take_while(|l| l.char_start <= pos)
+            .filter_map(|l| l.page)
+            .last()
+    };
+    let section_at = |pos: usize| {
+        locations
+            .iter()
+            .take_while(|l| l.char_start <= pos)
+            .filter_map(|l| l.section.clone())
+            .last()
+    };
+
+    let line_at = |pos: usize| {
+        locations
+            .iter()
+            .take_while(|l| l.char_start <= pos)
+            .filter_map(|l| l.line)
+            .last()
+    };
+
+    for chunk in chunks {
+        let last = chunk.char_end.saturating_sub(1).max(chunk.char_start);
+        chunk.page_start = page_at(chunk.char_start);
+        chunk.page_end = page_at(last);
+        chunk.section = section_at(chunk.char_start);
+        // Chunkers that know their lines (source code) have filled them in already
+        chunk.line_start = line_at(chunk.char_start).or(chunk.line_start);
+        chunk.line_end = line_at(last).or(chunk.line_end);
+    }
+}
+
 /// File information
 #[derive(Debug)]
 pub struct FileInfo {
@@ -253,10 +677,49 @@ mod tests {
         let processor = FileProcessor::new();
         let text = "This is a test. This is only a test. We are testing text chunking.";
 
-        let chunks = processor.chunk_text(text, 20, 5);
+        let chunks = processor.chunk_text(text, 20, 5).unwrap();
 
         assert!(!chunks.is_empty());
         assert_eq!(chunks[0].index, 0);
+
+        // An overlap as long as the chunk would never advance
+        assert!(processor.chunk_text(text, 20, 20).is_err());
+    }
+
+    #[test]
+    fn test_chunk_lines_keeps_rows_whole() {
+        let processor = FileProcessor::new();
+        let extracted = csv_extractor::csv_to_text("id,name\n1,alpha\n2,beta\n3,gamma\n").unwrap();
+
+        // Each row estimates at 7-8 tokens, so two fit in a budget of 18
+        let strategy = ChunkingConfig::TokenCount { max_tokens: 18, overlap: 0 }.strategy(None).unwrap();
+        let mut chunks = processor.chunk_lines(&extracted.text, strategy.as_ref());
+        locate_chunks(&mut chunks, &extracted.locations);
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, "id: 1; name: alpha\nid: 2; name: beta");
+        assert_eq!((chunks[0].line_start, chunks[0].line_end), (Some(2), Some(3)));
+        assert_eq!(chunks[1].text, "id: 3; name: gamma");
+        assert_eq!((chunks[1].line_start, chunks[1].line_end), (Some(4), Some(4)));
+        assert_eq!(&extracted.text[chunks[1].byte_start..chunks[1].byte_end], chunks[1].text);
+    }
+
+    #[test]
+    fn test_locate_chunks() {
+        let processor = FileProcessor::new();
+        let text = "First page text\nSecond page text\n";
+        let locations = vec![
+            TextLocation { char_start: 0, page: Some(1), section: Some("Intro".to_string()), line: None },
+            TextLocation { char_start: 16, page: Some(2), section: None, line: None },
+        ];
+
+        let mut chunks = processor.chunk_text(text, 20, 0).unwrap();
+        locate_chunks(&mut chunks, &locations);
+
+        assert_eq!((chunks[0].page_start, chunks[0].page_end), (Some(1), Some(2)));
+        assert_eq!(chunks[0].section.as_deref(), Some("Intro"));
+        assert_eq!((chunks[0].byte_start, chunks[0].byte_end), (0, 20));
+        assert_eq!((chunks[1].page_start, chunks[1].page_end), (Some(2), Some(2)));
     }
 
     #[test]
@@ -269,7 +732,7 @@ mod tests {
         let mut file = fs::File::create(&file_path).unwrap();
         file.write_all(test_content.as_bytes()).unwrap();
 
-        let result = processor.extract_plain_text(&file_path);
+        let result = processor.extract_text(&file_path);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), test_content);
 
@@ -277,12 +740,79 @@ mod tests {
         fs::remove_file(file_path).ok();
     }
 
+    #[test]
+    fn test_hash_file() {
+        let processor = FileProcessor::new();
+        let file_path = env::temp_dir().join("test_hash_file.txt");
+        fs::write(&file_path, "same content").unwrap();
+
+        let first = processor.hash_file(&file_path).unwrap();
+        let second = processor.hash_file(&file_path).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 64);
+
+        // Cleanup
+        fs::remove_file(file_path).ok();
+    }
+
     #[test]
     fn test_file_type_detection() {
         assert!(matches!(FileType::from_extension("pdf"), Ok(FileType::Pdf)));
         assert!(matches!(FileType::from_extension("PDF"), Ok(FileType::Pdf)));
         assert!(matches!(FileType::from_extension("txt"), Ok(FileType::Txt)));
         assert!(matches!(FileType::from_extension("docx"), Ok(FileType::Docx)));
+        assert!(matches!(FileType::from_extension("htm"), Ok(FileType::Html)));
+        assert!(matches!(FileType::from_extension("XLSX"), Ok(FileType::Xlsx)));
+        assert!(matches!(FileType::from_extension("odp"), Ok(FileType::Odp)));
+        assert!(matches!(FileType::from_extension("RS"), Ok(FileType::Code(Language::Rust))));
+        assert!(matches!(FileType::from_extension("tsx"), Ok(FileType::Code(Language::TypeScript))));
         assert!(FileType::from_extension("xyz").is_err());
     }
+
+    #[test]
+    fn test_detect_by_content() {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer.start_file("word/document.xml", zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"<w:document/>").unwrap();
+        let docx = writer.finish().unwrap().into_inner();
+
+        // Content wins over a wrong extension
+        assert!(matches!(FileType::detect(&docx, Path::new("report.pdf")), Ok(FileType::Docx)));
+        assert!(matches!(FileType::detect(b"%PDF-1.7\n", Path::new("scan.txt")), Ok(FileType::Pdf)));
+        assert!(matches!(FileType::detect(b"# Notes", Path::new("notes.md")), Ok(FileType::Md)));
+
+        let mismatch = |data: &[u8], name: &str| match FileType::detect(data, Path::new(name)) {
+            Err(AppError::Extraction(ExtractionError::ContentMismatch { expected })) => expected,
+            other => panic!("expected a content mismatch, got {:?}", other),
+        };
+        assert_eq!(mismatch(b"just text", "paper.pdf"), "PDF");
+        assert_eq!(mismatch(b"\x7fELF\x02\x01\x00\x00", "notes.txt"), "text");
+    }
+
+    #[test]
+    fn test_extraction_enforces_size_limit() {
+        let processor = FileProcessor::with_limits(ExtractionLimits {
+            max_file_size_mb: 1,
+            ..Default::default()
+        });
+        let strategy = ChunkingConfig::FixedWidth { chunk_size: 1000, overlap: 0 }.strategy(None).unwrap();
+        let file_path = env::temp_dir().join("test_size_limit.txt");
+
+        fs::write(&file_path, "a".repeat(1024 * 1024)).unwrap();
+        assert!(processor.process_file(&file_path, strategy.as_ref()).is_ok());
+
+        fs::write(&file_path, "a".repeat(1024 * 1024 + 1)).unwrap();
+        let result = processor.process_file(&file_path, strategy.as_ref());
+        assert!(matches!(
+            result,
+            Err(AppError::Extraction(ExtractionError::FileTooLarge { max_mb: 1, .. }))
+        ));
+        assert!(matches!(
+            processor.extract_text(&file_path),
+            Err(AppError::Extraction(ExtractionError::FileTooLarge { max_mb: 1, .. }))
+        ));
+
+        // Cleanup
+        fs::remove_file(file_path).ok();
+    }
 }
@@ -0,0 +1,311 @@
+use crate::file_processor::{labelled_row, DocumentMetadata, ExtractedText, TextBuilder};
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// Elements whose content never reaches the extracted text
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "nav", "footer", "svg", "iframe",
+];
+
+/// Elements rendered as paragraphs separated by a blank line
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "blockquote", "section", "article", "main", "header", "aside", "figure", "address",
+    "details", "dl", "form", "fieldset",
+];
+
+/// Elements rendered on their own line
+const LINE_ELEMENTS: &[&str] = &["div", "dt", "dd", "figcaption", "summary", "legend"];
+
+/// Convert an HTML document into plain text with section markers
+///
+/// Boilerplate (scripts, styles, navigation, footers) is dropped. Headings
+/// are kept as markdown `#` lines, lists as `-` / `1.` items and tables as
+/// one line per row, labelled with the header cells when there are any.
+pub fn html_to_text(html: &str) -> ExtractedText {
+    let document = Html::parse_document(html);
+
+    let mut renderer = Renderer::default();
+    renderer.element(document.root_element());
+    renderer.end_line();
+
+    renderer.out.finish(DocumentMetadata {
+        title: page_title(&document),
+        ..Default::default()
+    })
+}
+
+/// The document's `<title>`, if it has a non-empty one
+fn page_title(document: &Html) -> Option<String> {
+    let selector = Selector::parse("title").expect("valid selector");
+    document
+        .select(&selector)
+        .next()
+        .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+        .filter(|title| !title.is_empty())
+}
+
+/// Whether an element is page chrome rather than content
+fn is_skipped(element: ElementRef) -> bool {
+    let value = element.value();
+    SKIPPED_ELEMENTS.contains(&value.name())
+        || matches!(value.attr("role"), Some("navigation" | "contentinfo"))
+        || value.attr("hidden").is_some()
+        || value.attr("aria-hidden") == Some("true")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn child_elements<'a>(element: ElementRef<'a>) -> impl Iterator<Item = ElementRef<'a>> {
+    element.children().filter_map(ElementRef::wrap)
+}
+
+/// Rows of a table, without descending into nested tables
+fn table_rows(table: ElementRef) -> Vec<ElementRef> {
+    let mut rows = Vec::new();
+    for child in child_elements(table) {
+        match child.value().name() {
+            "tr" => rows.push(child),
+            "thead" | "tbody" | "tfoot" => {
+                rows.extend(child_elements(child).filter(|row| row.value().name() == "tr"))
+            }
+            _ => {}
+        }
+    }
+    rows
+}
+
+#[derive(Default)]
+struct Renderer {
+    out: TextBuilder,
+    /// Inline content of the line being built
+    line: String,
+    /// Length of the list marker at the start of `line`
+    marker_len: usize,
+    /// Enclosing lists: None for unordered, Some(next number) for ordered
+    lists: Vec<Option<u32>>,
+    /// Depth of enclosing `<pre>` elements
+    preformatted: usize,
+}
+
+impl Renderer {
+    /// Flush the current line, dropping it if it holds nothing but a list marker
+    fn end_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        let has_content = !line[self.marker_len..].trim().is_empty();
+        self.marker_len = 0;
+        if has_content {
+            self.out.line(&line);
+        }
+    }
+
+    /// Flush the current line and leave a blank line after it
+    fn end_block(&mut self) {
+        self.end_line();
+        self.out.blank_line();
+    }
+
+    fn inline(&mut self, text: &str) {
+        if self.preformatted > 0 {
+            self.line.push_str(text);
+            return;
+        }
+
+        let words = collapse_whitespace(text);
+        let needs_space = |line: &str| !line.is_empty() && !line.ends_with(' ');
+        if text.starts_with(char::is_whitespace) && needs_space(&self.line) {
+            self.line.push(' ');
+        }
+        self.line.push_str(&words);
+        if !words.is_empty() && text.ends_with(char::is_whitespace) {
+            self.line.push(' ');
+        }
+    }
+
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.inline(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.element(child);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn element(&mut self, element: ElementRef) {
+        if is_skipped(element) {
+            return;
+        }
+
+        let name = element.value().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.heading(element, name[1..].parse().unwrap_or(1)),
+            "br" => self.end_line(),
+            "hr" => self.end_block(),
+            "ul" | "ol" => self.list(element, name == "ol"),
+            "li" => self.list_item(element),
+            "table" => self.table(element),
+            "pre" => {
+                self.end_block();
+                self.preformatted += 1;
+                self.children(element);
+                self.preformatted -= 1;
+                self.end_block();
+            }
+            _ if BLOCK_ELEMENTS.contains(&name) => {
+                self.end_block();
+                self.children(element);
+                self.end_block();
+            }
+            _ if LINE_ELEMENTS.contains(&name) => {
+                self.end_line();
+                self.children(element);
+                self.end_line();
+            }
+            _ => self.children(element),
+        }
+    }
+
+    fn heading(&mut self, element: ElementRef, level: u8) {
+        self.end_line();
+        self.out.heading(level, &element.text().collect::<String>());
+    }
+
+    fn list(&mut self, element: ElementRef, ordered: bool) {
+        self.end_line();
+        let start = element.value().attr("start").and_then(|s| s.trim().parse().ok()).unwrap_or(1);
+        self.lists.push(ordered.then_some(start));
+        self.children(element);
+        self.lists.pop();
+        if self.lists.is_empty() {
+            self.end_block();
+        }
+    }
+
+    fn list_item(&mut self, element: ElementRef) {
+        self.end_line();
+        let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+        let marker = match self.lists.last_mut() {
+            Some(Some(number)) => {
+                *number += 1;
+                format!("{}{}. ", indent, *number - 1)
+            }
+            _ => format!("{}- ", indent),
+        };
+        self.marker_len = marker.len();
+        self.line = marker;
+        self.children(element);
+        self.end_line();
+    }
+
+    fn table(&mut self, table: ElementRef) {
+        self.end_block();
+
+        if let Some(caption) = child_elements(table).find(|c| c.value().name() == "caption") {
+            self.inline(&caption.text().collect::<String>());
+            self.end_line();
+        }
+
+        let rows: Vec<(bool, Vec<String>)> = table_rows(table)
+            .into_iter()
+            .map(|row| {
+                let cells: Vec<ElementRef> = child_elements(row)
+                    .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+                    .collect();
+                let is_header = !cells.is_empty() && cells.iter().all(|cell| cell.value().name() == "th");
+                let cells = cells
+                    .iter()
+                    .map(|cell| collapse_whitespace(&cell.text().collect::<String>()))
+                    .collect();
+                (is_header, cells)
+            })
+            .filter(|(_, cells): &(bool, Vec<String>)| cells.iter().any(|cell| !cell.is_empty()))
+            .collect();
+
+        // A leading all-<th> row labels the values of every following row
+        let (header, body) = match rows.split_first() {
+            Some(((true, header), body)) => (Some(header), body),
+            _ => (None, rows.as_slice()),
+        };
+
+        for (_, cells) in body {
+            match header {
+                Some(header) => self.out.line(&labelled_row(header, cells)),
+                None => self.out.line(&cells.join(" | ")),
+            }
+        }
+        if let (Some(header), true) = (header, body.is_empty()) {
+            self.out.line(&header.join(" | "));
+        }
+
+        self.end_block();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        let html = r#"<html>
+            <head><title> Install   Guide </title><style>body { color: red }</style></head>
+            <body>
+                <nav><a href="/">Home</a> | <a href="/docs">Docs</a></nav>
+                <script>console.log("tracking")</script>
+                <h1>Setup</h1>
+                <p>Run the <strong>installer</strong>
+                   and follow the prompts.</p>
+                <h2>Linux</h2>
+                <ol>
+                    <li>Download the package</li>
+                    <li>Install it
+                        <ul><li>Debian: <code>dpkg -i</code></li><li>Fedora: <code>rpm -i</code></li></ul>
+                    </li>
+                </ol>
+                <table>
+                    <caption>Requirements</caption>
+                    <tr><th>Component</th><th>Minimum</th></tr>
+                    <tr><td>RAM</td><td>8 GB</td></tr>
+                    <tr><td>Disk</td><td>20 GB</td></tr>
+                </table>
+                <footer>Copyright 2024</footer>
+            </body>
+        </html>"#;
+
+        let extracted = html_to_text(html);
+
+        assert_eq!(extracted.metadata.title.as_deref(), Some("Install Guide"));
+        assert_eq!(
+            extracted.text,
+            "# Setup\n\n\
+             Run the installer and follow the prompts.\n\n\
+             ## Linux\n\n\
+             1. Download the package\n\
+             2. Install it\n  \
+             - Debian: dpkg -i\n  \
+             - Fedora: rpm -i\n\n\
+             Requirements\n\
+             Component: RAM; Minimum: 8 GB\n\
+             Component: Disk; Minimum: 20 GB"
+        );
+
+        let sections: Vec<_> = extracted
+            .locations
+            .iter()
+            .map(|l| (l.char_start, l.section.as_deref().unwrap()))
+            .collect();
+        assert_eq!(sections, vec![(0, "Setup"), (52, "Setup > Linux")]);
+    }
+
+    #[test]
+    fn test_table_without_header() {
+        let extracted = html_to_text("<table><tr><td>a</td><td>b</td></tr><tr><td>c</td><td>d</td></tr></table>");
+        assert_eq!(extracted.text, "a | b\nc | d");
+        assert_eq!(extracted.metadata.title, None);
+    }
+}
@@ -1,80 +1,591 @@
 use crate::encryption::EncryptionService;
 use crate::error::{AppError, AppResult};
-use arrow_array::{Float32Array, RecordBatch, RecordBatchIterator, StringArray};
-use arrow_schema::{DataType, Field, Schema};
+use crate::file_processor::embedding_text;
+use crate::quantization::{self, PrecisionComparison, StoragePrecision, VectorFormat, SCALE_COLUMN};
+use crate::retrieval::{self, ContextOptions, SearchOptions, WindowChunk};
+use crate::table_export::{self, ExportManifest, ExportSummary, ImportSummary, EXPORT_FORMAT_VERSION};
+use crate::table_metadata::{DistanceMetric, EmbeddingProvenance, TableMetadataStore};
+use crate::vector_schema::{self, CURRENT_SCHEMA_VERSION};
+use arrow_array::{Array, Float32Array, RecordBatch, RecordBatchIterator, StringArray};
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow_schema::{DataType, SchemaRef};
 use futures::stream::StreamExt;
 use lancedb::connection::Connection;
-use lancedb::query::{ExecutableQuery, QueryBase};
-use lancedb::Table;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use lancedb::table::{CompactionOptions, NewColumnTransform, OptimizeAction, OptimizeOptions};
+use lancedb::{DistanceType, Table};
+use lru::LruCache;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::sync::Arc;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::{OnceCell, OwnedMutexGuard};
+
+/// Maximum number of ids per `IN (...)` delete predicate
+const DELETE_BATCH_SIZE: usize = 500;
+
+/// Number of open table handles kept between calls
+const TABLE_CACHE_SIZE: usize = 16;
 
 /// Document chunk with embedding
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DocumentChunk {
     pub id: String,
     pub model_id: String,
     pub chunk_text: String,
     pub chunk_index: i32,
     pub file_name: String,
+    /// Training data record this chunk belongs to (falls back to the file name)
+    #[serde(default)]
+    pub training_data_id: String,
+    /// SHA-256 of the plaintext chunk (computed on store if empty)
+    #[serde(default)]
+    pub content_hash: String,
+    /// SHA-256 of the source file
+    #[serde(default)]
+    pub file_hash: String,
+    /// Grapheme offsets of the chunk in the extracted text (equal when unknown)
+    #[serde(default)]
+    pub char_start: i32,
+    #[serde(default)]
+    pub char_end: i32,
+    /// Byte offsets of the chunk in the extracted text
+    #[serde(default)]
+    pub byte_start: i32,
+    #[serde(default)]
+    pub byte_end: i32,
+    /// PDF pages the chunk spans (1-based)
+    #[serde(default)]
+    pub page_start: Option<i32>,
+    #[serde(default)]
+    pub page_end: Option<i32>,
+    /// Heading or chapter the chunk falls under
+    #[serde(default)]
+    pub section: Option<String>,
+    /// Source lines (or CSV rows) the chunk spans (1-based)
+    #[serde(default)]
+    pub line_start: Option<i32>,
+    #[serde(default)]
+    pub line_end: Option<i32>,
+    /// Programming language of a source code file
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Functions, types or impl blocks the chunk covers
+    #[serde(default)]
+    pub symbol: Option<String>,
+}
+
+impl DocumentChunk {
+    /// Text sent to the embedding model
+    pub fn embedding_text(&self) -> String {
+        embedding_text(&self.chunk_text, self.section.as_deref().or(self.symbol.as_deref()))
+    }
+}
+
+/// Identity of a chunk already stored for a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredChunkKey {
+    pub id: String,
+    pub training_data_id: String,
+    pub chunk_index: i32,
+    pub content_hash: String,
+    pub file_hash: String,
+}
+
+/// Hex-encoded SHA-256 of a chunk's text
+pub fn content_hash(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+/// Escape a string literal for use in a LanceDB filter
+pub fn sql_quote(value: &str) -> String {
+    value.replace('\'', "''")
 }
 
 /// Embedding search result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub chunk_text: String,
+    /// Vector similarity (higher is better)
     pub similarity: f32,
     pub file_name: String,
+    #[serde(default)]
+    pub training_data_id: String,
+    pub chunk_index: i32,
+    #[serde(flatten)]
+    pub location: ChunkLocation,
+    /// Relevance from the reranking stage (0-1), when it ran
+    #[serde(default)]
+    pub rerank_score: Option<f32>,
+    /// Model whose knowledge base the chunk came from
+    #[serde(default)]
+    pub model_id: String,
+    /// Similarity rescaled over the merged results (federated search only)
+    #[serde(default)]
+    pub normalized_score: Option<f32>,
+}
+
+/// A model's knowledge base to include in a federated search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchTarget {
+    pub model_id: String,
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+/// Where a chunk or passage sits in its source document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChunkLocation {
+    /// Grapheme offsets into the extracted text
+    #[serde(default)]
+    pub char_start: i32,
+    #[serde(default)]
+    pub char_end: i32,
+    /// Byte offsets into the extracted text
+    #[serde(default)]
+    pub byte_start: i32,
+    #[serde(default)]
+    pub byte_end: i32,
+    /// PDF pages covered (1-based)
+    #[serde(default)]
+    pub page_start: Option<i32>,
+    #[serde(default)]
+    pub page_end: Option<i32>,
+    /// Heading or chapter the chunk starts in
+    #[serde(default)]
+    pub section: Option<String>,
+    /// Source lines (or CSV rows) covered (1-based)
+    #[serde(default)]
+    pub line_start: Option<i32>,
+    #[serde(default)]
+    pub line_end: Option<i32>,
+    /// Programming language, for chunks of source code
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Functions, types or impl blocks the chunk covers
+    #[serde(default)]
+    pub symbol: Option<String>,
+}
+
+impl ChunkLocation {
+    fn of_chunk(chunk: &DocumentChunk) -> Self {
+        Self {
+            char_start: chunk.char_start,
+            char_end: chunk.char_end,
+            byte_start: chunk.byte_start,
+            byte_end: chunk.byte_end,
+            page_start: chunk.page_start,
+            page_end: chunk.page_end,
+            section: chunk.section.clone(),
+            line_start: chunk.line_start,
+            line_end: chunk.line_end,
+            language: chunk.language.clone(),
+            symbol: chunk.symbol.clone(),
+        }
+    }
+
+    /// Where the passage comes from, e.g. "parser.rs:120-168" for source code
+    pub fn source(&self, file_name: &str) -> String {
+        match (&self.language, self.line_start, self.line_end) {
+            (Some(_), Some(start), Some(end)) if end > start => format!("{}:{}-{}", file_name, start, end),
+            (Some(_), Some(start), _) => format!("{}:{}", file_name, start),
+            _ => file_name.to_string(),
+        }
+    }
+
+    /// Human-readable position for citations, e.g. "Chapter 4: The Storm, p. 12"
+    ///
+    /// Lines of source code are part of [`Self::source`] rather than this.
+    pub fn citation(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(section) = &self.section {
+            parts.push(section.clone());
+        }
+        if let Some(symbol) = &self.symbol {
+            parts.push(symbol.clone());
+        }
+        match (self.page_start, self.page_end) {
+            (Some(start), Some(end)) if end > start => parts.push(format!("pp. {}-{}", start, end)),
+            (Some(start), _) => parts.push(format!("p. {}", start)),
+            _ => {}
+        }
+        match (self.line_start, self.line_end) {
+            _ if self.language.is_some() => {}
+            (Some(start), Some(end)) if end > start => parts.push(format!("lines {}-{}", start, end)),
+            (Some(start), _) => parts.push(format!("line {}", start)),
+            _ => {}
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+
+    /// Location covering consecutive chunks, first to last
+    fn spanning(first: &ChunkLocation, last: &ChunkLocation) -> Self {
+        Self {
+            char_start: first.char_start,
+            char_end: last.char_end,
+            byte_start: first.byte_start,
+            byte_end: last.byte_end,
+            page_start: first.page_start.or(last.page_start),
+            page_end: last.page_end.or(first.page_end),
+            section: first.section.clone(),
+            line_start: first.line_start.or(last.line_start),
+            line_end: last.line_end.or(first.line_end),
+            language: first.language.clone().or_else(|| last.language.clone()),
+            symbol: first.symbol.clone().or_else(|| last.symbol.clone()),
+        }
+    }
+}
+
+/// One cited passage of RAG context
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextPassage {
+    pub file_name: String,
+    pub training_data_id: String,
+    /// Chunk that matched the query
     pub chunk_index: i32,
+    /// Range of chunks merged into the passage
+    pub first_chunk: i32,
+    pub last_chunk: i32,
+    #[serde(flatten)]
+    pub location: ChunkLocation,
+    /// Rerank score when available, otherwise vector similarity
+    pub score: f32,
+    pub similarity: f32,
+    pub rerank_score: Option<f32>,
+    pub text: String,
+}
+
+/// RAG context: cited passages plus the prompt text built from them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContextResult {
+    pub passages: Vec<ContextPassage>,
+    pub prompt: String,
+}
+
+impl From<DistanceMetric> for DistanceType {
+    fn from(metric: DistanceMetric) -> Self {
+        match metric {
+            DistanceMetric::L2 => DistanceType::L2,
+            DistanceMetric::Cosine => DistanceType::Cosine,
+            DistanceMetric::Dot => DistanceType::Dot,
+        }
+    }
+}
+
+/// Convert a LanceDB `_distance` into a similarity where higher is better
+fn distance_to_similarity(metric: DistanceMetric, distance: f32) -> f32 {
+    match metric {
+        DistanceMetric::Cosine => 1.0 - distance,
+        DistanceMetric::L2 => 1.0 / (1.0 + distance),
+        // LanceDB reports dot distance as 1 - dot product
+        DistanceMetric::Dot => 1.0 - distance,
+    }
+}
+
+/// Whether every vector has unit length
+fn is_normalized(embeddings: &[Vec<f32>]) -> bool {
+    embeddings.iter().all(|e| {
+        let norm = e.iter().map(|x| x * x).sum::<f32>().sqrt();
+        (norm - 1.0).abs() < 1e-3
+    })
+}
+
+/// Name of the vector table holding a model's embeddings
+///
+/// This is also the key of the model's metadata; the table actually serving
+/// queries may differ after a re-embedding migration (see `physical_table_name`).
+fn table_name(model_id: &str) -> String {
+    format!("embeddings_{}", model_id.replace('-', "_"))
+}
+
+/// Separator between a model's table name and a migration suffix
+const SHADOW_SEPARATOR: &str = "__";
+
+/// Model ID used for the shadow table of a re-embedding migration
+pub fn shadow_model_id(model_id: &str) -> String {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    format!("{}{}{}", model_id, SHADOW_SEPARATOR, &suffix[..8])
+}
+
+/// Total size in bytes of all files below a directory
+fn dir_size(path: &Path) -> u64 {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
 }
 
 /// LanceDB service for local vector storage
 pub struct LanceDBService {
     db_path: PathBuf,
     encryption: EncryptionService,
+    metadata: TableMetadataStore,
+    /// Unix millis of the last user-facing read or write
+    last_activity_ms: AtomicI64,
+    /// Connection opened on first use and shared by every call
+    connection: OnceCell<Connection>,
+    /// Handles at their latest version, keyed by physical table name
+    tables: Mutex<LruCache<String, Table>>,
+    /// Per-model locks held by callers while they modify a model's data
+    write_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
 }
 
 impl LanceDBService {
     /// Create a new LanceDB service
     pub fn new(db_path: PathBuf) -> Self {
         Self {
+            metadata: TableMetadataStore::new(&db_path),
             db_path,
             encryption: EncryptionService::new(),
+            last_activity_ms: AtomicI64::new(chrono::Utc::now().timestamp_millis()),
+            connection: OnceCell::new(),
+            tables: Mutex::new(LruCache::new(
+                NonZeroUsize::new(TABLE_CACHE_SIZE).expect("cache size is non-zero"),
+            )),
+            write_locks: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Record user activity (used to schedule maintenance while idle)
+    fn touch(&self) {
+        self.last_activity_ms
+            .store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    /// Time of the last search or ingestion
+    pub fn last_activity(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp_millis(self.last_activity_ms.load(Ordering::Relaxed))
+            .unwrap_or_else(chrono::Utc::now)
+    }
+
     /// Initialize the database
+    ///
+    /// The connection is opened once and reused by later calls.
     pub async fn initialize(&self) -> AppResult<Connection> {
-        // Ensure directory exists
-        if let Some(parent) = self.db_path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| AppError::Storage(format!("Failed to create DB directory: {}", e)))?;
-        }
+        let db = self
+            .connection
+            .get_or_try_init(|| async {
+                // Ensure directory exists
+                if let Some(parent) = self.db_path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| AppError::Storage(format!("Failed to create DB directory: {}", e)))?;
+                }
+
+                lancedb::connect(self.db_path.to_str().unwrap())
+                    .execute()
+                    .await
+                    .map_err(|e| AppError::LanceDB(format!("Failed to connect to LanceDB: {}", e)))
+            })
+            .await?;
+
+        Ok(db.clone())
+    }
+
+    /// Serialize writes to a model's table
+    ///
+    /// Ingestion, deletion, restores, checkouts, tags, maintenance and
+    /// re-embedding hold the returned guard while they modify a model's data or
+    /// version state. Reads never wait on it.
+    pub async fn write_lock(&self, model_id: &str) -> OwnedMutexGuard<()> {
+        let lock = self
+            .write_locks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(model_id.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
+    fn table_cache(&self) -> MutexGuard<'_, LruCache<String, Table>> {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
 
-        let db = lancedb::connect(self.db_path.to_str().unwrap())
+    /// Forget the cached handle of a physical table
+    ///
+    /// Called whenever a table is dropped, migrated or restored so the next
+    /// call opens it afresh.
+    fn invalidate_table(&self, table_name: &str) {
+        self.table_cache().pop(table_name);
+    }
+
+    /// Open a physical table without touching the cache
+    ///
+    /// Checking out an old version changes the handle itself, so version
+    /// browsing and restores work on handles of their own.
+    async fn open_uncached_table(&self, table_name: &str) -> AppResult<Table> {
+        self.initialize()
+            .await?
+            .open_table(table_name)
             .execute()
             .await
-            .map_err(|e| AppError::LanceDB(format!("Failed to connect to LanceDB: {}", e)))?;
+            .map_err(|e| AppError::LanceDB(format!("Failed to open table: {}", e)))
+    }
+
+    /// Open a physical table at its latest version, reusing a cached handle
+    async fn open_cached_table(&self, table_name: &str) -> AppResult<Table> {
+        let cached = self.table_cache().get(table_name).cloned();
+        if let Some(table) = cached {
+            return Ok(table);
+        }
+
+        let table = self.open_uncached_table(table_name).await?;
+        self.table_cache().put(table_name.to_string(), table.clone());
+        Ok(table)
+    }
 
-        Ok(db)
+    /// Name of the table currently serving a model
+    fn physical_table_name(&self, model_id: &str) -> AppResult<String> {
+        Ok(self
+            .metadata
+            .load(&table_name(model_id))?
+            .active_table
+            .unwrap_or_else(|| table_name(model_id)))
+    }
+
+    /// Open an existing table at its latest version
+    async fn open_latest_table(&self, model_id: &str) -> AppResult<Table> {
+        self.open_cached_table(&self.physical_table_name(model_id)?).await
     }
 
     /// Open an existing table (for read operations like search)
+    ///
+    /// Honours a version checked out with `checkout_version`.
     async fn open_table(&self, model_id: &str) -> AppResult<Table> {
-        let db = self.initialize().await?;
-        let table_name = format!("embeddings_{}", model_id.replace('-', "_"));
+        let Some(version) = self.metadata.load(&table_name(model_id))?.checked_out_version else {
+            return self.open_latest_table(model_id).await;
+        };
 
-        db.open_table(&table_name)
-            .execute()
+        let table = self
+            .open_uncached_table(&self.physical_table_name(model_id)?)
+            .await?;
+        table
+            .checkout(version)
             .await
-            .map_err(|e| AppError::LanceDB(format!("Failed to open table: {}", e)))
+            .map_err(|e| AppError::LanceDB(format!("Failed to check out version {}: {}", version, e)))?;
+
+        Ok(table)
+    }
+
+    /// Refuse writes while an old version is checked out
+    fn ensure_writable(&self, model_id: &str) -> AppResult<()> {
+        match self.metadata.load(&table_name(model_id))?.checked_out_version {
+            Some(version) => Err(AppError::LanceDB(format!(
+                "Knowledge base is checked out at version {}; restore it or return to the latest version before adding data",
+                version
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Refuse replacing a model's table while that would lose data
+    ///
+    /// Re-embedding, replacing imports and precision changes all swap in a new
+    /// table, which drops the old table's version history. They are refused
+    /// while an old version is checked out or any version is tagged.
+    pub fn ensure_replaceable(&self, model_id: &str) -> AppResult<()> {
+        self.ensure_writable(model_id)?;
+        let metadata = self.metadata.load(&table_name(model_id))?;
+        if metadata.tags.is_empty() {
+            return Ok(());
+        }
+        let tags: Vec<&str> = metadata.tags.keys().map(String::as_str).collect();
+        Err(AppError::LanceDB(format!(
+            "Replacing the knowledge base discards its version history, including tagged versions ({}); delete the tags first",
+            tags.join(", ")
+        )))
+    }
+
+    /// Open the latest version of a table for writing
+    async fn open_writable_table(&self, model_id: &str) -> AppResult<Table> {
+        self.ensure_writable(model_id)?;
+        self.open_latest_table(model_id).await
+    }
+
+    /// Check that vectors from `embedding_model` with `dimension` fit a model's table
+    ///
+    /// Returns the table's provenance, or None when the table doesn't exist yet
+    /// or predates provenance tracking (in which case only the dimension is checked).
+    async fn check_embedding_model(
+        &self,
+        model_id: &str,
+        embedding_model: &str,
+        dimension: i32,
+    ) -> AppResult<Option<EmbeddingProvenance>> {
+        let requested_model = embedding_model.to_string();
+
+        if let Some(provenance) = self.embedding_provenance(model_id).await? {
+            if embedding_model != provenance.model {
+                return Err(AppError::EmbeddingModelMismatch {
+                    table_model: provenance.model,
+                    requested_model,
+                });
+            }
+            if dimension != provenance.dimension {
+                return Err(AppError::EmbeddingDimensionMismatch {
+                    table_model: provenance.model,
+                    requested_model,
+                    expected: provenance.dimension,
+                    actual: dimension,
+                });
+            }
+            return Ok(Some(provenance));
+        }
+
+        // Legacy table: fall back to the dimension in its schema
+        if let Some(schema) = self.latest_schema(model_id).await? {
+            if let Ok(field) = schema.field_with_name("embedding") {
+                if let DataType::FixedSizeList(_, expected) = field.data_type() {
+                    if *expected != dimension {
+                        return Err(AppError::EmbeddingDimensionMismatch {
+                            table_model: "unknown".to_string(),
+                            requested_model,
+                            expected: *expected,
+                            actual: dimension,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(None)
     }
 
-    /// Get or create a table for a model with specific embedding dimension
-    async fn get_table(&self, model_id: &str, embedding_dim: i32) -> AppResult<Table> {
+    /// Schema of a model's table at its latest version, or None when it has no table
+    async fn latest_schema(&self, model_id: &str) -> AppResult<Option<SchemaRef>> {
+        let Ok(table) = self.open_latest_table(model_id).await else {
+            return Ok(None);
+        };
+        let schema = table
+            .schema()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read schema: {}", e)))?;
+
+        Ok(Some(schema))
+    }
+
+    /// Get or create a table for a model
+    ///
+    /// A new table is sized for `provenance` and carries it in its schema.
+    /// The flag is true when the table was created by this call.
+    async fn get_table(&self, model_id: &str, provenance: &EmbeddingProvenance) -> AppResult<(Table, bool)> {
+        self.ensure_writable(model_id)?;
+
         let db = self.initialize().await?;
-        let table_name = format!("embeddings_{}", model_id.replace('-', "_"));
+        let physical = self.physical_table_name(model_id)?;
 
         // Check if table exists
         let table_names = db
@@ -83,52 +594,128 @@ impl LanceDBService {
             .await
             .map_err(|e| AppError::LanceDB(format!("Failed to list tables: {}", e)))?;
 
-        if table_names.contains(&table_name) {
-            // Table exists, open it
-            db.open_table(&table_name)
-                .execute()
-                .await
-                .map_err(|e| AppError::LanceDB(format!("Failed to open table: {}", e)))
+        if table_names.contains(&physical) {
+            // Table exists, open it (upgrading it first if startup migration missed it)
+            let table = self.open_cached_table(&physical).await?;
+            self.upgrade_table(&physical, &table).await?;
+            Ok((table, false))
         } else {
-            // Create new table with schema using actual embedding dimension
-            let schema = Arc::new(Schema::new(vec![
-                Field::new("id", DataType::Utf8, false),
-                Field::new("model_id", DataType::Utf8, false),
-                Field::new("chunk_text", DataType::Utf8, false),
-                Field::new("chunk_index", DataType::Int32, false),
-                Field::new("file_name", DataType::Utf8, false),
-                Field::new(
-                    "embedding",
-                    DataType::FixedSizeList(
-                        Arc::new(Field::new("item", DataType::Float32, true)),
-                        embedding_dim, // Use dynamic dimension
-                    ),
-                    false,
-                ),
-            ]));
+            // Create new table with the current schema, embedding dimension and precision
+            let precision = self.metadata.load(&table_name(model_id))?.precision;
+            let schema = provenance.attach(&vector_schema::table_schema(provenance.dimension, &precision))?;
 
             // Create empty initial batch
             let empty_batch = RecordBatch::new_empty(schema.clone());
 
-            db.create_table(
-                &table_name,
-                RecordBatchIterator::new(vec![Ok(empty_batch)], schema),
-            )
+            let table = db
+                .create_table(
+                    &physical,
+                    RecordBatchIterator::new(vec![Ok(empty_batch)], schema),
+                )
+                .execute()
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to create table: {}", e)))?;
+
+            self.metadata
+                .update(&physical, |m| m.schema_version = CURRENT_SCHEMA_VERSION)?;
+            self.table_cache().put(physical, table.clone());
+
+            Ok((table, true))
+        }
+    }
+
+    /// Bring one physical table up to the current schema
+    ///
+    /// Missing columns are added in place and filled from their migration's
+    /// SQL expression. Returns None when the table was already current.
+    async fn upgrade_table(&self, table_name: &str, table: &Table) -> AppResult<Option<SchemaUpgrade>> {
+        let schema = table
+            .schema()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read schema: {}", e)))?;
+        let columns: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+        let from_version = self.metadata.load(table_name)?.schema_version;
+
+        let pending = vector_schema::pending_migrations(&columns);
+        if pending.is_empty() {
+            if from_version != CURRENT_SCHEMA_VERSION {
+                self.metadata
+                    .update(table_name, |m| m.schema_version = CURRENT_SCHEMA_VERSION)?;
+            }
+            return Ok(None);
+        }
+
+        let mut added_columns = Vec::new();
+        for (migration, new_columns) in pending {
+            let names: Vec<String> = new_columns.iter().map(|(name, _)| name.clone()).collect();
+            table
+                .add_columns(NewColumnTransform::SqlExpressions(new_columns), None)
+                .await
+                .map_err(|e| {
+                    AppError::LanceDB(format!(
+                        "Failed to migrate {} to schema v{}: {}",
+                        table_name, migration.version, e
+                    ))
+                })?;
+            println!(
+                "🔧 Migrated {} to schema v{} ({}): added {}",
+                table_name,
+                migration.version,
+                migration.description,
+                names.join(", ")
+            );
+            added_columns.extend(names);
+        }
+
+        self.metadata
+            .update(table_name, |m| m.schema_version = CURRENT_SCHEMA_VERSION)?;
+        self.invalidate_table(table_name);
+
+        Ok(Some(SchemaUpgrade {
+            table_name: table_name.to_string(),
+            from_version,
+            to_version: CURRENT_SCHEMA_VERSION,
+            added_columns,
+        }))
+    }
+
+    /// Upgrade every vector table to the current schema (run on startup)
+    pub async fn migrate_tables(&self) -> AppResult<Vec<SchemaUpgrade>> {
+        let db = self.initialize().await?;
+        let table_names = db
+            .table_names()
             .execute()
             .await
-            .map_err(|e| AppError::LanceDB(format!("Failed to create table: {}", e)))
+            .map_err(|e| AppError::LanceDB(format!("Failed to list tables: {}", e)))?;
+
+        let mut upgrades = Vec::new();
+        for table_name in table_names {
+            let table = self.open_uncached_table(&table_name).await?;
+            if let Some(upgrade) = self.upgrade_table(&table_name, &table).await? {
+                upgrades.push(upgrade);
+            }
         }
+
+        Ok(upgrades)
     }
 
     /// Store embeddings for a model
+    ///
+    /// Rows are upserted on `(training_data_id, chunk_index, content_hash)`, so
+    /// storing the same chunks twice leaves a single copy in the table.
+    /// Vectors from a different embedding model than the table was built with
+    /// are rejected.
     pub async fn store_embeddings(
         &self,
         model_id: &str,
+        embedding_model: &str,
         chunks: Vec<DocumentChunk>,
         embeddings: Vec<Vec<f32>>,
         encrypt: bool,
         password: Option<&str>,
     ) -> AppResult<usize> {
+        self.touch();
+
         if chunks.len() != embeddings.len() {
             return Err(AppError::LanceDB(
                 "Chunks and embeddings length mismatch".to_string(),
@@ -154,7 +741,33 @@ impl LanceDBService {
             }
         }
 
-        let table = self.get_table(model_id, embedding_dim).await?;
+        let provenance = self
+            .check_embedding_model(model_id, embedding_model, embedding_dim)
+            .await?;
+        let new_provenance = EmbeddingProvenance {
+            model: embedding_model.to_string(),
+            dimension: embedding_dim,
+            normalized: is_normalized(&embeddings),
+            distance_metric: DistanceMetric::Cosine,
+        };
+        let (table, created) = self.get_table(model_id, &new_provenance).await?;
+
+        // Record provenance for new tables and adopt legacy ones
+        let provenance = match provenance {
+            Some(provenance) => provenance,
+            None => {
+                let provenance = EmbeddingProvenance {
+                    // Legacy tables were always searched with LanceDB's default L2
+                    distance_metric: if created { DistanceMetric::Cosine } else { DistanceMetric::L2 },
+                    ..new_provenance
+                };
+                self.metadata
+                    .update(&table_name(model_id), |m| m.embedding = Some(provenance.clone()))?;
+                provenance
+            }
+        };
+        let normalize = provenance.normalized;
+        let precision = self.metadata.load(&table_name(model_id))?.precision;
 
         // Prepare data for insertion
         let mut ids = Vec::new();
@@ -162,9 +775,22 @@ impl LanceDBService {
         let mut chunk_texts = Vec::new();
         let mut chunk_indices = Vec::new();
         let mut file_names = Vec::new();
-        let mut embedding_values: Vec<f32> = Vec::new();
-
-        for (chunk, embedding) in chunks.iter().zip(embeddings.iter()) {
+        let mut training_data_ids = Vec::new();
+        let mut content_hashes = Vec::new();
+        let mut file_hashes = Vec::new();
+        let mut char_starts = Vec::new();
+        let mut char_ends = Vec::new();
+        let mut byte_starts = Vec::new();
+        let mut byte_ends = Vec::new();
+        let mut page_starts = Vec::new();
+        let mut page_ends = Vec::new();
+        let mut sections = Vec::new();
+        let mut line_starts = Vec::new();
+        let mut line_ends = Vec::new();
+        let mut languages = Vec::new();
+        let mut symbols = Vec::new();
+
+        for chunk in &chunks {
             ids.push(chunk.id.clone());
             model_ids.push(chunk.model_id.clone());
 
@@ -185,8 +811,29 @@ impl LanceDBService {
             chunk_indices.push(chunk.chunk_index);
             file_names.push(chunk.file_name.clone());
 
-            // Flatten embedding for Arrow FixedSizeList
-            embedding_values.extend_from_slice(embedding);
+            // Hash the plaintext so re-uploads match even when text is encrypted
+            training_data_ids.push(if chunk.training_data_id.is_empty() {
+                chunk.file_name.clone()
+            } else {
+                chunk.training_data_id.clone()
+            });
+            content_hashes.push(if chunk.content_hash.is_empty() {
+                content_hash(&chunk.chunk_text)
+            } else {
+                chunk.content_hash.clone()
+            });
+            file_hashes.push(chunk.file_hash.clone());
+            char_starts.push(chunk.char_start);
+            char_ends.push(chunk.char_end);
+            byte_starts.push(chunk.byte_start);
+            byte_ends.push(chunk.byte_end);
+            page_starts.push(chunk.page_start);
+            page_ends.push(chunk.page_end);
+            sections.push(chunk.section.clone());
+            line_starts.push(chunk.line_start);
+            line_ends.push(chunk.line_end);
+            languages.push(chunk.language.clone());
+            symbols.push(chunk.symbol.clone());
         }
 
         // Create Arrow arrays
@@ -195,198 +842,1057 @@ impl LanceDBService {
         let chunk_text_array = StringArray::from(chunk_texts);
         let chunk_index_array = arrow_array::Int32Array::from(chunk_indices);
         let file_name_array = StringArray::from(file_names);
+        let training_data_id_array = StringArray::from(training_data_ids);
+        let content_hash_array = StringArray::from(content_hashes);
+        let file_hash_array = StringArray::from(file_hashes);
+        let char_start_array = arrow_array::Int32Array::from(char_starts);
+        let char_end_array = arrow_array::Int32Array::from(char_ends);
+        let byte_start_array = arrow_array::Int32Array::from(byte_starts);
+        let byte_end_array = arrow_array::Int32Array::from(byte_ends);
+        let page_start_array = arrow_array::Int32Array::from(page_starts);
+        let page_end_array = arrow_array::Int32Array::from(page_ends);
+        let section_array = StringArray::from(sections);
+        let line_start_array = arrow_array::Int32Array::from(line_starts);
+        let line_end_array = arrow_array::Int32Array::from(line_ends);
+        let language_array = StringArray::from(languages);
+        let symbol_array = StringArray::from(symbols);
         
-        // Create FixedSizeListArray for embeddings
-        let flat_embedding_array = Float32Array::from(embedding_values);
-        let embedding_field = Arc::new(Field::new("item", DataType::Float32, true));
-        let embedding_array = arrow_array::FixedSizeListArray::try_new(
-            embedding_field,
-            embedding_dim,
-            Arc::new(flat_embedding_array),
-            None,
-        ).map_err(|e| AppError::LanceDB(format!("Failed to create embedding array: {}", e)))?;
+        // Create the embedding column at the table's storage precision
+        let stored: Vec<Vec<f32>> = embeddings
+            .iter()
+            .map(|e| precision.project(e, normalize))
+            .collect();
+        let (embedding_array, scale_array) =
+            precision.encode(&stored, precision.stored_dimension(embedding_dim))?;
 
-        // Create schema with dynamic dimension
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("id", DataType::Utf8, false),
-            Field::new("model_id", DataType::Utf8, false),
-            Field::new("chunk_text", DataType::Utf8, false),
-            Field::new("chunk_index", DataType::Int32, false),
-            Field::new("file_name", DataType::Utf8, false),
-            Field::new(
-                "embedding",
-                DataType::FixedSizeList(
-                    Arc::new(Field::new("item", DataType::Float32, true)),
-                    embedding_dim,
-                ),
-                false,
-            ),
-        ]));
+        let schema = vector_schema::table_schema(embedding_dim, &precision);
 
         // Create record batch
-        let batch = RecordBatch::try_new(
-            schema.clone(),
-            vec![
-                Arc::new(id_array),
-                Arc::new(model_id_array),
-                Arc::new(chunk_text_array),
-                Arc::new(chunk_index_array),
-                Arc::new(file_name_array),
-                Arc::new(embedding_array),
-            ],
-        )
-        .map_err(|e| AppError::LanceDB(format!("Failed to create record batch: {}", e)))?;
-
-        // Insert data
-        table
-            .add(RecordBatchIterator::new(vec![Ok(batch)], schema))
-            .execute()
+        let mut columns: Vec<arrow_array::ArrayRef> = vec![
+            Arc::new(id_array),
+            Arc::new(model_id_array),
+            Arc::new(chunk_text_array),
+            Arc::new(chunk_index_array),
+            Arc::new(file_name_array),
+            Arc::new(training_data_id_array),
+            Arc::new(content_hash_array),
+            Arc::new(file_hash_array),
+            Arc::new(char_start_array),
+            Arc::new(char_end_array),
+            Arc::new(byte_start_array),
+            Arc::new(byte_end_array),
+            Arc::new(page_start_array),
+            Arc::new(page_end_array),
+            Arc::new(section_array),
+            Arc::new(line_start_array),
+            Arc::new(line_end_array),
+            Arc::new(language_array),
+            Arc::new(symbol_array),
+            embedding_array,
+        ];
+        columns.extend(scale_array);
+        let batch = RecordBatch::try_new(schema.clone(), columns)
+            .map_err(|e| AppError::LanceDB(format!("Failed to create record batch: {}", e)))?;
+
+        // Upsert data so re-ingesting a file doesn't duplicate its chunks
+        let mut merge = table.merge_insert(&["training_data_id", "chunk_index", "content_hash"]);
+        merge.when_matched_update_all(None).when_not_matched_insert_all();
+        merge
+            .execute(Box::new(RecordBatchIterator::new(vec![Ok(batch)], schema)))
             .await
-            .map_err(|e| AppError::LanceDB(format!("Failed to insert embeddings: {}", e)))?;
+            .map_err(|e| AppError::LanceDB(format!("Failed to upsert embeddings: {}", e)))?;
+
+        let now = chrono::Utc::now().to_rfc3339();
+        self.metadata
+            .update(&table_name(model_id), |m| m.last_ingested_at = Some(now))?;
 
         Ok(chunks.len())
     }
 
-    /// Search for similar embeddings
-    pub async fn search_similar(
+    /// List the chunks already stored for a training data record
+    pub async fn list_chunk_keys(
         &self,
         model_id: &str,
-        query_embedding: Vec<f32>,
-        limit: usize,
-        encrypted: bool,
-        password: Option<&str>,
-    ) -> AppResult<Vec<SearchResult>> {
-        let table = self.open_table(model_id).await?;
+        training_data_id: &str,
+    ) -> AppResult<Vec<StoredChunkKey>> {
+        self.query_chunk_keys(
+            model_id,
+            Some(format!("training_data_id = '{}'", sql_quote(training_data_id))),
+        )
+        .await
+    }
 
-        // Perform vector search using query().nearest_to() API
-        let mut stream = table
-            .query()
-            .nearest_to(query_embedding)
-            .map_err(|e| AppError::LanceDB(format!("Failed to create query: {}", e)))?
-            .limit(limit)
+    /// List every chunk stored for a model
+    pub async fn list_all_chunk_keys(&self, model_id: &str) -> AppResult<Vec<StoredChunkKey>> {
+        self.query_chunk_keys(model_id, None).await
+    }
+
+    /// Read chunk identities (without text or vectors) matching a filter
+    async fn query_chunk_keys(
+        &self,
+        model_id: &str,
+        filter: Option<String>,
+    ) -> AppResult<Vec<StoredChunkKey>> {
+        let table = match self.open_table(model_id).await {
+            Ok(t) => t,
+            // Nothing has been stored for this model yet
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let mut query = table.query().select(Select::columns(&[
+            "id",
+            "training_data_id",
+            "chunk_index",
+            "content_hash",
+            "file_hash",
+        ]));
+        if let Some(filter) = filter {
+            query = query.only_if(filter);
+        }
+
+        let mut stream = query
             .execute()
             .await
-            .map_err(|e| AppError::LanceDB(format!("Search failed: {}", e)))?;
+            .map_err(|e| AppError::LanceDB(format!("Failed to query chunks: {}", e)))?;
 
-        let mut search_results = Vec::new();
+        let mut keys = Vec::new();
 
-        // Parse results using StreamExt
         while let Some(batch_result) = stream.next().await {
             let batch = batch_result.map_err(|e| AppError::LanceDB(format!("Failed to read batch: {}", e)))?;
 
-            // Extract columns
-            let chunk_texts = batch
-                .column_by_name("chunk_text")
-                .ok_or_else(|| AppError::LanceDB("Missing chunk_text column".to_string()))?
-                .as_any()
-                .downcast_ref::<StringArray>()
-                .ok_or_else(|| AppError::LanceDB("Invalid chunk_text type".to_string()))?;
-
-            let file_names = batch
-                .column_by_name("file_name")
-                .ok_or_else(|| AppError::LanceDB("Missing file_name column".to_string()))?
-                .as_any()
-                .downcast_ref::<StringArray>()
-                .ok_or_else(|| AppError::LanceDB("Invalid file_name type".to_string()))?;
-
+            let ids = string_column(&batch, "id")?;
+            let training_data_ids = string_column(&batch, "training_data_id")?;
             let chunk_indices = batch
                 .column_by_name("chunk_index")
                 .ok_or_else(|| AppError::LanceDB("Missing chunk_index column".to_string()))?
                 .as_any()
                 .downcast_ref::<arrow_array::Int32Array>()
                 .ok_or_else(|| AppError::LanceDB("Invalid chunk_index type".to_string()))?;
+            let content_hashes = string_column(&batch, "content_hash")?;
+            let file_hashes = string_column(&batch, "file_hash")?;
 
-            // Process each row
             for i in 0..batch.num_rows() {
-                let mut chunk_text = chunk_texts.value(i).to_string();
-
-                // Decrypt if needed
-                if encrypted {
-                    if let Some(pwd) = password {
-                        chunk_text = self.encryption.decrypt(&chunk_text, pwd)?;
-                    } else {
-                        return Err(AppError::Encryption(
-                            "Password required for decryption".to_string(),
-                        ));
-                    }
-                }
-
-                search_results.push(SearchResult {
-                    chunk_text,
-                    similarity: 1.0 - (i as f32 / limit as f32), // Approximate similarity
-                    file_name: file_names.value(i).to_string(),
+                keys.push(StoredChunkKey {
+                    id: ids.value(i).to_string(),
+                    training_data_id: training_data_ids.value(i).to_string(),
                     chunk_index: chunk_indices.value(i),
+                    content_hash: content_hashes.value(i).to_string(),
+                    file_hash: file_hashes.value(i).to_string(),
                 });
             }
         }
 
-        Ok(search_results)
+        Ok(keys)
     }
 
-    /// Get context for RAG (retrieve relevant chunks)
-    pub async fn get_context(
+    /// Remove stored chunks of a file that are no longer part of its current content
+    ///
+    /// `current` holds the `(chunk_index, content_hash)` pairs of the latest version
+    /// of the file; every other row for `training_data_id` is deleted.
+    pub async fn delete_stale_chunks(
         &self,
         model_id: &str,
-        query_embedding: Vec<f32>,
-        max_chunks: usize,
-        encrypted: bool,
-        password: Option<&str>,
-    ) -> AppResult<String> {
-        let results = self
-            .search_similar(model_id, query_embedding, max_chunks, encrypted, password)
-            .await?;
-
-        if results.is_empty() {
-            return Ok(String::new());
-        }
+        training_data_id: &str,
+        current: &HashSet<(i32, String)>,
+    ) -> AppResult<usize> {
+        let stale_ids: Vec<String> = self
+            .list_chunk_keys(model_id, training_data_id)
+            .await?
+            .into_iter()
+            .filter(|key| !current.contains(&(key.chunk_index, key.content_hash.clone())))
+            .map(|key| key.id)
+            .collect();
 
-        // Format context
-        let context = results
-            .iter()
-            .map(|r| {
-                format!(
-                    "From {} (chunk {}):\n{}",
-                    r.file_name, r.chunk_index, r.chunk_text
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n\n---\n\n");
+        self.delete_chunks(model_id, &stale_ids).await?;
 
-        Ok(context)
+        Ok(stale_ids.len())
     }
 
-    /// Delete all data for a model
-    pub async fn delete_model_data(&self, model_id: &str) -> AppResult<()> {
-        let db = self.initialize().await?;
-        let table_name = format!("embeddings_{}", model_id.replace('-', "_"));
+    /// Remove a file's rows migrated from the original table layout
+    ///
+    /// Those rows are keyed on the file name and carry no content hash, so a
+    /// re-ingest can't match them and stores the file in full; this drops the
+    /// old copies afterwards.
+    pub async fn delete_legacy_chunks(&self, model_id: &str, file_name: &str) -> AppResult<usize> {
+        let legacy_ids: Vec<String> = self
+            .query_chunk_keys(
+                model_id,
+                Some(format!(
+                    "training_data_id = '{}' AND content_hash = ''",
+                    sql_quote(file_name)
+                )),
+            )
+            .await?
+            .into_iter()
+            .map(|key| key.id)
+            .collect();
 
-        // Check if table exists
-        let table_names = db
-            .table_names()
-            .execute()
-            .await
-            .map_err(|e| AppError::LanceDB(format!("Failed to list tables: {}", e)))?;
+        self.delete_chunks(model_id, &legacy_ids).await?;
 
-        if table_names.contains(&table_name) {
-            db.drop_table(&table_name)
+        Ok(legacy_ids.len())
+    }
+
+    /// Stamp every stored chunk of a file with its latest file hash
+    pub async fn update_file_hash(
+        &self,
+        model_id: &str,
+        training_data_id: &str,
+        file_hash: &str,
+    ) -> AppResult<()> {
+        let table = self.open_writable_table(model_id).await?;
+
+        table
+            .update()
+            .only_if(format!(
+                "training_data_id = '{}' AND file_hash != '{}'",
+                sql_quote(training_data_id),
+                sql_quote(file_hash)
+            ))
+            .column("file_hash", format!("'{}'", sql_quote(file_hash)))
+            .execute()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to update file hash: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Search for similar embeddings
+    ///
+    /// `embedding_model` must match the model the table was built with.
+    /// With MMR enabled a larger candidate pool is fetched and re-ranked for diversity
+    /// using the stored vectors. The query is projected to the table's storage
+    /// precision; int8 tables are searched by an exact scan in the app.
+    pub async fn search_similar(
+        &self,
+        model_id: &str,
+        embedding_model: &str,
+        query_embedding: Vec<f32>,
+        limit: usize,
+        options: &SearchOptions,
+        encrypted: bool,
+        password: Option<&str>,
+    ) -> AppResult<Vec<SearchResult>> {
+        self.touch();
+        let provenance = self
+            .check_embedding_model(model_id, embedding_model, query_embedding.len() as i32)
+            .await?;
+        let metric = provenance
+            .as_ref()
+            .map(|p| p.distance_metric)
+            .unwrap_or(DistanceMetric::L2);
+        let precision = self.metadata.load(&table_name(model_id))?.precision;
+        let query_embedding = precision.project(&query_embedding, provenance.is_some_and(|p| p.normalized));
+        let scan = precision.format == VectorFormat::Int8;
+        let table = self.open_table(model_id).await?;
+
+        let fetch_limit = match &options.mmr {
+            Some(mmr) => mmr.pool_size(limit),
+            None => limit,
+        };
+
+        // Perform vector search using query().nearest_to() API, or read every
+        // row when the stored vectors can't be searched natively
+        let mut stream = if scan {
+            table.query().execute().await
+        } else {
+            table
+                .query()
+                .nearest_to(query_embedding.clone())
+                .map_err(|e| AppError::LanceDB(format!("Failed to create query: {}", e)))?
+                .distance_type(metric.into())
+                .limit(fetch_limit)
+                .execute()
+                .await
+        }
+        .map_err(|e| AppError::LanceDB(format!("Search failed: {}", e)))?;
+
+        // Candidates keep their stored (possibly encrypted) text until selection is done
+        let mut candidates = Vec::new();
+        let mut vectors = Vec::new();
+
+        // Parse results using StreamExt
+        while let Some(batch_result) = stream.next().await {
+            let batch = batch_result.map_err(|e| AppError::LanceDB(format!("Failed to read batch: {}", e)))?;
+
+            // Extract columns
+            let chunk_texts = string_column(&batch, "chunk_text")?;
+            let file_names = string_column(&batch, "file_name")?;
+            let training_data_ids = string_column(&batch, "training_data_id")?;
+
+            let chunk_indices = batch
+                .column_by_name("chunk_index")
+                .ok_or_else(|| AppError::LanceDB("Missing chunk_index column".to_string()))?
+                .as_any()
+                .downcast_ref::<arrow_array::Int32Array>()
+                .ok_or_else(|| AppError::LanceDB("Invalid chunk_index type".to_string()))?;
+
+            let batch_vectors = if scan || options.mmr.is_some() {
+                quantization::decode_embeddings(&batch)?
+            } else {
+                Vec::new()
+            };
+            let distances: Vec<f32> = if scan {
+                batch_vectors
+                    .iter()
+                    .map(|v| quantization::distance(metric, &query_embedding, v))
+                    .collect()
+            } else {
+                batch
+                    .column_by_name("_distance")
+                    .ok_or_else(|| AppError::LanceDB("Missing _distance column".to_string()))?
+                    .as_any()
+                    .downcast_ref::<Float32Array>()
+                    .ok_or_else(|| AppError::LanceDB("Invalid _distance type".to_string()))?
+                    .values()
+                    .to_vec()
+            };
+
+            let locations = location_columns(&batch);
+            vectors.extend(batch_vectors);
+
+            // Process each row
+            for i in 0..batch.num_rows() {
+                candidates.push(SearchResult {
+                    chunk_text: chunk_texts.value(i).to_string(),
+                    similarity: distance_to_similarity(metric, distances[i]),
+                    file_name: file_names.value(i).to_string(),
+                    training_data_id: training_data_ids.value(i).to_string(),
+                    chunk_index: chunk_indices.value(i),
+                    location: locations[i].clone(),
+                    rerank_score: None,
+                    model_id: model_id.to_string(),
+                    normalized_score: None,
+                });
+            }
+
+            if scan {
+                keep_nearest(&mut candidates, &mut vectors, fetch_limit);
+            }
+        }
+
+        let mut search_results = match &options.mmr {
+            Some(mmr) => {
+                let mut slots: Vec<Option<SearchResult>> = candidates.into_iter().map(Some).collect();
+                retrieval::mmr_select(&query_embedding, &vectors, limit, mmr.lambda)
+                    .into_iter()
+                    .filter_map(|i| slots[i].take())
+                    .collect()
+            }
+            None => candidates,
+        };
+        search_results.truncate(limit);
+
+        // Decrypt only what is returned
+        if encrypted {
+            let pwd = password.ok_or_else(|| {
+                AppError::Encryption("Password required for decryption".to_string())
+            })?;
+            for result in &mut search_results {
+                result.chunk_text = self.encryption.decrypt(&result.chunk_text, pwd)?;
+            }
+        }
+
+        Ok(search_results)
+    }
+
+    /// Search several models' knowledge bases at once
+    ///
+    /// Tables are queried concurrently and each goes through the same embedding
+    /// model check as `search_similar`, so one mismatched table fails the search.
+    /// Hits are merged on their raw similarity, which is comparable because every
+    /// table holds vectors from the same embedding model; rescaling per table
+    /// would rank an irrelevant table's best hit level with a relevant one's.
+    /// `normalized_score` is the similarity min-max scaled over the merged list.
+    pub async fn federated_search(
+        &self,
+        targets: &[SearchTarget],
+        embedding_model: &str,
+        query_embedding: Vec<f32>,
+        limit: usize,
+        options: &SearchOptions,
+        password: Option<&str>,
+    ) -> AppResult<Vec<SearchResult>> {
+        let searches = targets.iter().map(|target| {
+            self.search_similar(
+                &target.model_id,
+                embedding_model,
+                query_embedding.clone(),
+                limit,
+                options,
+                target.encrypted,
+                password,
+            )
+        });
+
+        let mut merged = Vec::new();
+        for results in futures::future::join_all(searches).await {
+            merged.extend(results?);
+        }
+
+        merged.sort_by(|a, b| {
+            b.similarity
+                .partial_cmp(&a.similarity)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        merged.truncate(limit);
+
+        let scores: Vec<f32> = merged.iter().map(|r| r.similarity).collect();
+        for (result, score) in merged.iter_mut().zip(retrieval::min_max_normalize(&scores)) {
+            result.normalized_score = Some(score);
+        }
+
+        Ok(merged)
+    }
+
+    /// Build RAG context from search hits
+    ///
+    /// With a window, each hit is widened to `chunk_index ± window` of the same
+    /// file and merged into one passage; hits whose windows touch share a passage.
+    /// Passages are added in rank order while they fit the character budget,
+    /// falling back to the bare hit when its widened passage doesn't fit and
+    /// skipping hits too long for what is left.
+    pub async fn get_context(
+        &self,
+        model_id: &str,
+        hits: &[SearchResult],
+        options: &ContextOptions,
+        encrypted: bool,
+        password: Option<&str>,
+    ) -> AppResult<ContextResult> {
+        let window = options.window as i32;
+
+        // Group hits into index ranges per file
+        let mut spans: Vec<(&SearchResult, i32, i32)> = Vec::new();
+        for hit in hits {
+            let (lo, hi) = (hit.chunk_index - window, hit.chunk_index + window);
+            let existing = spans.iter_mut().find(|(first, s_lo, s_hi)| {
+                window > 0
+                    && first.training_data_id == hit.training_data_id
+                    && lo <= *s_hi + 1
+                    && *s_lo <= hi + 1
+            });
+            match existing {
+                Some(span) => {
+                    span.1 = span.1.min(lo);
+                    span.2 = span.2.max(hi);
+                }
+                None => spans.push((hit, lo, hi)),
+            }
+        }
+
+        let mut budget = options.char_budget().unwrap_or(usize::MAX);
+        let mut passages = Vec::new();
+
+        for (hit, lo, hi) in spans {
+            let mut passage = ContextPassage {
+                file_name: hit.file_name.clone(),
+                training_data_id: hit.training_data_id.clone(),
+                chunk_index: hit.chunk_index,
+                first_chunk: hit.chunk_index,
+                last_chunk: hit.chunk_index,
+                location: hit.location.clone(),
+                score: hit.rerank_score.unwrap_or(hit.similarity),
+                similarity: hit.similarity,
+                rerank_score: hit.rerank_score,
+                text: hit.chunk_text.clone(),
+            };
+
+            if window > 0 {
+                let filter = format!(
+                    "training_data_id = '{}' AND chunk_index >= {} AND chunk_index <= {}",
+                    sql_quote(&hit.training_data_id),
+                    lo,
+                    hi
+                );
+                let mut neighbours = self
+                    .read_chunks(model_id, Some(&filter), encrypted, password)
+                    .await?;
+                neighbours.sort_by_key(|c| c.chunk_index);
+
+                if let (Some(first), Some(last)) = (neighbours.first(), neighbours.last()) {
+                    let merged = retrieval::merge_chunks(
+                        &neighbours
+                            .iter()
+                            .map(|c| WindowChunk {
+                                chunk_index: c.chunk_index,
+                                text: c.chunk_text.clone(),
+                                char_start: c.char_start,
+                                char_end: c.char_end,
+                            })
+                            .collect::<Vec<_>>(),
+                    );
+
+                    // Keep just the hit when the widened passage doesn't fit
+                    if merged.chars().count() <= budget {
+                        passage.first_chunk = first.chunk_index;
+                        passage.last_chunk = last.chunk_index;
+                        passage.location = ChunkLocation::spanning(
+                            &ChunkLocation::of_chunk(first),
+                            &ChunkLocation::of_chunk(last),
+                        );
+                        passage.text = merged;
+                    }
+                }
+            }
+
+            let len = passage.text.chars().count();
+            if len > budget {
+                continue;
+            }
+            budget -= len;
+            passages.push(passage);
+        }
+
+        let prompt = passages
+            .iter()
+            .map(|p| {
+                // Prefer the chapter, section or page over raw chunk numbers
+                let label = p.location.citation().unwrap_or_else(|| {
+                    if p.first_chunk == p.last_chunk {
+                        format!("chunk {}", p.first_chunk)
+                    } else {
+                        format!("chunks {}-{}", p.first_chunk, p.last_chunk)
+                    }
+                });
+                format!("From {} ({}):\n{}", p.location.source(&p.file_name), label, p.text)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n---\n\n");
+
+        Ok(ContextResult { passages, prompt })
+    }
+
+    /// Delete all data for a model
+    pub async fn delete_model_data(&self, model_id: &str) -> AppResult<()> {
+        let db = self.initialize().await?;
+        let table_name = table_name(model_id);
+        let shadow_prefix = format!("{}{}", table_name, SHADOW_SEPARATOR);
+
+        // Drop the table along with any migration tables
+        let table_names = db
+            .table_names()
+            .execute()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to list tables: {}", e)))?;
+
+        for name in table_names
+            .iter()
+            .filter(|name| **name == table_name || name.starts_with(&shadow_prefix))
+        {
+            db.drop_table(name)
                 .await
                 .map_err(|e| AppError::LanceDB(format!("Failed to drop table: {}", e)))?;
+            self.invalidate_table(name);
+            self.metadata.remove(name)?;
+        }
+
+        self.metadata.remove(&table_name)?;
+
+        Ok(())
+    }
+
+    /// Write a model's table and its metadata to an Arrow IPC file
+    ///
+    /// Chunk text is written as stored, so an encrypted knowledge base stays
+    /// encrypted and needs the same password after import.
+    pub async fn export_table(&self, model_id: &str, path: &Path) -> AppResult<ExportSummary> {
+        let physical = self.physical_table_name(model_id)?;
+        let table = self.open_latest_table(model_id).await?;
+
+        // Exports always carry the current schema
+        self.upgrade_table(&physical, &table).await?;
+        let metadata = self.metadata.load(&table_name(model_id))?;
+        let embedding = self.embedding_provenance(model_id).await?;
+
+        let row_count = table
+            .count_rows(None)
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to count rows: {}", e)))?;
+        let manifest = ExportManifest {
+            format_version: EXPORT_FORMAT_VERSION,
+            source_model_id: model_id.to_string(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            schema_version: CURRENT_SCHEMA_VERSION,
+            embedding,
+            precision: metadata.precision,
+            row_count,
+        };
+
+        let schema = table
+            .schema()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read schema: {}", e)))?;
+        let file_schema = manifest.attach(&schema)?;
+
+        // Encoded in memory and written without blocking the runtime
+        let mut writer = FileWriter::try_new(Vec::new(), &file_schema)
+            .map_err(|e| AppError::Storage(format!("Failed to write export file: {}", e)))?;
+
+        let mut stream = table
+            .query()
+            .execute()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read table: {}", e)))?;
+        while let Some(batch) = stream.next().await {
+            let batch = batch.map_err(|e| AppError::LanceDB(format!("Failed to read batch: {}", e)))?;
+            RecordBatch::try_new(file_schema.clone(), batch.columns().to_vec())
+                .and_then(|batch| writer.write(&batch))
+                .map_err(|e| AppError::Storage(format!("Failed to write export file: {}", e)))?;
+        }
+        let data = writer
+            .into_inner()
+            .map_err(|e| AppError::Storage(format!("Failed to write export file: {}", e)))?;
+        tokio::fs::write(path, data)
+            .await
+            .map_err(|e| AppError::Storage(format!("Failed to create export file: {}", e)))?;
+
+        Ok(ExportSummary {
+            path: path.to_string_lossy().to_string(),
+            manifest,
+        })
+    }
+
+    /// Load an export file as the knowledge base of `model_id`
+    ///
+    /// The manifest is checked before anything is written and every row is
+    /// relabelled with the target model. Exports from older schema versions are
+    /// migrated after loading. An existing knowledge base is only overwritten
+    /// when `replace` is set; the import then goes into a shadow table that is
+    /// swapped in once it has fully loaded, so a failed import leaves the
+    /// current knowledge base untouched.
+    pub async fn import_table(&self, path: &Path, model_id: &str, replace: bool) -> AppResult<ImportSummary> {
+        // Read without blocking the runtime; decoding batches from memory is cheap
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|e| AppError::Storage(format!("Failed to open export file: {}", e)))?;
+        let reader = FileReader::try_new(std::io::Cursor::new(data), None)
+            .map_err(|e| AppError::IncompatibleExport(format!("not an Arrow IPC file: {}", e)))?;
+        let manifest = ExportManifest::from_schema(&reader.schema())?;
+        manifest.validate(&reader.schema())?;
+
+        let db = self.initialize().await?;
+        let table_names = db
+            .table_names()
+            .execute()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to list tables: {}", e)))?;
+        let exists = table_names.contains(&self.physical_table_name(model_id)?);
+        if exists && !replace {
+            return Err(AppError::LanceDB(format!(
+                "Model '{}' already has a knowledge base; delete it first or import with replace",
+                model_id
+            )));
+        }
+
+        let target_id = if exists {
+            self.ensure_replaceable(model_id)?;
+            shadow_model_id(model_id)
+        } else {
+            // Clears any leftover metadata from a previous table
+            self.delete_model_data(model_id).await?;
+            model_id.to_string()
+        };
+
+        let name = table_name(&target_id);
+        let result: AppResult<usize> = async {
+            let schema = table_export::import_schema(&reader.schema());
+            let schema = match &manifest.embedding {
+                Some(provenance) => provenance.attach(&schema)?,
+                None => schema,
+            };
+            let table = if manifest.row_count == 0 {
+                db.create_empty_table(&name, schema)
+                    .execute()
+                    .await
+            } else {
+                let target = model_id.to_string();
+                let batch_schema = schema.clone();
+                let batches = reader.map(move |batch| {
+                    batch.and_then(|b| table_export::remap_model_id(b, batch_schema.clone(), &target))
+                });
+                db.create_table(&name, RecordBatchIterator::new(batches, schema))
+                    .execute()
+                    .await
+            }
+            .map_err(|e| AppError::LanceDB(format!("Failed to import table: {}", e)))?;
+
+            self.upgrade_table(&name, &table).await?;
+            let now = chrono::Utc::now().to_rfc3339();
+            self.metadata.update(&name, |m| {
+                m.embedding = manifest.embedding.clone();
+                m.precision = manifest.precision;
+                m.last_ingested_at = Some(now);
+            })?;
+
+            table
+                .count_rows(None)
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to count rows: {}", e)))
+        }
+        .await;
+
+        let row_count = match result {
+            Ok(row_count) => row_count,
+            Err(e) => {
+                // Nothing points at the half-imported table yet, so dropping it rolls back
+                self.delete_model_data(&target_id).await.ok();
+                return Err(e);
+            }
+        };
+        if exists {
+            self.swap_in_table(model_id, &target_id).await?;
+        }
+
+        Ok(ImportSummary {
+            model_id: model_id.to_string(),
+            source_model_id: manifest.source_model_id,
+            row_count,
+            embedding: manifest.embedding,
+        })
+    }
+
+    /// Get how a model's table stores its embeddings
+    pub fn storage_precision(&self, model_id: &str) -> AppResult<StoragePrecision> {
+        Ok(self.metadata.load(&table_name(model_id))?.precision)
+    }
+
+    /// Full embedding dimension of a model, from provenance or the stored vectors
+    async fn model_dimension(&self, model_id: &str, table: &Table) -> AppResult<i32> {
+        if let Some(provenance) = self.embedding_provenance(model_id).await? {
+            return Ok(provenance.dimension);
+        }
+
+        let schema = table
+            .schema()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read schema: {}", e)))?;
+        match schema.field_with_name("embedding").map(|f| f.data_type()) {
+            Ok(DataType::FixedSizeList(_, dimension)) => Ok(*dimension),
+            _ => Err(AppError::LanceDB("Invalid embedding type".to_string())),
+        }
+    }
+
+    /// Change how a model's table stores its embeddings
+    ///
+    /// Existing rows are rewritten into a shadow table at the new precision
+    /// and swapped in, so searches keep working during the conversion. As after
+    /// re-embedding, version history starts over: older versions can no longer
+    /// be listed, checked out or restored, so tagged tables are refused (see
+    /// `ensure_replaceable`). Models without a table only
+    /// record the setting. Returns the number of rows rewritten.
+    pub async fn set_storage_precision(&self, model_id: &str, precision: StoragePrecision) -> AppResult<usize> {
+        precision.validate()?;
+        self.ensure_writable(model_id)?;
+
+        let metadata = self.metadata.load(&table_name(model_id))?;
+        if metadata.precision == precision {
+            return Ok(0);
+        }
+
+        let Ok(table) = self.open_latest_table(model_id).await else {
+            self.metadata
+                .update(&table_name(model_id), |m| m.precision = precision)?;
+            return Ok(0);
+        };
+        self.ensure_replaceable(model_id)?;
+        let provenance = self.embedding_provenance(model_id).await?;
+        if precision.truncate_to.is_some() && provenance.is_none() {
+            // Queries are checked against the model's full dimension, which legacy tables don't record
+            return Err(AppError::LanceDB(
+                "Truncation needs a knowledge base with a recorded embedding model; re-embed it first".to_string(),
+            ));
+        }
+        self.upgrade_table(&self.physical_table_name(model_id)?, &table).await?;
+
+        let dimension = self.model_dimension(model_id, &table).await?;
+        let normalize = provenance.as_ref().is_some_and(|p| p.normalized);
+        let schema = vector_schema::table_schema(dimension, &precision);
+        let schema = match &provenance {
+            Some(provenance) => provenance.attach(&schema)?,
+            None => schema,
+        };
+
+        let shadow_id = shadow_model_id(model_id);
+        let shadow_table = self
+            .initialize()
+            .await?
+            .create_empty_table(&table_name(&shadow_id), schema.clone())
+            .execute()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to create table: {}", e)))?;
+
+        let result: AppResult<usize> = async {
+            let mut stream = table
+                .query()
+                .execute()
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to read table: {}", e)))?;
+            let mut rows = 0;
+            while let Some(batch) = stream.next().await {
+                let batch = batch.map_err(|e| AppError::LanceDB(format!("Failed to read batch: {}", e)))?;
+                let batch = quantization::recode_batch(&batch, &precision, normalize, schema.clone())?;
+                rows += batch.num_rows();
+                shadow_table
+                    .add(RecordBatchIterator::new(vec![Ok(batch)], schema.clone()))
+                    .execute()
+                    .await
+                    .map_err(|e| AppError::LanceDB(format!("Failed to write converted rows: {}", e)))?;
+            }
+            Ok(rows)
+        }
+        .await;
+
+        let rows = match result {
+            Ok(rows) => rows,
+            Err(e) => {
+                // Searches never saw the shadow table, so dropping it is enough to roll back
+                self.delete_model_data(&shadow_id).await.ok();
+                return Err(e);
+            }
+        };
+
+        self.metadata.update(&table_name(&shadow_id), |m| {
+            m.embedding = provenance.clone();
+            m.precision = precision;
+            m.last_ingested_at = metadata.last_ingested_at.clone();
+            m.schema_version = CURRENT_SCHEMA_VERSION;
+        })?;
+        self.swap_in_table(model_id, &shadow_id).await?;
+
+        Ok(rows)
+    }
+
+    /// Measure how well searches would hold up if a model's vectors were stored at `precision`
+    ///
+    /// `sample_size` stored vectors are used as queries, and recall@k is
+    /// computed against the vectors as currently stored.
+    pub async fn compare_precision(
+        &self,
+        model_id: &str,
+        precision: StoragePrecision,
+        sample_size: usize,
+        k: usize,
+    ) -> AppResult<PrecisionComparison> {
+        precision.validate()?;
+
+        let metadata = self.metadata.load(&table_name(model_id))?;
+        let table = self.open_table(model_id).await?;
+        let dimension = self.model_dimension(model_id, &table).await?;
+        let provenance = self.embedding_provenance(model_id).await?;
+        let metric = provenance
+            .as_ref()
+            .map(|p| p.distance_metric)
+            .unwrap_or(DistanceMetric::L2);
+        let normalize = provenance.as_ref().is_some_and(|p| p.normalized);
+
+        let mut columns = vec!["embedding"];
+        if metadata.precision.format == VectorFormat::Int8 {
+            columns.push(SCALE_COLUMN);
+        }
+        let mut stream = table
+            .query()
+            .select(Select::columns(&columns))
+            .execute()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read vectors: {}", e)))?;
+
+        let mut vectors = Vec::new();
+        while let Some(batch) = stream.next().await {
+            let batch = batch.map_err(|e| AppError::LanceDB(format!("Failed to read batch: {}", e)))?;
+            vectors.extend(quantization::decode_embeddings(&batch)?);
+        }
+
+        let recall = quantization::recall_at_k(&vectors, &precision, metric, normalize, sample_size, k);
+
+        Ok(PrecisionComparison {
+            current: metadata.precision,
+            candidate: precision,
+            vectors: vectors.len(),
+            sample_size: sample_size.min(vectors.len()),
+            k,
+            recall,
+            current_bytes_per_vector: metadata.precision.bytes_per_vector(dimension),
+            candidate_bytes_per_vector: precision.bytes_per_vector(dimension),
+        })
+    }
+
+    /// Get the embedding model a model's table was built with
+    ///
+    /// Read from the table's schema, so it can't drift from the rows after an
+    /// import or a table swap; tables created before provenance was stamped
+    /// into the schema fall back to the metadata sidecar.
+    pub async fn embedding_provenance(&self, model_id: &str) -> AppResult<Option<EmbeddingProvenance>> {
+        if let Some(provenance) = self
+            .latest_schema(model_id)
+            .await?
+            .and_then(|schema| EmbeddingProvenance::from_schema(&schema))
+        {
+            return Ok(Some(provenance));
+        }
+
+        Ok(self.metadata.load(&table_name(model_id))?.embedding)
+    }
+
+    /// Read stored chunks (optionally filtered), decrypting their text if needed
+    pub async fn read_chunks(
+        &self,
+        model_id: &str,
+        filter: Option<&str>,
+        encrypted: bool,
+        password: Option<&str>,
+    ) -> AppResult<Vec<DocumentChunk>> {
+        let table = self.open_table(model_id).await?;
+
+        let mut query = table.query().select(Select::columns(&[
+            "id",
+            "model_id",
+            "chunk_text",
+            "chunk_index",
+            "file_name",
+            "training_data_id",
+            "content_hash",
+            "file_hash",
+            "char_start",
+            "char_end",
+            "byte_start",
+            "byte_end",
+            "page_start",
+            "page_end",
+            "section",
+            "line_start",
+            "line_end",
+            "language",
+            "symbol",
+        ]));
+        if let Some(filter) = filter {
+            query = query.only_if(filter);
+        }
+
+        let mut stream = query
+            .execute()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read chunks: {}", e)))?;
+
+        let mut chunks = Vec::new();
+
+        while let Some(batch_result) = stream.next().await {
+            let batch = batch_result.map_err(|e| AppError::LanceDB(format!("Failed to read batch: {}", e)))?;
+
+            let ids = string_column(&batch, "id")?;
+            let model_ids = string_column(&batch, "model_id")?;
+            let chunk_texts = string_column(&batch, "chunk_text")?;
+            let chunk_indices = batch
+                .column_by_name("chunk_index")
+                .ok_or_else(|| AppError::LanceDB("Missing chunk_index column".to_string()))?
+                .as_any()
+                .downcast_ref::<arrow_array::Int32Array>()
+                .ok_or_else(|| AppError::LanceDB("Invalid chunk_index type".to_string()))?;
+            let file_names = string_column(&batch, "file_name")?;
+            let training_data_ids = string_column(&batch, "training_data_id")?;
+            let content_hashes = string_column(&batch, "content_hash")?;
+            let file_hashes = string_column(&batch, "file_hash")?;
+            let locations = location_columns(&batch);
+
+            for i in 0..batch.num_rows() {
+                let mut chunk_text = chunk_texts.value(i).to_string();
+
+                if encrypted {
+                    if let Some(pwd) = password {
+                        chunk_text = self.encryption.decrypt(&chunk_text, pwd)?;
+                    } else {
+                        return Err(AppError::Encryption(
+                            "Password required for decryption".to_string(),
+                        ));
+                    }
+                }
+
+                let location = &locations[i];
+                chunks.push(DocumentChunk {
+                    id: ids.value(i).to_string(),
+                    model_id: model_ids.value(i).to_string(),
+                    chunk_text,
+                    chunk_index: chunk_indices.value(i),
+                    file_name: file_names.value(i).to_string(),
+                    training_data_id: training_data_ids.value(i).to_string(),
+                    content_hash: content_hashes.value(i).to_string(),
+                    file_hash: file_hashes.value(i).to_string(),
+                    char_start: location.char_start,
+                    char_end: location.char_end,
+                    byte_start: location.byte_start,
+                    byte_end: location.byte_end,
+                    page_start: location.page_start,
+                    page_end: location.page_end,
+                    section: location.section.clone(),
+                    line_start: location.line_start,
+                    line_end: location.line_end,
+                    language: location.language.clone(),
+                    symbol: location.symbol.clone(),
+                });
+            }
+        }
+
+        Ok(chunks)
+    }
+
+    /// Delete rows by ID
+    pub async fn delete_chunks(&self, model_id: &str, ids: &[String]) -> AppResult<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let table = self.open_writable_table(model_id).await?;
+
+        for batch in ids.chunks(DELETE_BATCH_SIZE) {
+            let id_list = batch
+                .iter()
+                .map(|id| format!("'{}'", sql_quote(id)))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            table
+                .delete(&format!("id IN ({})", id_list))
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to delete chunks: {}", e)))?;
         }
 
         Ok(())
     }
 
+    /// Make a shadow model's table the one serving `model_id`
+    ///
+    /// Only the metadata pointer changes, so readers see either the old table
+    /// or the new one. The old table is dropped afterwards, so this is refused
+    /// whenever `ensure_replaceable` is.
+    pub async fn swap_in_table(&self, model_id: &str, shadow_model_id: &str) -> AppResult<()> {
+        self.ensure_replaceable(model_id)?;
+        let old_physical = self.physical_table_name(model_id)?;
+        let shadow_table = table_name(shadow_model_id);
+        let shadow_metadata = self.metadata.load(&shadow_table)?;
+
+        self.metadata.update(&table_name(model_id), |m| {
+            m.active_table = Some(shadow_table.clone());
+            m.embedding = shadow_metadata.embedding.clone();
+            m.precision = shadow_metadata.precision;
+            m.last_ingested_at = shadow_metadata.last_ingested_at.clone();
+        })?;
+        self.metadata.remove(&shadow_table)?;
+
+        let db = self.initialize().await?;
+        db.drop_table(&old_physical)
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to drop old table: {}", e)))?;
+        self.invalidate_table(&old_physical);
+
+        Ok(())
+    }
+
     /// Get statistics for a model
     pub async fn get_stats(&self, model_id: &str) -> AppResult<ModelStats> {
         let table = match self.open_table(model_id).await {
             Ok(t) => t,
             Err(_) => {
                 // Table doesn't exist yet
-                return Ok(ModelStats {
-                    total_chunks: 0,
-                    total_files: 0,
-                    table_size_mb: 0.0,
-                });
+                return Ok(ModelStats::default());
             }
         };
 
@@ -396,20 +1902,406 @@ impl LanceDBService {
             .await
             .map_err(|e| AppError::LanceDB(format!("Failed to count rows: {}", e)))?;
 
-        // Get unique file names (would need to scan table in real implementation)
-        // For now, estimate
-        let total_files = (count as f64 / 10.0).ceil() as usize;
+        let files = self.scan_files(&table).await?;
+
+        let table_size_bytes = self.table_size_bytes(model_id).await;
+
+        // Dimension comes from the FixedSizeList width of the embedding column
+        let schema = table
+            .schema()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read schema: {}", e)))?;
+        let embedding_dim = schema
+            .field_with_name("embedding")
+            .ok()
+            .and_then(|field| match field.data_type() {
+                DataType::FixedSizeList(_, dim) => Some(*dim),
+                _ => None,
+            });
+
+        let num_versions = table
+            .list_versions()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to list versions: {}", e)))?
+            .len();
+
+        let num_fragments = match table.as_native() {
+            Some(native) => native.count_fragments().await,
+            None => 0,
+        };
+
+        let indices = table
+            .list_indices()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to list indices: {}", e)))?
+            .into_iter()
+            .map(|index| IndexStatus {
+                name: index.name,
+                index_type: format!("{:?}", index.index_type),
+                columns: index.columns,
+            })
+            .collect();
 
-        // Estimate table size (simplified)
-        let table_size_mb = count as f64 * 0.01; // Rough estimate: 10KB per row
+        let metadata = self.metadata.load(&table_name(model_id))?;
 
         Ok(ModelStats {
             total_chunks: count,
-            total_files,
-            table_size_mb,
+            total_files: files.len(),
+            avg_chunks_per_file: if files.is_empty() {
+                0.0
+            } else {
+                count as f64 / files.len() as f64
+            },
+            table_size_mb: table_size_bytes as f64 / (1024.0 * 1024.0),
+            table_size_bytes,
+            embedding_dim,
+            num_versions,
+            num_fragments,
+            indices,
+            last_ingested_at: metadata.last_ingested_at,
+            precision: metadata.precision,
+        })
+    }
+
+    /// Per-file breakdown of a model's knowledge base
+    pub async fn get_file_stats(&self, model_id: &str) -> AppResult<Vec<FileStats>> {
+        match self.open_table(model_id).await {
+            Ok(table) => self.scan_files(&table).await,
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// Scan the file columns of a table and group rows by training data record
+    async fn scan_files(&self, table: &Table) -> AppResult<Vec<FileStats>> {
+        let mut stream = table
+            .query()
+            .select(Select::columns(&["training_data_id", "file_name", "file_hash"]))
+            .execute()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to scan table: {}", e)))?;
+
+        let mut files: BTreeMap<String, FileStats> = BTreeMap::new();
+
+        while let Some(batch_result) = stream.next().await {
+            let batch = batch_result.map_err(|e| AppError::LanceDB(format!("Failed to read batch: {}", e)))?;
+
+            let training_data_ids = string_column(&batch, "training_data_id")?;
+            let file_names = string_column(&batch, "file_name")?;
+            let file_hashes = string_column(&batch, "file_hash")?;
+
+            for i in 0..batch.num_rows() {
+                let entry = files
+                    .entry(training_data_ids.value(i).to_string())
+                    .or_insert_with(|| FileStats {
+                        training_data_id: training_data_ids.value(i).to_string(),
+                        file_name: file_names.value(i).to_string(),
+                        file_hash: file_hashes.value(i).to_string(),
+                        chunk_count: 0,
+                    });
+                entry.chunk_count += 1;
+            }
+        }
+
+        Ok(files.into_values().collect())
+    }
+
+    /// List the versions of a model's table, oldest first
+    pub async fn list_versions(&self, model_id: &str) -> AppResult<Vec<TableVersion>> {
+        let table = self
+            .open_uncached_table(&self.physical_table_name(model_id)?)
+            .await?;
+        let metadata = self.metadata.load(&table_name(model_id))?;
+
+        let versions = table
+            .list_versions()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to list versions: {}", e)))?;
+
+        let mut result = Vec::with_capacity(versions.len());
+
+        for version in versions {
+            table
+                .checkout(version.version)
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to check out version {}: {}", version.version, e)))?;
+            let row_count = table
+                .count_rows(None)
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to count rows: {}", e)))?;
+
+            result.push(TableVersion {
+                version: version.version,
+                timestamp: version.timestamp.to_rfc3339(),
+                row_count,
+                tags: metadata
+                    .tags
+                    .iter()
+                    .filter(|(_, v)| **v == version.version)
+                    .map(|(tag, _)| tag.clone())
+                    .collect(),
+                checked_out: metadata.checked_out_version == Some(version.version),
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Compare row counts between two versions, overall and per file
+    pub async fn diff_versions(
+        &self,
+        model_id: &str,
+        from_version: u64,
+        to_version: u64,
+    ) -> AppResult<VersionDiff> {
+        let table = self
+            .open_uncached_table(&self.physical_table_name(model_id)?)
+            .await?;
+
+        let mut snapshots = Vec::with_capacity(2);
+        for version in [from_version, to_version] {
+            table
+                .checkout(version)
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to check out version {}: {}", version, e)))?;
+            let rows = table
+                .count_rows(None)
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to count rows: {}", e)))?;
+            snapshots.push((rows, self.scan_files(&table).await?));
+        }
+        let (to_rows, to_files) = snapshots.pop().unwrap_or_default();
+        let (from_rows, from_files) = snapshots.pop().unwrap_or_default();
+
+        // Merge both file listings, keyed by training data record
+        let mut files: BTreeMap<String, FileDiff> = BTreeMap::new();
+        for file in from_files {
+            files.insert(file.training_data_id.clone(), FileDiff {
+                training_data_id: file.training_data_id,
+                file_name: file.file_name,
+                from_chunks: file.chunk_count,
+                to_chunks: 0,
+            });
+        }
+        for file in to_files {
+            files
+                .entry(file.training_data_id.clone())
+                .or_insert_with(|| FileDiff {
+                    training_data_id: file.training_data_id.clone(),
+                    file_name: file.file_name.clone(),
+                    from_chunks: 0,
+                    to_chunks: 0,
+                })
+                .to_chunks = file.chunk_count;
+        }
+
+        Ok(VersionDiff {
+            from_version,
+            to_version,
+            from_rows,
+            to_rows,
+            row_delta: to_rows as i64 - from_rows as i64,
+            files: files
+                .into_values()
+                .filter(|f| f.from_chunks != f.to_chunks)
+                .collect(),
         })
     }
 
+    /// Pin reads (search, context, stats) to an older version
+    pub async fn checkout_version(&self, model_id: &str, version: u64) -> AppResult<()> {
+        // Validate the version exists before pinning it
+        let table = self
+            .open_uncached_table(&self.physical_table_name(model_id)?)
+            .await?;
+        table
+            .checkout(version)
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to check out version {}: {}", version, e)))?;
+
+        self.metadata
+            .update(&table_name(model_id), |m| m.checked_out_version = Some(version))?;
+        Ok(())
+    }
+
+    /// Return reads to the latest version
+    pub async fn checkout_latest(&self, model_id: &str) -> AppResult<()> {
+        self.metadata
+            .update(&table_name(model_id), |m| m.checked_out_version = None)?;
+        Ok(())
+    }
+
+    /// Restore a previous version by committing it as the new latest version
+    ///
+    /// History is kept, so a restore can itself be undone.
+    pub async fn restore_version(&self, model_id: &str, version: u64) -> AppResult<u64> {
+        let physical = self.physical_table_name(model_id)?;
+        let table = self.open_uncached_table(&physical).await?;
+
+        table
+            .checkout(version)
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to check out version {}: {}", version, e)))?;
+        table
+            .restore()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to restore version {}: {}", version, e)))?;
+
+        // Cached handles still point at the version before the restore
+        self.invalidate_table(&physical);
+
+        // Versions from before a schema migration come back with the old columns
+        self.upgrade_table(&physical, &table).await?;
+
+        self.metadata
+            .update(&table_name(model_id), |m| m.checked_out_version = None)?;
+
+        table
+            .version()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to read version: {}", e)))
+    }
+
+    /// Label a version, e.g. "before Q3 import"
+    pub async fn tag_version(&self, model_id: &str, tag: &str, version: u64) -> AppResult<()> {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err(AppError::LanceDB("Tag name cannot be empty".to_string()));
+        }
+
+        let exists = self
+            .open_latest_table(model_id)
+            .await?
+            .list_versions()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to list versions: {}", e)))?
+            .iter()
+            .any(|v| v.version == version);
+        if !exists {
+            return Err(AppError::LanceDB(format!("Version {} does not exist", version)));
+        }
+
+        self.metadata.update(&table_name(model_id), |m| {
+            m.tags.insert(tag.to_string(), version);
+        })?;
+        Ok(())
+    }
+
+    /// Remove a version label
+    pub async fn delete_tag(&self, model_id: &str, tag: &str) -> AppResult<()> {
+        self.metadata.update(&table_name(model_id), |m| {
+            m.tags.remove(tag);
+        })?;
+        Ok(())
+    }
+
+    /// On-disk size of a model's table, including old versions
+    ///
+    /// The directory walk runs on a blocking thread.
+    pub async fn table_size_bytes(&self, model_id: &str) -> u64 {
+        let Ok(name) = self.physical_table_name(model_id) else {
+            return 0;
+        };
+        let path = self.db_path.join(format!("{}.lance", name));
+        tokio::task::spawn_blocking(move || dir_size(&path))
+            .await
+            .unwrap_or(0)
+    }
+
+    /// Merge small fragments and drop rows hidden by deletion files
+    pub async fn compact_table(&self, model_id: &str) -> AppResult<CompactionSummary> {
+        let table = self.open_writable_table(model_id).await?;
+
+        let stats = table
+            .optimize(OptimizeAction::Compact {
+                options: CompactionOptions::default(),
+                remap_options: None,
+            })
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to compact table: {}", e)))?;
+
+        Ok(stats
+            .compaction
+            .map(|m| CompactionSummary {
+                fragments_removed: m.fragments_removed,
+                fragments_added: m.fragments_added,
+            })
+            .unwrap_or_default())
+    }
+
+    /// Delete versions older than the retention window
+    ///
+    /// Tagged and checked-out versions are never pruned: the window is widened
+    /// to keep the oldest of them.
+    pub async fn prune_versions(
+        &self,
+        model_id: &str,
+        retention: chrono::Duration,
+    ) -> AppResult<PruneSummary> {
+        let table = self.open_latest_table(model_id).await?;
+        let metadata = self.metadata.load(&table_name(model_id))?;
+
+        let protected: Vec<u64> = metadata
+            .tags
+            .values()
+            .copied()
+            .chain(metadata.checked_out_version)
+            .collect();
+
+        let now = chrono::Utc::now();
+        let mut older_than = retention;
+        if !protected.is_empty() {
+            let versions = table
+                .list_versions()
+                .await
+                .map_err(|e| AppError::LanceDB(format!("Failed to list versions: {}", e)))?;
+            if let Some(oldest) = versions
+                .iter()
+                .filter(|v| protected.contains(&v.version))
+                .map(|v| v.timestamp)
+                .min()
+            {
+                older_than = older_than.max(now - oldest + chrono::Duration::minutes(1));
+            }
+        }
+
+        let stats = table
+            .optimize(OptimizeAction::Prune {
+                older_than,
+                delete_unverified: Some(false),
+            })
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to prune versions: {}", e)))?;
+
+        Ok(stats
+            .prune
+            .map(|p| PruneSummary {
+                versions_removed: p.old_versions,
+                bytes_removed: p.bytes_removed,
+            })
+            .unwrap_or_default())
+    }
+
+    /// Fold newly added rows into existing indices
+    pub async fn optimize_indices(&self, model_id: &str) -> AppResult<()> {
+        let table = self.open_writable_table(model_id).await?;
+
+        if table
+            .list_indices()
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to list indices: {}", e)))?
+            .is_empty()
+        {
+            return Ok(());
+        }
+
+        table
+            .optimize(OptimizeAction::Index(OptimizeOptions::default()))
+            .await
+            .map_err(|e| AppError::LanceDB(format!("Failed to optimize indices: {}", e)))?;
+
+        Ok(())
+    }
+
     /// List all models with embeddings
     pub async fn list_models(&self) -> AppResult<Vec<String>> {
         let db = self.initialize().await?;
@@ -419,12 +2311,17 @@ impl LanceDBService {
             .await
             .map_err(|e| AppError::LanceDB(format!("Failed to list tables: {}", e)))?;
 
-        // Extract model IDs from table names
-        let model_ids = table_names
+        // Extract model IDs from table names, folding migration tables into their model
+        let mut model_ids: Vec<String> = table_names
             .iter()
             .filter(|name| name.starts_with("embeddings_"))
-            .map(|name| name.replace("embeddings_", "").replace('_', "-"))
+            .map(|name| {
+                let base = name.split(SHADOW_SEPARATOR).next().unwrap_or(name);
+                base.replace("embeddings_", "").replace('_', "-")
+            })
             .collect();
+        model_ids.sort();
+        model_ids.dedup();
 
         Ok(model_ids)
     }
@@ -444,21 +2341,188 @@ impl LanceDBService {
                 .map_err(|e| AppError::LanceDB(format!("Failed to drop table: {}", e)))?;
         }
 
+        self.table_cache().clear();
+        self.metadata.clear()?;
+
         Ok(())
     }
 }
 
-/// Statistics for a model's embeddings
+/// Downcast a Utf8 column of a batch
+fn string_column<'a>(batch: &'a RecordBatch, name: &str) -> AppResult<&'a StringArray> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| AppError::LanceDB(format!("Missing {} column", name)))?
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| AppError::LanceDB(format!("Invalid {} type", name)))
+}
+
+/// Read the location columns of every row
+///
+/// Missing columns (e.g. on a checked-out version from an older schema)
+/// read as unknown.
+fn location_columns(batch: &RecordBatch) -> Vec<ChunkLocation> {
+    let int32 = |name: &str| int32_column(batch, name).ok();
+    let (char_starts, char_ends) = (int32("char_start"), int32("char_end"));
+    let (byte_starts, byte_ends) = (int32("byte_start"), int32("byte_end"));
+    let (page_starts, page_ends) = (int32("page_start"), int32("page_end"));
+    let (line_starts, line_ends) = (int32("line_start"), int32("line_end"));
+    let sections = string_column(batch, "section").ok();
+    let languages = string_column(batch, "language").ok();
+    let symbols = string_column(batch, "symbol").ok();
+
+    let value = |array: Option<&arrow_array::Int32Array>, i: usize| {
+        array.filter(|a| a.is_valid(i)).map(|a| a.value(i))
+    };
+    let text = |array: Option<&StringArray>, i: usize| {
+        array.filter(|a| a.is_valid(i)).map(|a| a.value(i).to_string())
+    };
+
+    (0..batch.num_rows())
+        .map(|i| ChunkLocation {
+            char_start: value(char_starts, i).unwrap_or(0),
+            char_end: value(char_ends, i).unwrap_or(0),
+            byte_start: value(byte_starts, i).unwrap_or(0),
+            byte_end: value(byte_ends, i).unwrap_or(0),
+            page_start: value(page_starts, i),
+            page_end: value(page_ends, i),
+            section: text(sections, i),
+            line_start: value(line_starts, i),
+            line_end: value(line_ends, i),
+            language: text(languages, i),
+            symbol: text(symbols, i),
+        })
+        .collect()
+}
+
+/// Get an Int32 column from a record batch
+fn int32_column<'a>(batch: &'a RecordBatch, name: &str) -> AppResult<&'a arrow_array::Int32Array> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| AppError::LanceDB(format!("Missing {} column", name)))?
+        .as_any()
+        .downcast_ref::<arrow_array::Int32Array>()
+        .ok_or_else(|| AppError::LanceDB(format!("Invalid {} type", name)))
+}
+
+/// Keep the `n` most similar scanned candidates (and their vectors), best first
+fn keep_nearest(candidates: &mut Vec<SearchResult>, vectors: &mut Vec<Vec<f32>>, n: usize) {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| {
+        candidates[b]
+            .similarity
+            .partial_cmp(&candidates[a].similarity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    order.truncate(n);
+
+    let mut slots: Vec<Option<(SearchResult, Vec<f32>)>> = candidates
+        .drain(..)
+        .zip(vectors.drain(..))
+        .map(Some)
+        .collect();
+    for i in order {
+        if let Some((candidate, vector)) = slots[i].take() {
+            candidates.push(candidate);
+            vectors.push(vector);
+        }
+    }
+}
+
+/// Columns added to one table by the schema migration runner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaUpgrade {
+    pub table_name: String,
+    /// Recorded version before the upgrade (0 when never recorded)
+    pub from_version: u32,
+    pub to_version: u32,
+    pub added_columns: Vec<String>,
+}
+
+/// Statistics for a model's embeddings
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ModelStats {
+    pub total_chunks: usize,
+    pub total_files: usize,
+    pub avg_chunks_per_file: f64,
+    pub table_size_mb: f64,
+    pub table_size_bytes: u64,
+    pub embedding_dim: Option<i32>,
+    pub num_versions: usize,
+    pub num_fragments: usize,
+    pub indices: Vec<IndexStatus>,
+    pub last_ingested_at: Option<String>,
+    /// How embeddings are stored (`embedding_dim` is the stored width)
+    pub precision: StoragePrecision,
+}
+
+/// Vector or scalar index on a table
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexStatus {
+    pub name: String,
+    pub index_type: String,
+    pub columns: Vec<String>,
+}
+
+/// Result of compacting a table
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CompactionSummary {
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+}
+
+/// Result of pruning old versions of a table
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PruneSummary {
+    pub versions_removed: u64,
+    pub bytes_removed: u64,
+}
+
+/// A version of a model's table
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableVersion {
+    pub version: u64,
+    pub timestamp: String,
+    pub row_count: usize,
+    pub tags: Vec<String>,
+    pub checked_out: bool,
+}
+
+/// Row count difference between two versions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDiff {
+    pub from_version: u64,
+    pub to_version: u64,
+    pub from_rows: usize,
+    pub to_rows: usize,
+    pub row_delta: i64,
+    /// Files whose chunk count differs between the two versions
+    pub files: Vec<FileDiff>,
+}
+
+/// Chunk count of one file in two versions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileDiff {
+    pub training_data_id: String,
+    pub file_name: String,
+    pub from_chunks: usize,
+    pub to_chunks: usize,
+}
+
+/// Chunks stored for a single file
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ModelStats {
-    pub total_chunks: usize,
-    pub total_files: usize,
-    pub table_size_mb: f64,
+pub struct FileStats {
+    pub training_data_id: String,
+    pub file_name: String,
+    pub file_hash: String,
+    pub chunk_count: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use arrow_schema::Field;
     use std::env;
 
     #[tokio::test]
@@ -473,6 +2537,41 @@ mod tests {
         std::fs::remove_dir_all(temp_dir).ok();
     }
 
+    #[test]
+    fn test_location_citation() {
+        let chapter = ChunkLocation {
+            section: Some("Chapter 4: The Storm".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(chapter.citation().as_deref(), Some("Chapter 4: The Storm"));
+
+        let pages = ChunkLocation {
+            page_start: Some(3),
+            page_end: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(pages.citation().as_deref(), Some("pp. 3-5"));
+
+        let rows = ChunkLocation {
+            line_start: Some(12),
+            line_end: Some(40),
+            ..Default::default()
+        };
+        assert_eq!(rows.citation().as_deref(), Some("lines 12-40"));
+        assert_eq!(rows.source("people.csv"), "people.csv");
+        assert_eq!(ChunkLocation::default().citation(), None);
+
+        let code = ChunkLocation {
+            line_start: Some(120),
+            line_end: Some(168),
+            language: Some("rust".to_string()),
+            symbol: Some("Parser::parse".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(code.source("parser.rs"), "parser.rs:120-168");
+        assert_eq!(code.citation().as_deref(), Some("Parser::parse"));
+    }
+
     #[tokio::test]
     async fn test_store_and_search_embeddings() {
         let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_2");
@@ -485,12 +2584,13 @@ mod tests {
             chunk_text: "This is a test chunk".to_string(),
             chunk_index: 0,
             file_name: "test.txt".to_string(),
+            ..Default::default()
         }];
 
         let embeddings = vec![vec![0.1; 1536]]; // Mock 1536-dimension embedding
 
         let result = service
-            .store_embeddings(model_id, chunks, embeddings, false, None)
+            .store_embeddings(model_id, "test-embed", chunks, embeddings, false, None)
             .await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 1);
@@ -498,7 +2598,7 @@ mod tests {
         // Search
         let query_embedding = vec![0.1; 1536];
         let search_results = service
-            .search_similar(model_id, query_embedding, 5, false, None)
+            .search_similar(model_id, "test-embed", query_embedding, 5, &SearchOptions::default(), false, None)
             .await;
         assert!(search_results.is_ok());
         assert!(!search_results.unwrap().is_empty());
@@ -507,4 +2607,677 @@ mod tests {
         service.delete_model_data(model_id).await.ok();
         std::fs::remove_dir_all(temp_dir).ok();
     }
+
+    #[tokio::test]
+    async fn test_context_window_expansion() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_window");
+        let service = LanceDBService::new(temp_dir.clone());
+
+        // "The quick brown fox jumps over" chunked with a 5 character overlap
+        let model_id = "test_model_window";
+        let chunks: Vec<DocumentChunk> = [("The quick brown", 0), ("brown fox jumps", 10), ("jumps over", 20)]
+            .iter()
+            .enumerate()
+            .map(|(i, (text, start))| DocumentChunk {
+                id: uuid::Uuid::new_v4().to_string(),
+                model_id: model_id.to_string(),
+                chunk_text: text.to_string(),
+                chunk_index: i as i32,
+                file_name: "fox.txt".to_string(),
+                training_data_id: "fox".to_string(),
+                char_start: *start,
+                char_end: start + text.len() as i32,
+                byte_start: *start,
+                byte_end: start + text.len() as i32,
+                ..Default::default()
+            })
+            .collect();
+        let embeddings = vec![vec![0.1, 0.2], vec![0.9, 0.1], vec![0.2, 0.8]];
+        service
+            .store_embeddings(model_id, "test-embed", chunks, embeddings, false, None)
+            .await
+            .unwrap();
+
+        let hits = service
+            .search_similar(model_id, "test-embed", vec![0.9, 0.1], 1, &SearchOptions::default(), false, None)
+            .await
+            .unwrap();
+        assert_eq!(hits[0].chunk_index, 1);
+
+        let options = ContextOptions {
+            window: 1,
+            ..Default::default()
+        };
+        let context = service.get_context(model_id, &hits, &options, false, None).await.unwrap();
+        assert_eq!(context.prompt, "From fox.txt (chunks 0-2):\nThe quick brown fox jumps over");
+        assert_eq!(context.passages[0].training_data_id, "fox");
+        assert_eq!(context.passages[0].chunk_index, 1);
+        assert_eq!((context.passages[0].location.char_start, context.passages[0].location.char_end), (0, 30));
+
+        // A budget too small for the window keeps just the hit
+        let options = ContextOptions {
+            window: 1,
+            max_chars: Some(20),
+            ..Default::default()
+        };
+        let context = service.get_context(model_id, &hits, &options, false, None).await.unwrap();
+        assert_eq!(context.prompt, "From fox.txt (chunk 1):\nbrown fox jumps");
+
+        // Lower-ranked hits that fit are still used after a top hit that doesn't
+        let hits = service
+            .search_similar(model_id, "test-embed", vec![0.9, 0.1], 3, &SearchOptions::default(), false, None)
+            .await
+            .unwrap();
+        let options = ContextOptions {
+            max_chars: Some(12),
+            ..Default::default()
+        };
+        let context = service.get_context(model_id, &hits, &options, false, None).await.unwrap();
+        assert_eq!(context.prompt, "From fox.txt (chunk 2):\njumps over");
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_migrate_legacy_table() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_migrate");
+        let service = LanceDBService::new(temp_dir.clone());
+        let model_id = "test_model_legacy";
+
+        // Table in the original layout, without hashes, offsets or pages
+        let schema = Arc::new(arrow_schema::Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("model_id", DataType::Utf8, false),
+            Field::new("chunk_text", DataType::Utf8, false),
+            Field::new("chunk_index", DataType::Int32, false),
+            Field::new("file_name", DataType::Utf8, false),
+            Field::new(
+                "embedding",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                false,
+            ),
+        ]));
+        let embeddings = arrow_array::FixedSizeListArray::try_new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            2,
+            Arc::new(Float32Array::from(vec![0.6, 0.8])),
+            None,
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["legacy-1"])),
+                Arc::new(StringArray::from(vec![model_id])),
+                Arc::new(StringArray::from(vec!["old chunk"])),
+                Arc::new(arrow_array::Int32Array::from(vec![0])),
+                Arc::new(StringArray::from(vec!["old.txt"])),
+                Arc::new(embeddings),
+            ],
+        )
+        .unwrap();
+        service
+            .initialize()
+            .await
+            .unwrap()
+            .create_table(&table_name(model_id), RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+
+        let upgrades = service.migrate_tables().await.unwrap();
+        assert_eq!(upgrades.len(), 1);
+        assert_eq!(upgrades[0].from_version, 0);
+        assert!(upgrades[0].added_columns.contains(&"page_start".to_string()));
+
+        // Existing rows are backfilled and readable with the current schema
+        let chunks = service.read_chunks(model_id, None, false, None).await.unwrap();
+        assert_eq!(chunks[0].training_data_id, "old.txt");
+        assert_eq!(chunks[0].page_start, None);
+
+        // A second run has nothing to do
+        assert!(service.migrate_tables().await.unwrap().is_empty());
+
+        // Migrated rows have no content hash, so the first re-ingest stores the
+        // file in full and then drops the old copy
+        let reingested = DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: model_id.to_string(),
+            chunk_text: "old chunk".to_string(),
+            chunk_index: 0,
+            file_name: "old.txt".to_string(),
+            training_data_id: "record-1".to_string(),
+            content_hash: content_hash("old chunk"),
+            ..Default::default()
+        };
+        assert!(service.list_chunk_keys(model_id, "record-1").await.unwrap().is_empty());
+        service
+            .store_embeddings(model_id, "test-embed", vec![reingested], vec![vec![0.6, 0.8]], false, None)
+            .await
+            .unwrap();
+        assert_eq!(service.delete_legacy_chunks(model_id, "old.txt").await.unwrap(), 1);
+        let chunks = service.read_chunks(model_id, None, false, None).await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].training_data_id, "record-1");
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_federated_search() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_federated");
+        let service = LanceDBService::new(temp_dir.clone());
+
+        let make_chunk = |model_id: &str, text: &str| DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: model_id.to_string(),
+            chunk_text: text.to_string(),
+            chunk_index: 0,
+            file_name: format!("{}.txt", model_id),
+            ..Default::default()
+        };
+        service
+            .store_embeddings(
+                "work_notes",
+                "test-embed",
+                vec![make_chunk("work_notes", "notes"), make_chunk("work_notes", "related notes")],
+                vec![vec![1.0, 0.0], vec![0.8, 0.6]],
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        service
+            .store_embeddings(
+                "project_docs",
+                "test-embed",
+                vec![make_chunk("project_docs", "docs"), make_chunk("project_docs", "more docs")],
+                vec![vec![0.0, 1.0], vec![-0.6, 0.8]],
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        service
+            .store_embeddings("other_embed", "other-embed", vec![make_chunk("other_embed", "other")], vec![vec![1.0, 0.0]], false, None)
+            .await
+            .unwrap();
+
+        let target = |model_id: &str| SearchTarget {
+            model_id: model_id.to_string(),
+            encrypted: false,
+        };
+        let results = service
+            .federated_search(
+                &[target("work_notes"), target("project_docs")],
+                "test-embed",
+                vec![1.0, 0.0],
+                5,
+                &SearchOptions::default(),
+                None,
+            )
+            .await
+            .unwrap();
+        // The irrelevant table's best hit ranks below every relevant hit
+        let texts: Vec<&str> = results.iter().map(|r| r.chunk_text.as_str()).collect();
+        assert_eq!(texts, vec!["notes", "related notes", "docs", "more docs"]);
+        assert_eq!(results[0].model_id, "work_notes");
+        assert_eq!(results[0].normalized_score, Some(1.0));
+        assert_eq!(results[3].normalized_score, Some(0.0));
+
+        // A table built with another embedding model is still rejected
+        let mixed = service
+            .federated_search(
+                &[target("work_notes"), target("other_embed")],
+                "test-embed",
+                vec![1.0, 0.0],
+                5,
+                &SearchOptions::default(),
+                None,
+            )
+            .await;
+        assert!(matches!(mixed, Err(AppError::EmbeddingModelMismatch { .. })));
+
+        // Cleanup
+        for model_id in ["work_notes", "project_docs", "other_embed"] {
+            service.delete_model_data(model_id).await.ok();
+        }
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_embedding_model_guard() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_provenance");
+        let service = LanceDBService::new(temp_dir.clone());
+
+        let model_id = "test_model_provenance";
+        let chunk = DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: model_id.to_string(),
+            chunk_text: "provenance".to_string(),
+            chunk_index: 0,
+            file_name: "p.txt".to_string(),
+            ..Default::default()
+        };
+
+        service
+            .store_embeddings(model_id, "nomic-embed-text", vec![chunk.clone()], vec![vec![0.5; 4]], false, None)
+            .await
+            .unwrap();
+
+        let wrong_model = service
+            .search_similar(model_id, "mxbai-embed-large", vec![0.5; 4], 5, &SearchOptions::default(), false, None)
+            .await;
+        match wrong_model {
+            Err(AppError::EmbeddingModelMismatch { table_model, requested_model }) => {
+                assert_eq!(table_model, "nomic-embed-text");
+                assert_eq!(requested_model, "mxbai-embed-large");
+            }
+            other => panic!("expected model mismatch, got {:?}", other.map(|r| r.len())),
+        }
+
+        let wrong_dim = service
+            .store_embeddings(model_id, "nomic-embed-text", vec![chunk], vec![vec![0.5; 8]], false, None)
+            .await;
+        assert!(matches!(
+            wrong_dim,
+            Err(AppError::EmbeddingDimensionMismatch { expected: 4, actual: 8, .. })
+        ));
+
+        // Provenance lives in the table's schema, so a lost sidecar record doesn't disable the check
+        service.metadata.update(&table_name(model_id), |m| m.embedding = None).unwrap();
+        let stale_sidecar = service
+            .search_similar(model_id, "mxbai-embed-large", vec![0.5; 4], 5, &SearchOptions::default(), false, None)
+            .await;
+        assert!(matches!(stale_sidecar, Err(AppError::EmbeddingModelMismatch { .. })));
+
+        let results = service
+            .search_similar(model_id, "nomic-embed-text", vec![0.5; 4], 5, &SearchOptions::default(), false, None)
+            .await
+            .unwrap();
+        assert!((results[0].similarity - 1.0).abs() < 1e-3);
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_swap_in_shadow_table() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_swap");
+        let service = LanceDBService::new(temp_dir.clone());
+
+        let model_id = "test-model-swap";
+        let chunk = DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: model_id.to_string(),
+            chunk_text: "migrate me".to_string(),
+            chunk_index: 0,
+            file_name: "m.txt".to_string(),
+            training_data_id: "m".to_string(),
+            ..Default::default()
+        };
+
+        service
+            .store_embeddings(model_id, "old-embed", vec![chunk.clone()], vec![vec![0.1; 4]], false, None)
+            .await
+            .unwrap();
+
+        let shadow = shadow_model_id(model_id);
+        service
+            .store_embeddings(&shadow, "new-embed", vec![chunk], vec![vec![0.2; 6]], false, None)
+            .await
+            .unwrap();
+
+        // Both tables belong to the same model
+        assert_eq!(service.list_models().await.unwrap(), vec![model_id.to_string()]);
+
+        // Tagged versions would be lost with the old table's history
+        let version = service.list_versions(model_id).await.unwrap().last().unwrap().version;
+        service.tag_version(model_id, "keep", version).await.unwrap();
+        assert!(service.swap_in_table(model_id, &shadow).await.is_err());
+        service.delete_tag(model_id, "keep").await.unwrap();
+
+        service.swap_in_table(model_id, &shadow).await.unwrap();
+
+        assert!(service
+            .search_similar(model_id, "old-embed", vec![0.1; 4], 5, &SearchOptions::default(), false, None)
+            .await
+            .is_err());
+        let results = service
+            .search_similar(model_id, "new-embed", vec![0.2; 6], 5, &SearchOptions::default(), false, None)
+            .await
+            .unwrap();
+        assert_eq!(results[0].chunk_text, "migrate me");
+        assert_eq!(service.list_models().await.unwrap(), vec![model_id.to_string()]);
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        assert!(service.list_models().await.unwrap().is_empty());
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_version_tag_and_restore() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_versions");
+        let service = LanceDBService::new(temp_dir.clone());
+
+        let model_id = "test_model_versions";
+        let make_chunk = |text: &str, training_data_id: &str| DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: model_id.to_string(),
+            chunk_text: text.to_string(),
+            chunk_index: 0,
+            file_name: format!("{}.txt", training_data_id),
+            training_data_id: training_data_id.to_string(),
+            ..Default::default()
+        };
+
+        service
+            .store_embeddings(model_id, "test-embed", vec![make_chunk("good", "a")], vec![vec![0.1; 4]], false, None)
+            .await
+            .unwrap();
+        let good_version = service.list_versions(model_id).await.unwrap().last().unwrap().version;
+        service.tag_version(model_id, "before bulk import", good_version).await.unwrap();
+
+        service
+            .store_embeddings(model_id, "test-embed", vec![make_chunk("bad", "b")], vec![vec![0.9; 4]], false, None)
+            .await
+            .unwrap();
+
+        let latest = service.list_versions(model_id).await.unwrap().last().unwrap().version;
+        let diff = service.diff_versions(model_id, good_version, latest).await.unwrap();
+        assert_eq!(diff.row_delta, 1);
+        assert_eq!(diff.files.len(), 1);
+        assert_eq!(diff.files[0].training_data_id, "b");
+
+        // Writes are refused while an old version is checked out
+        service.checkout_version(model_id, good_version).await.unwrap();
+        assert_eq!(service.get_stats(model_id).await.unwrap().total_chunks, 1);
+        assert!(service
+            .store_embeddings(model_id, "test-embed", vec![make_chunk("x", "c")], vec![vec![0.5; 4]], false, None)
+            .await
+            .is_err());
+        service.checkout_latest(model_id).await.unwrap();
+
+        service.restore_version(model_id, good_version).await.unwrap();
+        assert_eq!(service.get_stats(model_id).await.unwrap().total_chunks, 1);
+
+        let versions = service.list_versions(model_id).await.unwrap();
+        assert!(versions
+            .iter()
+            .any(|v| v.version == good_version && v.tags == vec!["before bulk import".to_string()]));
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_store_embeddings_is_idempotent() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_upsert");
+        let service = LanceDBService::new(temp_dir.clone());
+
+        let model_id = "test_model_upsert";
+        let make_chunks = || {
+            vec![DocumentChunk {
+                id: uuid::Uuid::new_v4().to_string(),
+                model_id: model_id.to_string(),
+                chunk_text: "Uploaded twice".to_string(),
+                chunk_index: 0,
+                file_name: "twice.txt".to_string(),
+                training_data_id: "td-1".to_string(),
+                file_hash: "abc".to_string(),
+                char_end: 14,
+                byte_end: 14,
+                ..Default::default()
+            }]
+        };
+
+        for _ in 0..2 {
+            service
+                .store_embeddings(model_id, "test-embed", make_chunks(), vec![vec![0.2; 8]], false, None)
+                .await
+                .unwrap();
+        }
+
+        let keys = service.list_chunk_keys(model_id, "td-1").await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].content_hash, content_hash("Uploaded twice"));
+
+        // Re-storing the same chunk must not add rows in the next version either
+        let versions = service.list_versions(model_id).await.unwrap();
+        assert!(versions.iter().all(|v| v.row_count <= 1));
+
+        // A changed file drops chunks that are no longer present
+        let removed = service
+            .delete_stale_chunks(model_id, "td-1", &HashSet::new())
+            .await
+            .unwrap();
+        assert_eq!(removed, 1);
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_chunks_files_and_size() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_stats");
+        let service = LanceDBService::new(temp_dir.clone());
+        let model_id = "test_model_stats";
+
+        let chunks = vec![DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: model_id.to_string(),
+            chunk_text: "Counted once".to_string(),
+            chunk_index: 0,
+            file_name: "stats.txt".to_string(),
+            training_data_id: "td-stats".to_string(),
+            file_hash: "def".to_string(),
+            char_end: 12,
+            byte_end: 12,
+            ..Default::default()
+        }];
+        service
+            .store_embeddings(model_id, "test-embed", chunks, vec![vec![0.3; 8]], false, None)
+            .await
+            .unwrap();
+
+        let stats = service.get_stats(model_id).await.unwrap();
+        assert_eq!(stats.total_chunks, 1);
+        assert_eq!(stats.total_files, 1);
+        assert_eq!(stats.embedding_dim, Some(8));
+        assert!(stats.table_size_bytes > 0);
+        assert!(stats.last_ingested_at.is_some());
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_shared_service_and_table_cache() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_cache");
+        let service = Arc::new(LanceDBService::new(temp_dir.clone()));
+        let model_id = "test_model_cache";
+
+        let chunk = |i: i32| DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: model_id.to_string(),
+            chunk_text: format!("chunk {}", i),
+            chunk_index: i,
+            file_name: "shared.txt".to_string(),
+            training_data_id: "shared".to_string(),
+            ..Default::default()
+        };
+
+        // Concurrent writers share one service through the per-model write lock
+        let tasks: Vec<_> = (0..4)
+            .map(|i| {
+                let service = service.clone();
+                let chunk = chunk(i);
+                tokio::spawn(async move {
+                    let _write_guard = service.write_lock(model_id).await;
+                    service
+                        .store_embeddings(model_id, "test-embed", vec![chunk], vec![vec![0.1; 4]], false, None)
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(service.get_stats(model_id).await.unwrap().total_chunks, 4);
+
+        // Restoring through a separate handle is visible to cached readers
+        let first_version = service.list_versions(model_id).await.unwrap()[1].version;
+        service.restore_version(model_id, first_version).await.unwrap();
+        assert_eq!(service.get_stats(model_id).await.unwrap().total_chunks, 1);
+
+        // A dropped table is not served from the cache once it is recreated
+        service.delete_model_data(model_id).await.unwrap();
+        service
+            .store_embeddings(model_id, "other-embed", vec![chunk(0)], vec![vec![0.1; 8]], false, None)
+            .await
+            .unwrap();
+        let stats = service.get_stats(model_id).await.unwrap();
+        assert_eq!(stats.total_chunks, 1);
+        assert_eq!(stats.embedding_dim, Some(8));
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_table() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_export");
+        let service = LanceDBService::new(temp_dir.join("db"));
+        let export_path = temp_dir.join("notes.arrow");
+
+        let chunk = DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: "laptop-model".to_string(),
+            chunk_text: "Exported chunk".to_string(),
+            chunk_index: 0,
+            file_name: "notes.txt".to_string(),
+            training_data_id: "notes".to_string(),
+            char_end: 14,
+            byte_end: 14,
+            section: Some("Intro".to_string()),
+            ..Default::default()
+        };
+        service
+            .store_embeddings("laptop-model", "test-embed", vec![chunk], vec![vec![0.5; 4]], false, None)
+            .await
+            .unwrap();
+
+        let export = service.export_table("laptop-model", &export_path).await.unwrap();
+        assert_eq!(export.manifest.row_count, 1);
+        assert_eq!(export.manifest.embedding.as_ref().map(|e| e.dimension), Some(4));
+
+        let import = service
+            .import_table(&export_path, "desktop-model", false)
+            .await
+            .unwrap();
+        assert_eq!(import.row_count, 1);
+        assert_eq!(import.source_model_id, "laptop-model");
+
+        // Rows belong to the new model and keep their provenance
+        let chunks = service.read_chunks("desktop-model", None, false, None).await.unwrap();
+        assert_eq!(chunks[0].model_id, "desktop-model");
+        assert_eq!(chunks[0].section.as_deref(), Some("Intro"));
+        assert!(service
+            .search_similar("desktop-model", "other-embed", vec![0.5; 4], 1, &SearchOptions::default(), false, None)
+            .await
+            .is_err());
+
+        // Existing knowledge bases are only overwritten on request
+        assert!(service.import_table(&export_path, "desktop-model", false).await.is_err());
+        assert!(service.import_table(&export_path, "desktop-model", true).await.is_ok());
+        assert_eq!(service.get_stats("desktop-model").await.unwrap().total_chunks, 1);
+
+        // Replacing follows the same tag rule as re-embedding
+        let version = service.list_versions("desktop-model").await.unwrap().last().unwrap().version;
+        service.tag_version("desktop-model", "keep", version).await.unwrap();
+        assert!(service.import_table(&export_path, "desktop-model", true).await.is_err());
+        service.delete_tag("desktop-model", "keep").await.unwrap();
+
+        // A failed replacement keeps the current knowledge base
+        std::fs::write(&export_path, b"not an export").unwrap();
+        assert!(service.import_table(&export_path, "desktop-model", true).await.is_err());
+        assert_eq!(service.get_stats("desktop-model").await.unwrap().total_chunks, 1);
+
+        // Cleanup
+        service.clear_all().await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_reduced_precision_storage() {
+        let temp_dir = env::temp_dir().join("mydistinctai_lancedb_test_precision");
+        let service = LanceDBService::new(temp_dir.clone());
+        let model_id = "test_model_precision";
+
+        let chunk = |i: i32| DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            model_id: model_id.to_string(),
+            chunk_text: format!("chunk {}", i),
+            chunk_index: i,
+            file_name: "vectors.txt".to_string(),
+            training_data_id: "vectors".to_string(),
+            ..Default::default()
+        };
+        let vectors = vec![
+            vec![1.0, 0.0, 0.0, 0.0],
+            vec![0.0, 1.0, 0.0, 0.0],
+            vec![0.6, 0.8, 0.0, 0.0],
+        ];
+        service
+            .store_embeddings(model_id, "test-embed", (0..3).map(chunk).collect(), vectors, false, None)
+            .await
+            .unwrap();
+
+        let int8 = StoragePrecision {
+            format: VectorFormat::Int8,
+            truncate_to: None,
+        };
+        let comparison = service.compare_precision(model_id, int8, 3, 1).await.unwrap();
+        assert_eq!(comparison.vectors, 3);
+        assert_eq!(comparison.current_bytes_per_vector, 16);
+        assert_eq!(comparison.candidate_bytes_per_vector, 8);
+
+        // Tagged versions would be lost with the old table's history
+        let version = service.list_versions(model_id).await.unwrap().last().unwrap().version;
+        service.tag_version(model_id, "before int8", version).await.unwrap();
+        assert!(service.set_storage_precision(model_id, int8).await.is_err());
+        service.delete_tag(model_id, "before int8").await.unwrap();
+
+        // Converting keeps every row and search stays transparent
+        assert_eq!(service.set_storage_precision(model_id, int8).await.unwrap(), 3);
+        assert_eq!(service.get_stats(model_id).await.unwrap().precision, int8);
+
+        let results = service
+            .search_similar(model_id, "test-embed", vec![0.0, 1.0, 0.0, 0.0], 2, &SearchOptions::default(), false, None)
+            .await
+            .unwrap();
+        assert_eq!(results[0].chunk_index, 1);
+        assert_eq!(results[1].chunk_index, 2);
+
+        // New rows are quantized on the way in
+        service
+            .store_embeddings(model_id, "test-embed", vec![chunk(3)], vec![vec![0.0, 0.0, 1.0, 0.0]], false, None)
+            .await
+            .unwrap();
+        assert_eq!(service.get_stats(model_id).await.unwrap().total_chunks, 4);
+
+        // Cleanup
+        service.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
 }
@@ -5,12 +5,30 @@ mod ollama;
 mod storage;
 mod encryption;
 mod lancedb;
+mod retrieval;
+mod reranker;
+mod table_metadata;
+mod vector_schema;
+mod quantization;
+mod table_export;
 mod file_processor;
+mod pdf_extractor;
+mod chunking;
+mod csv_extractor;
+mod epub_extractor;
+mod office_extractor;
+mod zip_package;
+mod html_extractor;
+mod markdown_chunker;
+mod code_chunker;
 mod error;
 mod database;
+mod maintenance;
+mod reembed;
 
 
 use tauri::{Manager, Emitter};
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::path::PathBuf;
 use std::process::Stdio;
@@ -25,6 +43,8 @@ pub use storage::LocalStorage;
 pub use encryption::EncryptionService;
 pub use lancedb::LanceDBService;
 pub use database::Database;
+pub use maintenance::MaintenanceService;
+pub use reembed::ReembedService;
 pub use error::AppError;
 
 
@@ -33,8 +53,10 @@ pub struct AppState {
     pub ollama: Arc<Mutex<OllamaService>>,
     pub storage: Arc<Mutex<LocalStorage>>,
     pub encryption: Arc<Mutex<EncryptionService>>,
-    pub lancedb: Arc<Mutex<LanceDBService>>,
+    pub lancedb: Arc<LanceDBService>,
     pub database: Arc<Mutex<Database>>,
+    pub maintenance: Arc<MaintenanceService>,
+    pub reembed: Arc<ReembedService>,
 }
 
 /// Check if Ollama is installed on the system
@@ -226,15 +248,17 @@ async fn load_chat_history(
 #[tauri::command]
 async fn store_embeddings(
     model_id: String,
+    embedding_model: String,
     chunks: Vec<lancedb::DocumentChunk>,
     embeddings: Vec<Vec<f32>>,
     encrypt: bool,
     password: Option<String>,
     state: tauri::State<'_, AppState>
 ) -> Result<usize, String> {
-    let lancedb = state.lancedb.lock().await;
-    lancedb.store_embeddings(
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.store_embeddings(
         &model_id,
+        &embedding_model,
         chunks,
         embeddings,
         encrypt,
@@ -244,21 +268,62 @@ async fn store_embeddings(
     .map_err(|e| e.to_string())
 }
 
+/// Vector search followed by the model's reranking stage, when enabled
+///
+/// Reranking needs the query text, so it is skipped when `query` is None.
+async fn retrieve(
+    state: &AppState,
+    model_id: &str,
+    embedding_model: &str,
+    query_embedding: Vec<f32>,
+    query: Option<&str>,
+    limit: usize,
+    options: &retrieval::SearchOptions,
+    encrypted: bool,
+    password: Option<&str>,
+) -> error::AppResult<Vec<lancedb::SearchResult>> {
+    let rerank = state.database.lock().await.get_model_settings(model_id).await?.rerank;
+    let rerank_query = query.filter(|_| rerank.enabled);
+    let fetch_limit = match rerank_query {
+        Some(_) => rerank.candidate_pool.max(limit),
+        None => limit,
+    };
+
+    let results = state
+        .lancedb
+        .search_similar(model_id, embedding_model, query_embedding, fetch_limit, options, encrypted, password)
+        .await?;
+
+    match rerank_query {
+        Some(query) => {
+            let ollama = state.ollama.lock().await;
+            Ok(reranker::rerank(&ollama, &rerank, query, results, limit).await)
+        }
+        None => Ok(results),
+    }
+}
+
 /// Search for similar embeddings
 #[tauri::command]
 async fn search_similar(
     model_id: String,
+    embedding_model: String,
     query_embedding: Vec<f32>,
+    query: Option<String>,
     limit: usize,
+    options: Option<retrieval::SearchOptions>,
     encrypted: bool,
     password: Option<String>,
     state: tauri::State<'_, AppState>
 ) -> Result<Vec<lancedb::SearchResult>, String> {
-    let lancedb = state.lancedb.lock().await;
-    lancedb.search_similar(
+    retrieve(
+        &state,
         &model_id,
+        &embedding_model,
         query_embedding,
+        query.as_deref(),
         limit,
+        &options.unwrap_or_default(),
         encrypted,
         password.as_deref(),
     )
@@ -266,21 +331,61 @@ async fn search_similar(
     .map_err(|e| e.to_string())
 }
 
+/// Search several models' knowledge bases together
+#[tauri::command]
+async fn federated_search(
+    targets: Vec<lancedb::SearchTarget>,
+    embedding_model: String,
+    query_embedding: Vec<f32>,
+    limit: usize,
+    options: Option<retrieval::SearchOptions>,
+    password: Option<String>,
+    state: tauri::State<'_, AppState>
+) -> Result<Vec<lancedb::SearchResult>, String> {
+    state.lancedb.federated_search(
+        &targets,
+        &embedding_model,
+        query_embedding,
+        limit,
+        &options.unwrap_or_default(),
+        password.as_deref(),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
 /// Get context for RAG
 #[tauri::command]
 async fn get_rag_context(
     model_id: String,
+    embedding_model: String,
     query_embedding: Vec<f32>,
+    query: Option<String>,
     max_chunks: usize,
+    options: Option<retrieval::SearchOptions>,
+    context_options: Option<retrieval::ContextOptions>,
     encrypted: bool,
     password: Option<String>,
     state: tauri::State<'_, AppState>
-) -> Result<String, String> {
-    let lancedb = state.lancedb.lock().await;
-    lancedb.get_context(
+) -> Result<lancedb::ContextResult, String> {
+    let results = retrieve(
+        &state,
         &model_id,
+        &embedding_model,
         query_embedding,
+        query.as_deref(),
         max_chunks,
+        &options.unwrap_or_default(),
+        encrypted,
+        password.as_deref(),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    state.lancedb.get_context(
+        &model_id,
+        &results,
+        &context_options.unwrap_or_default(),
         encrypted,
         password.as_deref(),
     )
@@ -294,8 +399,8 @@ async fn delete_model_embeddings(
     model_id: String,
     state: tauri::State<'_, AppState>
 ) -> Result<(), String> {
-    let lancedb = state.lancedb.lock().await;
-    lancedb.delete_model_data(&model_id).await
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.delete_model_data(&model_id).await
         .map_err(|e| e.to_string())
 }
 
@@ -305,18 +410,228 @@ async fn get_embedding_stats(
     model_id: String,
     state: tauri::State<'_, AppState>
 ) -> Result<lancedb::ModelStats, String> {
-    let lancedb = state.lancedb.lock().await;
-    lancedb.get_stats(&model_id).await
+    state.lancedb.get_stats(&model_id).await
+        .map_err(|e| e.to_string())
+}
+
+/// Get the per-file breakdown of a model's knowledge base
+#[tauri::command]
+async fn get_knowledge_base_files(
+    model_id: String,
+    state: tauri::State<'_, AppState>
+) -> Result<Vec<lancedb::FileStats>, String> {
+    state.lancedb.get_file_stats(&model_id).await
+        .map_err(|e| e.to_string())
+}
+
+/// List the versions of a model's knowledge base
+#[tauri::command]
+async fn list_knowledge_base_versions(
+    model_id: String,
+    state: tauri::State<'_, AppState>
+) -> Result<Vec<lancedb::TableVersion>, String> {
+    state.lancedb.list_versions(&model_id).await
+        .map_err(|e| e.to_string())
+}
+
+/// Compare row counts between two knowledge base versions
+#[tauri::command]
+async fn diff_knowledge_base_versions(
+    model_id: String,
+    from_version: u64,
+    to_version: u64,
+    state: tauri::State<'_, AppState>
+) -> Result<lancedb::VersionDiff, String> {
+    state.lancedb.diff_versions(&model_id, from_version, to_version).await
+        .map_err(|e| e.to_string())
+}
+
+/// Pin reads of a knowledge base to an older version
+#[tauri::command]
+async fn checkout_knowledge_base_version(
+    model_id: String,
+    version: u64,
+    state: tauri::State<'_, AppState>
+) -> Result<(), String> {
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.checkout_version(&model_id, version).await
+        .map_err(|e| e.to_string())
+}
+
+/// Return a knowledge base to its latest version
+#[tauri::command]
+async fn checkout_latest_knowledge_base(
+    model_id: String,
+    state: tauri::State<'_, AppState>
+) -> Result<(), String> {
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.checkout_latest(&model_id).await
+        .map_err(|e| e.to_string())
+}
+
+/// Restore a previous knowledge base version as the latest version
+#[tauri::command]
+async fn restore_knowledge_base_version(
+    model_id: String,
+    version: u64,
+    state: tauri::State<'_, AppState>
+) -> Result<u64, String> {
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.restore_version(&model_id, version).await
+        .map_err(|e| e.to_string())
+}
+
+/// Tag a knowledge base version
+#[tauri::command]
+async fn tag_knowledge_base_version(
+    model_id: String,
+    tag: String,
+    version: u64,
+    state: tauri::State<'_, AppState>
+) -> Result<(), String> {
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.tag_version(&model_id, &tag, version).await
+        .map_err(|e| e.to_string())
+}
+
+/// Remove a knowledge base version tag
+#[tauri::command]
+async fn delete_knowledge_base_tag(
+    model_id: String,
+    tag: String,
+    state: tauri::State<'_, AppState>
+) -> Result<(), String> {
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.delete_tag(&model_id, &tag).await
+        .map_err(|e| e.to_string())
+}
+
+/// Change how a model's embeddings are stored (rewrites existing rows)
+///
+/// The rewritten table starts a new version history, so earlier versions
+/// can't be restored afterwards. Models with tagged versions are refused
+/// until the tags are deleted.
+#[tauri::command]
+async fn set_storage_precision(
+    model_id: String,
+    precision: quantization::StoragePrecision,
+    state: tauri::State<'_, AppState>
+) -> Result<usize, String> {
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.set_storage_precision(&model_id, precision).await
+        .map_err(|e| e.to_string())
+}
+
+/// Measure search recall of a model's vectors at another storage precision
+#[tauri::command]
+async fn compare_storage_precision(
+    model_id: String,
+    precision: quantization::StoragePrecision,
+    sample_size: Option<usize>,
+    k: Option<usize>,
+    state: tauri::State<'_, AppState>
+) -> Result<quantization::PrecisionComparison, String> {
+    state.lancedb
+        .compare_precision(&model_id, precision, sample_size.unwrap_or(50), k.unwrap_or(10))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Export a model's knowledge base to an Arrow IPC file
+#[tauri::command]
+async fn export_knowledge_base(
+    model_id: String,
+    path: String,
+    state: tauri::State<'_, AppState>
+) -> Result<table_export::ExportSummary, String> {
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.export_table(&model_id, &PathBuf::from(path)).await
         .map_err(|e| e.to_string())
 }
 
+/// Import an exported knowledge base into a model
+#[tauri::command]
+async fn import_knowledge_base(
+    path: String,
+    model_id: String,
+    replace: Option<bool>,
+    state: tauri::State<'_, AppState>
+) -> Result<table_export::ImportSummary, String> {
+    let _write_guard = state.lancedb.write_lock(&model_id).await;
+    state.lancedb.import_table(&PathBuf::from(path), &model_id, replace.unwrap_or(false)).await
+        .map_err(|e| e.to_string())
+}
+
+/// Compact, prune and re-index vector tables (all models when no ID is given)
+#[tauri::command]
+async fn run_vector_maintenance(
+    model_id: Option<String>,
+    options: Option<maintenance::MaintenanceOptions>,
+    state: tauri::State<'_, AppState>
+) -> Result<Vec<maintenance::MaintenanceReport>, String> {
+    let options = options.unwrap_or_default();
+    state.maintenance.run(model_id.as_deref(), &options).await
+        .map_err(|e| e.to_string())
+}
+
+/// Get the result of the last vector table maintenance run
+#[tauri::command]
+async fn get_maintenance_status(
+    state: tauri::State<'_, AppState>
+) -> Result<maintenance::MaintenanceStatus, String> {
+    Ok(state.maintenance.status().await)
+}
+
+/// Re-embed a model's knowledge base with a different embedding model
+///
+/// Runs in the background; progress is emitted as `reembed-progress` events.
+#[tauri::command]
+async fn start_reembed_migration(
+    model_id: String,
+    embedding_model: String,
+    encrypted: bool,
+    password: Option<String>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>
+) -> Result<reembed::ReembedProgress, String> {
+    let progress = state.reembed.begin(&model_id, &embedding_model).await
+        .map_err(|e| e.to_string())?;
+
+    let reembed = state.reembed.clone();
+    tauri::async_runtime::spawn(async move {
+        reembed.run(&model_id, encrypted, password, |progress| {
+            let _ = app.emit("reembed-progress", progress);
+        }).await;
+    });
+
+    Ok(progress)
+}
+
+/// Get the progress of a model's re-embedding migration
+#[tauri::command]
+async fn get_reembed_status(
+    model_id: String,
+    state: tauri::State<'_, AppState>
+) -> Result<Option<reembed::ReembedProgress>, String> {
+    Ok(state.reembed.status(&model_id).await)
+}
+
+/// Cancel a running re-embedding migration
+#[tauri::command]
+async fn cancel_reembed_migration(
+    model_id: String,
+    state: tauri::State<'_, AppState>
+) -> Result<(), String> {
+    state.reembed.cancel(&model_id).await;
+    Ok(())
+}
+
 /// List all models with embeddings
 #[tauri::command]
 async fn list_embedding_models(
     state: tauri::State<'_, AppState>
 ) -> Result<Vec<String>, String> {
-    let lancedb = state.lancedb.lock().await;
-    lancedb.list_models().await
+    state.lancedb.list_models().await
         .map_err(|e| e.to_string())
 }
 
@@ -351,7 +666,9 @@ async fn extract_text_from_file(
 ) -> Result<String, String> {
     let processor = file_processor::FileProcessor::new();
     let path = PathBuf::from(file_path);
-    processor.extract_text(&path)
+    tokio::task::spawn_blocking(move || processor.extract_text(&path))
+        .await
+        .map_err(|e| e.to_string())?
         .map_err(|e| e.to_string())
 }
 
@@ -363,27 +680,68 @@ async fn chunk_text(
     overlap: usize,
 ) -> Result<Vec<ChunkInfo>, String> {
     let processor = file_processor::FileProcessor::new();
-    let chunks = processor.chunk_text(&text, chunk_size, overlap);
+    let chunks = processor.chunk_text(&text, chunk_size, overlap)
+        .map_err(|e| e.to_string())?;
 
     Ok(chunks.into_iter().map(|c| ChunkInfo {
         text: c.text,
         index: c.index,
         char_start: c.char_start,
         char_end: c.char_end,
+        byte_start: c.byte_start,
+        byte_end: c.byte_end,
+        page_start: c.page_start,
+        page_end: c.page_end,
+        section: c.section,
+        line_start: c.line_start,
+        line_end: c.line_end,
+        symbol: c.symbol,
     }).collect())
 }
 
+/// Build a chunking strategy on a blocking thread, since loading a tokenizer may download it
+///
+/// Token-based chunking falls back to estimated counts when the embedding
+/// model's tokenizer can't be fetched, so uploads still work offline.
+async fn chunking_strategy(
+    config: chunking::ChunkingConfig,
+    embedding_model: Option<String>,
+) -> Result<Box<dyn chunking::ChunkingStrategy>, String> {
+    tokio::task::spawn_blocking(move || match config.strategy(embedding_model.as_deref()) {
+        Err(error::AppError::Tokenizer(e)) => {
+            println!("⚠️  Tokenizer unavailable, estimating tokens: {}", e);
+            config.strategy(None)
+        }
+        result => result,
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
 /// Process a file: extract text and chunk it
+///
+/// `chunking` picks a strategy other than fixed-width chunks of `chunk_size`;
+/// token-based chunking counts with `embedding_model`'s tokenizer. `limits`
+/// overrides the default bounds on file size, pages and extraction time.
 #[tauri::command]
 async fn process_file(
     file_path: String,
     chunk_size: usize,
     overlap: usize,
+    chunking: Option<chunking::ChunkingConfig>,
+    embedding_model: Option<String>,
+    limits: Option<file_processor::ExtractionLimits>,
 ) -> Result<FileProcessResult, String> {
-    let processor = file_processor::FileProcessor::new();
+    let processor = file_processor::FileProcessor::with_limits(limits.unwrap_or_default());
     let path = PathBuf::from(file_path);
 
-    let (text, chunks) = processor.process_file(&path, chunk_size, overlap)
+    let config = chunking.unwrap_or(chunking::ChunkingConfig::FixedWidth { chunk_size, overlap });
+    let strategy = chunking_strategy(config, embedding_model).await?;
+    // Extraction can block for up to its timeout, so it runs off the async runtime
+    let (extracted, chunks) = tokio::task::spawn_blocking(move || processor.process_file(&path, strategy.as_ref()))
+        .await
+        .map_err(|e| e.to_string())?
         .map_err(|e| e.to_string())?;
 
     let chunk_infos: Vec<ChunkInfo> = chunks.into_iter().map(|c| ChunkInfo {
@@ -391,10 +749,20 @@ async fn process_file(
         index: c.index,
         char_start: c.char_start,
         char_end: c.char_end,
+        byte_start: c.byte_start,
+        byte_end: c.byte_end,
+        page_start: c.page_start,
+        page_end: c.page_end,
+        section: c.section,
+        line_start: c.line_start,
+        line_end: c.line_end,
+        symbol: c.symbol,
     }).collect();
 
     Ok(FileProcessResult {
-        full_text: text,
+        full_text: extracted.text,
+        metadata: extracted.metadata,
+        pages: extracted.pages,
         chunks: chunk_infos,
     })
 }
@@ -419,6 +787,11 @@ async fn get_file_info(
 }
 
 /// Complete RAG workflow: process file, generate embeddings, store in LanceDB
+///
+/// `training_data_id` is the file's training data record (see
+/// `db_get_or_add_training_data`). Re-ingesting a file only embeds chunks
+/// whose content changed; chunks that disappeared from the file are removed
+/// from the vector table, and the record's chunk count is updated.
 #[tauri::command]
 async fn process_and_store_file(
     model_id: String,
@@ -427,37 +800,106 @@ async fn process_and_store_file(
     embedding_model: String,
     chunk_size: usize,
     overlap: usize,
+    chunking: Option<chunking::ChunkingConfig>,
+    limits: Option<file_processor::ExtractionLimits>,
     encrypt: bool,
     password: Option<String>,
+    training_data_id: String,
     state: tauri::State<'_, AppState>
 ) -> Result<ProcessResult, String> {
-    // 1. Process file
-    let processor = file_processor::FileProcessor::new();
+    // 1. Process file, chunked as the upload asks, else as the model is set up, else fixed-width
+    let chunking = match chunking {
+        Some(chunking) => chunking,
+        None => state.database.lock().await.get_model_settings(&model_id).await
+            .map_err(|e| format!("Settings lookup failed: {}", e))?
+            .chunking
+            .unwrap_or(chunking::ChunkingConfig::FixedWidth { chunk_size, overlap }),
+    };
+    let strategy = chunking_strategy(chunking, Some(embedding_model.clone())).await?;
+
+    let processor = file_processor::FileProcessor::with_limits(limits.unwrap_or_default());
     let path = PathBuf::from(&file_path);
 
-    let (full_text, chunks) = processor.process_file(&path, chunk_size, overlap)
-        .map_err(|e| format!("File processing failed: {}", e))?;
-
-    // 2. Generate embeddings
-    let ollama = state.ollama.lock().await;
-    let texts: Vec<String> = chunks.iter().map(|c| c.text.clone()).collect();
-    let embeddings = ollama.generate_embeddings_batch(&embedding_model, texts).await
-        .map_err(|e| format!("Embedding generation failed: {}", e))?;
-    drop(ollama); // Release lock
-
-    // 3. Prepare document chunks
-    let doc_chunks: Vec<lancedb::DocumentChunk> = chunks.iter().map(|c| lancedb::DocumentChunk {
+    // Extraction can block for up to its timeout, so it runs off the async runtime
+    let (extracted, chunks, file_hash) = tokio::task::spawn_blocking(move || {
+        let (extracted, chunks) = processor.process_file(&path, strategy.as_ref())
+            .map_err(|e| format!("File processing failed: {}", e))?;
+        let file_hash = processor.hash_file(&path)
+            .map_err(|e| format!("File hashing failed: {}", e))?;
+        Ok::<_, String>((extracted, chunks, file_hash))
+    })
+    .await
+    .map_err(|e| format!("File processing failed: {}", e))??;
+
+    // 2. Compare against what is already stored for this file
+    // Hash what gets embedded, so a renamed heading re-embeds the chunks under it
+    let hashes: Vec<String> = chunks.iter().map(|c| lancedb::content_hash(&c.embedding_text())).collect();
+    let current: HashSet<(i32, String)> = chunks.iter()
+        .zip(hashes.iter())
+        .map(|(c, h)| (c.index as i32, h.clone()))
+        .collect();
+
+    let lancedb = &state.lancedb;
+    let stored_keys = |keys: Vec<lancedb::StoredChunkKey>| -> HashSet<(i32, String)> {
+        keys.into_iter().map(|k| (k.chunk_index, k.content_hash)).collect()
+    };
+    let existing = stored_keys(lancedb.list_chunk_keys(&model_id, &training_data_id).await
+        .map_err(|e| format!("Storage lookup failed: {}", e))?);
+
+    let changed: Vec<usize> = (0..chunks.len())
+        .filter(|&i| !existing.contains(&(chunks[i].index as i32, hashes[i].clone())))
+        .collect();
+
+    // 3. Generate embeddings for new or changed chunks only, before taking the
+    // write lock so other writes to the model aren't held up by Ollama
+    let embeddings = if changed.is_empty() {
+        Vec::new()
+    } else {
+        let ollama = state.ollama.lock().await;
+        let texts: Vec<String> = changed.iter().map(|&i| chunks[i].embedding_text()).collect();
+        let embeddings = ollama.generate_embeddings_batch(&embedding_model, texts).await
+            .map_err(|e| format!("Embedding generation failed: {}", e))?;
+        drop(ollama); // Release lock
+        embeddings
+    };
+
+    // Re-read the stored keys under the lock, so a concurrent upload of the
+    // same file that finished meanwhile doesn't get its chunks stored twice
+    let write_guard = lancedb.write_lock(&model_id).await;
+    let existing = stored_keys(lancedb.list_chunk_keys(&model_id, &training_data_id).await
+        .map_err(|e| format!("Storage lookup failed: {}", e))?);
+    let (changed, embeddings): (Vec<usize>, Vec<Vec<f32>>) = changed.into_iter()
+        .zip(embeddings)
+        .filter(|(i, _)| !existing.contains(&(chunks[*i].index as i32, hashes[*i].clone())))
+        .unzip();
+
+    // 4. Prepare document chunks
+    let doc_chunks: Vec<lancedb::DocumentChunk> = changed.iter().map(|&i| lancedb::DocumentChunk {
         id: uuid::Uuid::new_v4().to_string(),
         model_id: model_id.clone(),
-        chunk_text: c.text.clone(),
-        chunk_index: c.index as i32,
+        chunk_text: chunks[i].text.clone(),
+        chunk_index: chunks[i].index as i32,
         file_name: file_name.clone(),
+        training_data_id: training_data_id.clone(),
+        content_hash: hashes[i].clone(),
+        file_hash: file_hash.clone(),
+        char_start: chunks[i].char_start as i32,
+        char_end: chunks[i].char_end as i32,
+        byte_start: chunks[i].byte_start as i32,
+        byte_end: chunks[i].byte_end as i32,
+        page_start: chunks[i].page_start.map(|p| p as i32),
+        page_end: chunks[i].page_end.map(|p| p as i32),
+        section: chunks[i].section.clone(),
+        line_start: chunks[i].line_start.map(|l| l as i32),
+        line_end: chunks[i].line_end.map(|l| l as i32),
+        language: extracted.metadata.language.clone(),
+        symbol: chunks[i].symbol.clone(),
     }).collect();
 
-    // 4. Store in LanceDB
-    let lancedb = state.lancedb.lock().await;
+    // 5. Upsert into LanceDB and drop chunks that no longer exist
     let stored_count = lancedb.store_embeddings(
         &model_id,
+        &embedding_model,
         doc_chunks,
         embeddings,
         encrypt,
@@ -465,10 +907,28 @@ async fn process_and_store_file(
     ).await
         .map_err(|e| format!("Storage failed: {}", e))?;
 
+    let removed_count = lancedb.delete_stale_chunks(&model_id, &training_data_id, &current).await
+        .map_err(|e| format!("Storage cleanup failed: {}", e))?;
+    // Rows migrated from before content hashing were just stored again above
+    let legacy_count = lancedb.delete_legacy_chunks(&model_id, &file_name).await
+        .map_err(|e| format!("Storage cleanup failed: {}", e))?;
+
+    if !chunks.is_empty() {
+        lancedb.update_file_hash(&model_id, &training_data_id, &file_hash).await
+            .map_err(|e| format!("Storage update failed: {}", e))?;
+    }
+    drop(write_guard);
+
+    state.database.lock().await.set_training_data_chunks(&training_data_id, chunks.len() as i32).await
+        .map_err(|e| format!("Training data update failed: {}", e))?;
+
     Ok(ProcessResult {
         chunks_processed: chunks.len(),
         chunks_stored: stored_count,
-        total_chars: full_text.len(),
+        chunks_unchanged: chunks.len() - changed.len(),
+        chunks_removed: removed_count + legacy_count,
+        total_chars: extracted.text.len(),
+        metadata: extracted.metadata,
     })
 }
 
@@ -478,11 +938,22 @@ struct ChunkInfo {
     index: usize,
     char_start: usize,
     char_end: usize,
+    byte_start: usize,
+    byte_end: usize,
+    page_start: Option<u32>,
+    page_end: Option<u32>,
+    section: Option<String>,
+    line_start: Option<u32>,
+    line_end: Option<u32>,
+    symbol: Option<String>,
 }
 
 #[derive(serde::Serialize)]
 struct FileProcessResult {
     full_text: String,
+    metadata: file_processor::DocumentMetadata,
+    /// Text of each page, for PDFs
+    pages: Vec<file_processor::PageText>,
     chunks: Vec<ChunkInfo>,
 }
 
@@ -498,7 +969,10 @@ struct FileInfoResponse {
 struct ProcessResult {
     chunks_processed: usize,
     chunks_stored: usize,
+    chunks_unchanged: usize,
+    chunks_removed: usize,
     total_chars: usize,
+    metadata: file_processor::DocumentMetadata,
 }
 
 // ============ DATABASE COMMANDS ============
@@ -559,6 +1033,32 @@ async fn db_delete_model(
         .map_err(|e| e.to_string())
 }
 
+/// Get retrieval settings for a model
+#[tauri::command]
+async fn db_get_model_settings(
+    model_id: String,
+    state: tauri::State<'_, AppState>
+) -> Result<database::ModelSettings, String> {
+    let db = state.database.lock().await;
+    db.get_model_settings(&model_id).await
+        .map_err(|e| e.to_string())
+}
+
+/// Save retrieval settings for a model
+#[tauri::command]
+async fn db_save_model_settings(
+    model_id: String,
+    settings: database::ModelSettings,
+    state: tauri::State<'_, AppState>
+) -> Result<(), String> {
+    if let Some(chunking) = &settings.chunking {
+        chunking.validate().map_err(|e| e.to_string())?;
+    }
+    let db = state.database.lock().await;
+    db.save_model_settings(&model_id, &settings).await
+        .map_err(|e| e.to_string())
+}
+
 /// Add training data
 #[tauri::command]
 async fn db_add_training_data(
@@ -570,6 +1070,17 @@ async fn db_add_training_data(
         .map_err(|e| e.to_string())
 }
 
+/// Get the training data record of a file, adding it on first upload
+#[tauri::command]
+async fn db_get_or_add_training_data(
+    data: database::NewTrainingData,
+    state: tauri::State<'_, AppState>
+) -> Result<database::TrainingData, String> {
+    let db = state.database.lock().await;
+    db.get_or_add_training_data(data).await
+        .map_err(|e| e.to_string())
+}
+
 /// List training data for a model
 #[tauri::command]
 async fn db_list_training_data(
@@ -996,7 +1507,25 @@ fn main() {
 
             // Initialize LanceDB with path in app data directory
             let lancedb_path = app_data_dir.join("lancedb");
-            let lancedb = Arc::new(Mutex::new(LanceDBService::new(lancedb_path)));
+            let lancedb = Arc::new(LanceDBService::new(lancedb_path));
+
+            // Upgrade vector tables written by older versions before anything reads them
+            tauri::async_runtime::block_on(async {
+                match lancedb.migrate_tables().await {
+                    Ok(upgrades) if upgrades.is_empty() => println!("✅ Vector tables are up to date"),
+                    Ok(upgrades) => println!("✅ Upgraded {} vector tables", upgrades.len()),
+                    Err(e) => println!("⚠️  Vector table migration failed: {}", e),
+                }
+            });
+
+            // Compact vector tables in the background while the app is idle
+            let maintenance = Arc::new(MaintenanceService::new(lancedb.clone()));
+            tauri::async_runtime::spawn(maintenance.clone().run_idle_schedule(
+                std::time::Duration::from_secs(6 * 60 * 60),
+                std::time::Duration::from_secs(10 * 60),
+            ));
+
+            let reembed = Arc::new(ReembedService::new(lancedb.clone(), ollama.clone()));
 
             // Initialize SQLite database (use block_on since setup is not async)
             let db_path = app_data_dir.join("mydistinctai.db");
@@ -1014,6 +1543,8 @@ fn main() {
                 encryption,
                 lancedb,
                 database,
+                maintenance,
+                reembed,
             });
 
             println!("✅ Application initialized successfully!");
@@ -1033,7 +1564,26 @@ fn main() {
             generate_embeddings_batch,
             store_embeddings,
             search_similar,
+            federated_search,
             get_rag_context,
+            get_embedding_stats,
+            get_knowledge_base_files,
+            list_knowledge_base_versions,
+            diff_knowledge_base_versions,
+            checkout_knowledge_base_version,
+            checkout_latest_knowledge_base,
+            restore_knowledge_base_version,
+            tag_knowledge_base_version,
+            delete_knowledge_base_tag,
+            set_storage_precision,
+            compare_storage_precision,
+            export_knowledge_base,
+            import_knowledge_base,
+            run_vector_maintenance,
+            get_maintenance_status,
+            start_reembed_migration,
+            get_reembed_status,
+            cancel_reembed_migration,
             encrypt_data,
             decrypt_data,
             save_user_data,
@@ -1053,7 +1603,10 @@ fn main() {
             db_list_models,
             db_update_model,
             db_delete_model,
+            db_get_model_settings,
+            db_save_model_settings,
             db_add_training_data,
+            db_get_or_add_training_data,
             db_list_training_data,
             db_delete_training_data,
             db_create_chat_session,
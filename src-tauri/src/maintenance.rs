@@ -0,0 +1,281 @@
+use crate::error::{AppError, AppResult};
+use crate::lancedb::LanceDBService;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// What a maintenance run should do
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceOptions {
+    /// Merge small fragments and purge deleted rows
+    #[serde(default = "default_true")]
+    pub compact: bool,
+    /// Delete versions older than `retention_days`
+    #[serde(default = "default_true")]
+    pub prune: bool,
+    /// Fold new rows into existing vector indices
+    #[serde(default = "default_true")]
+    pub optimize_indices: bool,
+    #[serde(default = "default_retention_days")]
+    pub retention_days: i64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_retention_days() -> i64 {
+    7
+}
+
+impl Default for MaintenanceOptions {
+    fn default() -> Self {
+        Self {
+            compact: true,
+            prune: true,
+            optimize_indices: true,
+            retention_days: default_retention_days(),
+        }
+    }
+}
+
+/// Outcome of maintaining one model's table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    pub model_id: String,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_reclaimed: u64,
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    pub versions_pruned: u64,
+    pub duration_ms: u128,
+    pub error: Option<String>,
+}
+
+/// Last maintenance run, for the settings screen
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    pub running: bool,
+    pub last_run_at: Option<String>,
+    pub last_reports: Vec<MaintenanceReport>,
+}
+
+/// Keeps vector tables compact: merges fragments, prunes old versions and
+/// re-optimizes indices, on demand or while the app is idle
+pub struct MaintenanceService {
+    lancedb: Arc<LanceDBService>,
+    status: Mutex<MaintenanceStatus>,
+}
+
+impl MaintenanceService {
+    /// Create a new maintenance service
+    pub fn new(lancedb: Arc<LanceDBService>) -> Self {
+        Self {
+            lancedb,
+            status: Mutex::new(MaintenanceStatus::default()),
+        }
+    }
+
+    /// Get the status of the last run
+    pub async fn status(&self) -> MaintenanceStatus {
+        self.status.lock().await.clone()
+    }
+
+    /// Maintain one model's table, or every table when `model_id` is None
+    ///
+    /// Fails with `AppError::MaintenanceRunning` if a run is already in progress.
+    pub async fn run(
+        &self,
+        model_id: Option<&str>,
+        options: &MaintenanceOptions,
+    ) -> AppResult<Vec<MaintenanceReport>> {
+        {
+            let mut status = self.status.lock().await;
+            if status.running {
+                return Err(AppError::MaintenanceRunning);
+            }
+            status.running = true;
+        }
+
+        let result = self.run_inner(model_id, options).await;
+
+        let mut status = self.status.lock().await;
+        status.running = false;
+        if let Ok(reports) = &result {
+            status.last_run_at = Some(chrono::Utc::now().to_rfc3339());
+            status.last_reports = reports.clone();
+        }
+
+        result
+    }
+
+    async fn run_inner(
+        &self,
+        model_id: Option<&str>,
+        options: &MaintenanceOptions,
+    ) -> AppResult<Vec<MaintenanceReport>> {
+        let model_ids = match model_id {
+            Some(id) => vec![id.to_string()],
+            None => self.lancedb.list_models().await?,
+        };
+
+        let mut reports = Vec::with_capacity(model_ids.len());
+
+        for model_id in model_ids {
+            // Hold the table's write lock; searches keep running meanwhile
+            let write_guard = self.lancedb.write_lock(&model_id).await;
+            let report = Self::maintain_table(&self.lancedb, &model_id, options).await;
+            drop(write_guard);
+
+            if let Some(err) = &report.error {
+                println!("⚠️  Maintenance failed for {}: {}", model_id, err);
+            }
+            reports.push(report);
+        }
+
+        Ok(reports)
+    }
+
+    /// Run the enabled steps on one table, recording the first error
+    async fn maintain_table(
+        lancedb: &LanceDBService,
+        model_id: &str,
+        options: &MaintenanceOptions,
+    ) -> MaintenanceReport {
+        let started = Instant::now();
+        let bytes_before = lancedb.table_size_bytes(model_id).await;
+
+        let mut report = MaintenanceReport {
+            model_id: model_id.to_string(),
+            bytes_before,
+            bytes_after: bytes_before,
+            bytes_reclaimed: 0,
+            fragments_removed: 0,
+            fragments_added: 0,
+            versions_pruned: 0,
+            duration_ms: 0,
+            error: None,
+        };
+
+        let steps: AppResult<()> = async {
+            if options.compact {
+                let compaction = lancedb.compact_table(model_id).await?;
+                report.fragments_removed = compaction.fragments_removed;
+                report.fragments_added = compaction.fragments_added;
+            }
+            if options.optimize_indices {
+                lancedb.optimize_indices(model_id).await?;
+            }
+            // Prune last so versions written by compaction are cleaned up too
+            if options.prune {
+                let retention = chrono::Duration::days(options.retention_days.max(0));
+                report.versions_pruned = lancedb.prune_versions(model_id, retention).await?.versions_removed;
+            }
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = steps {
+            report.error = Some(e.to_string());
+        }
+
+        report.bytes_after = lancedb.table_size_bytes(model_id).await;
+        report.bytes_reclaimed = report.bytes_before.saturating_sub(report.bytes_after);
+        report.duration_ms = started.elapsed().as_millis();
+        report
+    }
+
+    /// Run maintenance whenever the app has been idle for `idle_after`,
+    /// at most once per `interval`
+    pub async fn run_idle_schedule(self: Arc<Self>, interval: Duration, idle_after: Duration) {
+        let mut ticker = tokio::time::interval(Duration::from_secs(60));
+        let mut last_run: Option<Instant> = None;
+
+        loop {
+            ticker.tick().await;
+
+            if last_run.is_some_and(|t| t.elapsed() < interval) {
+                continue;
+            }
+
+            let last_activity = self.lancedb.last_activity();
+            let idle_for = (chrono::Utc::now() - last_activity).to_std().unwrap_or_default();
+            if idle_for < idle_after {
+                continue;
+            }
+
+            println!("🧹 Running idle vector table maintenance...");
+            match self.run(None, &MaintenanceOptions::default()).await {
+                Ok(reports) => {
+                    let reclaimed: u64 = reports.iter().map(|r| r.bytes_reclaimed).sum();
+                    println!("✅ Maintenance reclaimed {} bytes across {} tables", reclaimed, reports.len());
+                }
+                // A manual run started first; it covers this interval
+                Err(AppError::MaintenanceRunning) => {}
+                Err(e) => println!("⚠️  Maintenance failed: {}", e),
+            }
+            last_run = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lancedb::DocumentChunk;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_maintenance_compacts_fragments() {
+        let temp_dir = env::temp_dir().join("mydistinctai_maintenance_test");
+        let lancedb = Arc::new(LanceDBService::new(temp_dir.clone()));
+        let service = MaintenanceService::new(lancedb.clone());
+
+        let model_id = "test_model_maintenance";
+        for i in 0..3 {
+            let chunk = DocumentChunk {
+                id: uuid::Uuid::new_v4().to_string(),
+                model_id: model_id.to_string(),
+                chunk_text: format!("chunk {}", i),
+                chunk_index: i,
+                file_name: "notes.txt".to_string(),
+                training_data_id: "notes".to_string(),
+                ..Default::default()
+            };
+            lancedb
+                .store_embeddings(model_id, "test-embed", vec![chunk], vec![vec![0.3; 4]], false, None)
+                .await
+                .unwrap();
+        }
+
+        let options = MaintenanceOptions {
+            retention_days: 0,
+            ..Default::default()
+        };
+        let reports = service.run(Some(model_id), &options).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].error.is_none());
+        assert!(reports[0].fragments_removed >= 2);
+        assert_eq!(lancedb.get_stats(model_id).await.unwrap().total_chunks, 3);
+        assert!(service.status().await.last_run_at.is_some());
+
+        // Cleanup
+        lancedb.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_refuses_concurrent_run() {
+        let temp_dir = env::temp_dir().join("mydistinctai_maintenance_test_running");
+        let service = MaintenanceService::new(Arc::new(LanceDBService::new(temp_dir.clone())));
+
+        service.status.lock().await.running = true;
+        let result = service.run(None, &MaintenanceOptions::default()).await;
+        assert!(matches!(result, Err(AppError::MaintenanceRunning)));
+
+        // Cleanup
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+}
@@ -0,0 +1,333 @@
+use crate::chunking::ChunkingStrategy;
+use crate::file_processor::{HeadingPath, TextChunk, TextLocation};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Grapheme and byte range of source text, ending before any line break
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    char_start: usize,
+    char_end: usize,
+    byte_start: usize,
+    byte_end: usize,
+}
+
+/// A top-level Markdown block; fenced code and tables are never split
+#[derive(Debug)]
+enum Block {
+    Heading(u8, String),
+    Fenced,
+    Table,
+    Paragraph,
+}
+
+struct Line<'a> {
+    text: &'a str,
+    span: Span,
+}
+
+fn lines(text: &str) -> Vec<Line<'_>> {
+    let (mut graphemes, mut bytes) = (0, 0);
+    text.split_inclusive('\n')
+        .map(|raw| {
+            let content = raw.trim_end_matches(['\r', '\n']);
+            let span = Span {
+                char_start: graphemes,
+                char_end: graphemes + content.graphemes(true).count(),
+                byte_start: bytes,
+                byte_end: bytes + content.len(),
+            };
+            graphemes += raw.graphemes(true).count();
+            bytes += raw.len();
+            Line { text: content, span }
+        })
+        .collect()
+}
+
+/// Level and title of an ATX heading such as `## Linux ##`
+fn atx_heading(line: &str) -> Option<(u8, String)> {
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+    let level = rest.len() - rest.trim_start_matches('#').len();
+    let title = &rest[level..];
+    if !(1..=6).contains(&level) || !(title.is_empty() || title.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    // A closing run of hashes is decoration, but only when set off by a space
+    let title = title.trim();
+    let unclosed = title.trim_end_matches('#');
+    let title = if unclosed.is_empty() || unclosed.ends_with([' ', '\t']) {
+        unclosed.trim_end()
+    } else {
+        title
+    };
+    (!title.is_empty()).then(|| (level as u8, title.to_string()))
+}
+
+/// Fence character and length of a code fence opener such as "```rust"
+fn fence_opener(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let fence = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = trimmed.len() - trimmed.trim_start_matches(fence).len();
+    (len >= 3).then_some((fence, len))
+}
+
+fn closes_fence(line: &str, (fence, len): (char, usize)) -> bool {
+    fence_opener(line).is_some_and(|(c, n)| c == fence && n >= len)
+        && line.trim().trim_start_matches(fence).is_empty()
+}
+
+/// Whether a line is a table's header separator, e.g. `|---|:--:|`
+fn is_table_delimiter(line: &str) -> bool {
+    let cells: Vec<&str> = line.trim().trim_matches('|').split('|').map(str::trim).collect();
+    line.contains('|')
+        && cells.iter().all(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+}
+
+fn starts_table(lines: &[Line], i: usize) -> bool {
+    lines[i].text.contains('|') && lines.get(i + 1).is_some_and(|next| is_table_delimiter(next.text))
+}
+
+/// Whether a line ends the paragraph before it without a blank line
+fn interrupts_paragraph(lines: &[Line], i: usize) -> bool {
+    let line = lines[i].text;
+    line.trim().is_empty() || fence_opener(line).is_some() || atx_heading(line).is_some() || starts_table(lines, i)
+}
+
+/// Split Markdown into headings, fenced code, tables and paragraphs
+fn blocks(text: &str) -> Vec<(Block, Span)> {
+    let lines = lines(text);
+    let mut blocks = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let start = i;
+        let line = lines[i].text;
+        i += 1;
+
+        let block = if line.trim().is_empty() {
+            continue;
+        } else if let Some(fence) = fence_opener(line) {
+            // An unclosed fence runs to the end of the document
+            while i < lines.len() && !closes_fence(lines[i].text, fence) {
+                i += 1;
+            }
+            i = (i + 1).min(lines.len());
+            Block::Fenced
+        } else if let Some((level, title)) = atx_heading(line) {
+            Block::Heading(level, title)
+        } else if starts_table(&lines, start) {
+            while i < lines.len() && lines[i].text.contains('|') && !lines[i].text.trim().is_empty() {
+                i += 1;
+            }
+            Block::Table
+        } else {
+            while i < lines.len() && !interrupts_paragraph(&lines, i) {
+                i += 1;
+            }
+            Block::Paragraph
+        };
+
+        let (first, last) = (lines[start].span, lines[i - 1].span);
+        blocks.push((
+            block,
+            Span {
+                char_start: first.char_start,
+                char_end: last.char_end,
+                byte_start: first.byte_start,
+                byte_end: last.byte_end,
+            },
+        ));
+    }
+
+    blocks
+}
+
+/// Section markers for each heading, named by its full heading path
+pub fn heading_locations(text: &str) -> Vec<TextLocation> {
+    let mut headings = HeadingPath::default();
+    blocks(text)
+        .into_iter()
+        .filter_map(|(block, span)| match block {
+            Block::Heading(level, title) => Some(TextLocation {
+                char_start: span.char_start,
+                section: Some(headings.enter(level, &title)),
+                ..Default::default()
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Split a paragraph over the limit at sentence ends, leaving sentences
+/// that are too long on their own to the strategy
+fn split_paragraph(text: &str, span: Span, strategy: &dyn ChunkingStrategy) -> Vec<Span> {
+    let mut pieces = Vec::new();
+    let mut char_start = span.char_start;
+
+    for (offset, sentence) in text[span.byte_start..span.byte_end].split_sentence_bound_indices() {
+        let byte_start = span.byte_start + offset;
+        let length = sentence.graphemes(true).count();
+        if strategy.measure(sentence) <= strategy.limit() {
+            pieces.push(Span {
+                char_start,
+                char_end: char_start + length,
+                byte_start,
+                byte_end: byte_start + sentence.len(),
+            });
+        } else {
+            pieces.extend(strategy.chunk(sentence).into_iter().map(|chunk| Span {
+                char_start: char_start + chunk.char_start,
+                char_end: char_start + chunk.char_end,
+                byte_start: byte_start + chunk.byte_start,
+                byte_end: byte_start + chunk.byte_end,
+            }));
+        }
+        char_start += length;
+    }
+
+    pieces
+}
+
+/// Chunk Markdown along its structure
+///
+/// Every heading starts a new chunk, and blocks are packed into chunks up to
+/// the strategy's limit. Fenced code and tables are kept whole even when
+/// they are over it; long paragraphs are split between sentences. A heading
+/// stays with the content after it rather than ending up alone.
+pub fn chunk_markdown(text: &str, strategy: &dyn ChunkingStrategy) -> Vec<TextChunk> {
+    let fits = |start: usize, end: usize| strategy.measure(&text[start..end]) <= strategy.limit();
+    let mut spans: Vec<Span> = Vec::new();
+    // Whether the last chunk holds nothing but its section's heading
+    let mut heading_only = false;
+
+    for (block, span) in blocks(text) {
+        let is_heading = matches!(block, Block::Heading(..));
+        let pieces = match block {
+            Block::Paragraph if !fits(span.byte_start, span.byte_end) => split_paragraph(text, span, strategy),
+            _ => vec![span],
+        };
+
+        for piece in pieces {
+            match spans.last_mut() {
+                Some(current) if !is_heading && (heading_only || fits(current.byte_start, piece.byte_end)) => {
+                    current.char_end = piece.char_end;
+                    current.byte_end = piece.byte_end;
+                }
+                _ => {
+                    // A heading followed straight by another adds nothing the path doesn't
+                    if heading_only {
+                        spans.pop();
+                    }
+                    spans.push(piece);
+                }
+            }
+            heading_only = is_heading;
+        }
+    }
+
+    spans
+        .into_iter()
+        .enumerate()
+        .map(|(index, span)| TextChunk {
+            text: text[span.byte_start..span.byte_end].to_string(),
+            index,
+            char_start: span.char_start,
+            char_end: span.char_end,
+            byte_start: span.byte_start,
+            byte_end: span.byte_end,
+            page_start: None,
+            page_end: None,
+            section: None,
+            line_start: None,
+            line_end: None,
+            symbol: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunking::ChunkingConfig;
+    use crate::file_processor::locate_chunks;
+
+    fn fixed_width(chunk_size: usize) -> Box<dyn ChunkingStrategy> {
+        ChunkingConfig::FixedWidth { chunk_size, overlap: 0 }.strategy(None).unwrap()
+    }
+
+    const GUIDE: &str = "# Setup\n\
+        Install the app.\n\
+        \n\
+        ## Linux\n\
+        \n\
+        ### Troubleshooting\n\
+        \n\
+        ```sh\n\
+        # not a heading\n\
+        \n\
+        sudo apt install foo\n\
+        ```\n\
+        \n\
+        | Distro | Package |\n\
+        |--------|:-------:|\n\
+        | Debian | deb     |\n\
+        \n\
+        ## Windows ##\n\
+        Run the installer.\n";
+
+    #[test]
+    fn test_heading_locations() {
+        let sections: Vec<_> = heading_locations(GUIDE)
+            .into_iter()
+            .map(|l| l.section.unwrap())
+            .collect();
+        assert_eq!(
+            sections,
+            vec!["Setup", "Setup > Linux", "Setup > Linux > Troubleshooting", "Setup > Windows"]
+        );
+    }
+
+    #[test]
+    fn test_chunk_markdown() {
+        let mut chunks = chunk_markdown(GUIDE, fixed_width(20).as_ref());
+        locate_chunks(&mut chunks, &heading_locations(GUIDE));
+
+        let chunks: Vec<_> = chunks
+            .iter()
+            .map(|c| (c.section.as_deref().unwrap(), c.text.as_str()))
+            .collect();
+        assert_eq!(
+            chunks,
+            vec![
+                ("Setup", "# Setup\nInstall the app."),
+                (
+                    "Setup > Linux > Troubleshooting",
+                    "### Troubleshooting\n\n```sh\n# not a heading\n\nsudo apt install foo\n```"
+                ),
+                (
+                    "Setup > Linux > Troubleshooting",
+                    "| Distro | Package |\n|--------|:-------:|\n| Debian | deb     |"
+                ),
+                ("Setup > Windows", "## Windows ##\nRun the installer."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_paragraph_splits_between_sentences() {
+        let text = "First sentence here. Second one follows. Third ends it.";
+        let chunks = chunk_markdown(text, fixed_width(25).as_ref());
+        let texts: Vec<_> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["First sentence here. ", "Second one follows. ", "Third ends it."]);
+        assert!(chunks.iter().all(|c| text[c.byte_start..c.byte_end] == c.text));
+    }
+}
@@ -0,0 +1,1008 @@
+use crate::error::{AppError, AppResult, ExtractionError};
+use crate::file_processor::{labelled_row, DocumentMetadata, ExtractedText, TextBuilder};
+use crate::zip_package::{attr, parent_dir, resolve_href, ZipPackage};
+use calamine::{open_workbook_auto_from_rs, Data, Reader as _};
+use chrono::Timelike;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// Placeholders holding slide furniture (numbers, dates, footers) rather than content
+const FURNITURE_PLACEHOLDERS: &[&str] = &[
+    "sldNum", "dt", "ftr", "hdr", "page-number", "date-time", "footer", "header",
+];
+
+/// Paragraphs found on a slide or notes page
+#[derive(Default)]
+struct SlideText {
+    title: Vec<String>,
+    body: Vec<String>,
+}
+
+fn office_error(e: impl std::fmt::Display) -> AppError {
+    ExtractionError::malformed("Office document", e).into()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Write one slide as a "Slide N: Title" section followed by its speaker notes
+fn render_slide(out: &mut TextBuilder, number: usize, slide: SlideText, notes: Vec<String>) {
+    if slide.title.is_empty() && slide.body.is_empty() && notes.is_empty() {
+        return;
+    }
+
+    let heading = match slide.title.is_empty() {
+        true => format!("Slide {}", number),
+        false => format!("Slide {}: {}", number, slide.title.join(" ")),
+    };
+    out.heading(1, &heading);
+    for line in &slide.body {
+        out.line(line);
+    }
+    if !notes.is_empty() {
+        out.blank_line();
+        out.line("Speaker notes:");
+        for line in &notes {
+            out.line(line);
+        }
+    }
+}
+
+/// Document title from a `dc:title` element (docProps/core.xml or meta.xml)
+fn package_title(package: &mut ZipPackage, part: &str) -> Option<String> {
+    let xml = package.read(part).ok()?;
+    let mut reader = Reader::from_str(&xml);
+    let mut in_title = false;
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) if e.local_name().as_ref() == b"title" => in_title = true,
+            Event::Text(t) if in_title => {
+                let title = collapse_whitespace(&t.unescape().ok()?);
+                return (!title.is_empty()).then_some(title);
+            }
+            Event::End(_) => in_title = false,
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+// ============ POWERPOINT ============
+
+/// Convert a PPTX presentation into text, one section per slide in presentation order
+pub fn pptx_to_text(data: &[u8]) -> AppResult<ExtractedText> {
+    let mut package = ZipPackage::open(data)?;
+    let mut out = TextBuilder::default();
+
+    for (i, path) in pptx_slide_paths(&mut package)?.iter().enumerate() {
+        let slide = drawingml_text(&package.read(path)?)?;
+        let notes = match notes_path(&mut package, path) {
+            Some(notes) => drawingml_text(&package.read(&notes)?)?.body,
+            None => Vec::new(),
+        };
+        render_slide(&mut out, i + 1, slide, notes);
+    }
+
+    let title = package_title(&mut package, "docProps/core.xml");
+    Ok(out.finish(DocumentMetadata { title, ..Default::default() }))
+}
+
+/// Targets of a part's relationships file, keyed by relationship id, with their type
+fn relationships(xml: &str, base_dir: &str) -> AppResult<HashMap<String, (String, String)>> {
+    let mut reader = Reader::from_str(xml);
+    let mut relationships = HashMap::new();
+    loop {
+        match reader.read_event().map_err(office_error)? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"Relationship" => {
+                if attr(&e, b"TargetMode").as_deref() == Some("External") {
+                    continue;
+                }
+                if let (Some(id), Some(target)) = (attr(&e, b"Id"), attr(&e, b"Target")) {
+                    let kind = attr(&e, b"Type").unwrap_or_default();
+                    relationships.insert(id, (kind, resolve_href(base_dir, &target)));
+                }
+            }
+            Event::Eof => return Ok(relationships),
+            _ => {}
+        }
+    }
+}
+
+/// Slide parts in the order listed by ppt/presentation.xml
+fn pptx_slide_paths(package: &mut ZipPackage) -> AppResult<Vec<String>> {
+    let relationships = relationships(&package.read("ppt/_rels/presentation.xml.rels")?, "ppt")?;
+    let presentation = package.read("ppt/presentation.xml")?;
+
+    let mut reader = Reader::from_str(&presentation);
+    let mut paths = Vec::new();
+    loop {
+        match reader.read_event().map_err(office_error)? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"sldId" => {
+                // The relationship is the prefixed `r:id`; the bare `id` is the slide's own number
+                let rel_id = e
+                    .attributes()
+                    .flatten()
+                    .find(|a| a.key.prefix().is_some() && a.key.local_name().as_ref() == b"id")
+                    .and_then(|a| a.unescape_value().ok());
+                if let Some((_, path)) = rel_id.and_then(|id| relationships.get(id.as_ref())) {
+                    paths.push(path.clone());
+                }
+            }
+            Event::Eof => return Ok(paths),
+            _ => {}
+        }
+    }
+}
+
+/// Notes page attached to a slide, if any
+fn notes_path(package: &mut ZipPackage, slide_path: &str) -> Option<String> {
+    let dir = parent_dir(slide_path);
+    let file = slide_path.rsplit('/').next()?;
+    let xml = package.read(&format!("{}/_rels/{}.rels", dir, file)).ok()?;
+    relationships(&xml, dir)
+        .ok()?
+        .into_values()
+        .find(|(kind, _)| kind.ends_with("/notesSlide"))
+        .map(|(_, path)| path)
+}
+
+/// Paragraphs of a DrawingML slide or notes page, with title placeholders kept apart
+fn drawingml_text(xml: &str) -> AppResult<SlideText> {
+    let mut reader = Reader::from_str(xml);
+    let mut text = SlideText::default();
+    let mut is_title = false;
+    let mut is_furniture = false;
+    let mut in_run = false;
+    let mut paragraph = String::new();
+    let mut row: Option<Vec<String>> = None;
+    let mut cell: Option<String> = None;
+
+    loop {
+        let event = reader.read_event().map_err(office_error)?;
+        match &event {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"sp" | b"graphicFrame" => {
+                    is_title = false;
+                    is_furniture = false;
+                }
+                b"ph" => {
+                    let kind = attr(e, b"type");
+                    is_title = matches!(kind.as_deref(), Some("title" | "ctrTitle"));
+                    is_furniture = kind.is_some_and(|k| FURNITURE_PLACEHOLDERS.contains(&k.as_str()));
+                }
+                b"t" => in_run = matches!(event, Event::Start(_)),
+                b"br" => paragraph.push(' '),
+                b"tr" => row = Some(Vec::new()),
+                b"tc" => cell = Some(String::new()),
+                _ => {}
+            },
+            Event::Text(t) if in_run => paragraph.push_str(&t.unescape().map_err(office_error)?),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"t" => in_run = false,
+                b"p" => {
+                    let line = collapse_whitespace(&std::mem::take(&mut paragraph));
+                    if line.is_empty() || is_furniture {
+                        continue;
+                    }
+                    match cell.as_mut() {
+                        Some(cell) if cell.is_empty() => cell.push_str(&line),
+                        Some(cell) => {
+                            cell.push(' ');
+                            cell.push_str(&line);
+                        }
+                        None if is_title => text.title.push(line),
+                        None => text.body.push(line),
+                    }
+                }
+                b"tc" => {
+                    if let (Some(row), Some(cell)) = (row.as_mut(), cell.take()) {
+                        row.push(cell);
+                    }
+                }
+                b"tr" => {
+                    if let Some(row) = row.take().filter(|r| r.iter().any(|c| !c.is_empty())) {
+                        text.body.push(row.join(" | "));
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => return Ok(text),
+            _ => {}
+        }
+    }
+}
+
+// ============ WORD ============
+
+/// Elements whose content duplicates or no longer belongs to the visible text
+const WORD_SKIPPED: &[&[u8]] = &[b"Fallback", b"moveFrom", b"instrText", b"delText"];
+
+/// A paragraph being read, with the properties that decide how it's written
+#[derive(Default)]
+struct WordParagraph {
+    text: String,
+    style: Option<String>,
+    /// Outline level set on the paragraph itself (0 = top)
+    outline: Option<u8>,
+    /// Nesting level of a numbered or bulleted list item
+    list_level: Option<usize>,
+}
+
+/// A table being read; cells of nested tables are read into their own entry
+#[derive(Default)]
+struct WordTable {
+    rows: Vec<Vec<String>>,
+    header_rows: usize,
+    is_header: bool,
+    row: Option<Vec<String>>,
+    cell: Option<String>,
+}
+
+impl WordTable {
+    fn lines(&self) -> Vec<String> {
+        match self.header_rows {
+            0 => self.rows.iter().map(|row| row.join(" | ")).collect(),
+            n => self.rows[n..].iter().map(|row| labelled_row(&self.rows[0], row)).collect(),
+        }
+    }
+}
+
+/// Convert a DOCX document into text
+///
+/// Heading styles become sections, list items are bulleted and tables are
+/// written like other office tables. Footnotes and endnotes follow the body,
+/// referenced by `[^1]` markers, and then the text of headers and footers.
+pub fn docx_to_text(data: &[u8]) -> AppResult<ExtractedText> {
+    let mut package = ZipPackage::open(data)?;
+    let document = package.read("word/document.xml")?;
+    let styles = match package.read("word/styles.xml") {
+        Ok(xml) => heading_styles(&xml)?,
+        Err(_) => HashMap::new(),
+    };
+
+    let mut out = TextBuilder::default();
+    wordml_body(&document, &styles, &mut out)?;
+
+    for (part, heading, marker) in [
+        ("word/footnotes.xml", "Footnotes", "^"),
+        ("word/endnotes.xml", "Endnotes", "^e"),
+    ] {
+        let Ok(xml) = package.read(part) else { continue };
+        let notes = word_notes(&xml)?;
+        if !notes.is_empty() {
+            out.heading(1, heading);
+            for (id, text) in notes {
+                out.line(&format!("[{}{}]: {}", marker, id, text));
+            }
+        }
+    }
+
+    // The same header usually repeats across sections, so each line is kept once
+    let mut furniture: Vec<String> = Vec::new();
+    for path in header_footer_paths(&mut package) {
+        for (_, line) in word_paragraphs(&package.read(&path)?)? {
+            if !furniture.contains(&line) {
+                furniture.push(line);
+            }
+        }
+    }
+    if !furniture.is_empty() {
+        out.heading(1, "Headers and footers");
+        furniture.iter().for_each(|line| out.line(line));
+    }
+
+    let title = package_title(&mut package, "docProps/core.xml");
+    Ok(out.finish(DocumentMetadata { title, ..Default::default() }))
+}
+
+/// Heading level (1 = top) of each paragraph style that is a heading
+///
+/// A style is a heading if it has an outline level, is named "heading N" or
+/// "Title", or is based on a style that is.
+fn heading_styles(xml: &str) -> AppResult<HashMap<String, u8>> {
+    // Style id -> (own level, based on)
+    let mut styles: HashMap<String, (Option<u8>, Option<String>)> = HashMap::new();
+    let mut reader = Reader::from_str(xml);
+    let mut current: Option<String> = None;
+
+    loop {
+        match reader.read_event().map_err(office_error)? {
+            Event::Start(e) if e.local_name().as_ref() == b"style" => {
+                current = attr(&e, b"styleId").filter(|_| attr(&e, b"type").as_deref() == Some("paragraph"));
+            }
+            Event::Start(e) | Event::Empty(e) => {
+                let Some(entry) = current.as_ref().map(|id| styles.entry(id.clone()).or_default()) else {
+                    continue;
+                };
+                let val = attr(&e, b"val").unwrap_or_default();
+                match e.local_name().as_ref() {
+                    b"name" => entry.0 = entry.0.or(named_heading_level(&val)),
+                    b"outlineLvl" => entry.0 = val.parse::<u8>().ok().filter(|l| *l < 9).map(|l| l + 1).or(entry.0),
+                    b"basedOn" => entry.1 = Some(val),
+                    _ => {}
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"style" => current = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let level = |id: &str| {
+        let mut id = id;
+        // Inheritance chains are short; the bound only guards against cycles
+        for _ in 0..10 {
+            let (level, based_on) = styles.get(id)?;
+            match (level, based_on) {
+                (Some(level), _) => return Some(*level),
+                (None, Some(parent)) => id = parent,
+                (None, None) => return None,
+            }
+        }
+        None
+    };
+    Ok(styles
+        .keys()
+        .filter_map(|id| level(id).map(|l| (id.clone(), l)))
+        .collect())
+}
+
+/// Level of a built-in heading style by name or id, e.g. "heading 2" or "Heading2"
+fn named_heading_level(name: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    if name == "title" {
+        return Some(1);
+    }
+    let level = name.strip_prefix("heading")?.trim().parse::<u8>().ok()?;
+    (1..=9).contains(&level).then_some(level)
+}
+
+/// Write the body of a WordprocessingML part
+fn wordml_body(xml: &str, styles: &HashMap<String, u8>, out: &mut TextBuilder) -> AppResult<()> {
+    let mut reader = Reader::from_str(xml);
+    let mut skipped = 0;
+    let mut in_text = false;
+    // Text boxes nest paragraphs inside paragraphs, and tables nest in cells
+    let mut paragraphs: Vec<WordParagraph> = Vec::new();
+    let mut tables: Vec<WordTable> = Vec::new();
+
+    loop {
+        let event = reader.read_event().map_err(office_error)?;
+        if skipped > 0 {
+            match event {
+                Event::Start(_) => skipped += 1,
+                Event::End(_) => skipped -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            continue;
+        }
+
+        match &event {
+            Event::Start(e) | Event::Empty(e) => {
+                let is_start = matches!(event, Event::Start(_));
+                let paragraph = paragraphs.last_mut();
+                match e.local_name().as_ref() {
+                    name if is_start && WORD_SKIPPED.contains(&name) => skipped = 1,
+                    b"p" if is_start => paragraphs.push(WordParagraph::default()),
+                    b"t" => in_text = is_start,
+                    b"tab" | b"br" | b"cr" => paragraph.into_iter().for_each(|p| p.text.push(' ')),
+                    b"noBreakHyphen" => paragraph.into_iter().for_each(|p| p.text.push('-')),
+                    b"footnoteReference" | b"endnoteReference" => {
+                        let marker = if e.local_name().as_ref() == b"footnoteReference" { "^" } else { "^e" };
+                        if let (Some(p), Some(id)) = (paragraph, attr(e, b"id")) {
+                            p.text.push_str(&format!("[{}{}]", marker, id));
+                        }
+                    }
+                    b"pStyle" => paragraph.into_iter().for_each(|p| p.style = attr(e, b"val")),
+                    b"outlineLvl" => {
+                        let level = attr(e, b"val").and_then(|l| l.parse().ok()).filter(|l| *l < 9);
+                        paragraph.into_iter().for_each(|p| p.outline = level);
+                    }
+                    b"numPr" => paragraph.into_iter().for_each(|p| p.list_level = Some(0)),
+                    b"ilvl" => {
+                        if let Some(p) = paragraph.filter(|p| p.list_level.is_some()) {
+                            p.list_level = attr(e, b"val").and_then(|l| l.parse().ok()).or(Some(0));
+                        }
+                    }
+                    // Numbering id 0 switches off numbering inherited from the style
+                    b"numId" if attr(e, b"val").as_deref() == Some("0") => {
+                        paragraph.into_iter().for_each(|p| p.list_level = None)
+                    }
+                    b"tbl" if is_start => tables.push(WordTable::default()),
+                    b"tr" if is_start => {
+                        if let Some(table) = tables.last_mut() {
+                            table.row = Some(Vec::new());
+                            table.is_header = false;
+                        }
+                    }
+                    b"tblHeader" => {
+                        let on = !matches!(attr(e, b"val").as_deref(), Some("0" | "false" | "off"));
+                        tables.last_mut().into_iter().for_each(|t| t.is_header = on);
+                    }
+                    b"tc" if is_start => tables.last_mut().into_iter().for_each(|t| t.cell = Some(String::new())),
+                    _ => {}
+                }
+            }
+            Event::Text(t) if in_text => {
+                if let Some(paragraph) = paragraphs.last_mut() {
+                    paragraph.text.push_str(&t.unescape().map_err(office_error)?);
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"t" => in_text = false,
+                b"p" => {
+                    let Some(paragraph) = paragraphs.pop() else { continue };
+                    let line = collapse_whitespace(&paragraph.text);
+                    let level = paragraph
+                        .outline
+                        .map(|l| l + 1)
+                        .or_else(|| paragraph.style.as_ref().and_then(|s| styles.get(s).copied().or_else(|| named_heading_level(s))));
+
+                    if let Some(cell) = tables.last_mut().and_then(|t| t.cell.as_mut()) {
+                        if !cell.is_empty() && !line.is_empty() {
+                            cell.push(' ');
+                        }
+                        cell.push_str(&line);
+                    } else if let Some(level) = level {
+                        out.heading(level, &line);
+                    } else if let Some(depth) = paragraph.list_level {
+                        out.line(&format!("{}- {}", "  ".repeat(depth), line));
+                    } else {
+                        out.blank_line();
+                        out.line(&line);
+                        out.blank_line();
+                    }
+                }
+                b"tc" => {
+                    if let Some(table) = tables.last_mut() {
+                        if let (Some(row), Some(cell)) = (table.row.as_mut(), table.cell.take()) {
+                            row.push(cell);
+                        }
+                    }
+                }
+                b"tr" => {
+                    if let Some(table) = tables.last_mut() {
+                        if let Some(row) = table.row.take().filter(|r| r.iter().any(|c| !c.is_empty())) {
+                            // Only rows at the top of the table repeat as its header
+                            if table.is_header && table.header_rows == table.rows.len() {
+                                table.header_rows += 1;
+                            }
+                            table.rows.push(row);
+                        }
+                    }
+                }
+                b"tbl" => {
+                    let Some(table) = tables.pop() else { continue };
+                    match tables.last_mut().and_then(|t| t.cell.as_mut()) {
+                        Some(cell) => {
+                            if !cell.is_empty() {
+                                cell.push(' ');
+                            }
+                            cell.push_str(&table.lines().join("; "));
+                        }
+                        None => {
+                            out.blank_line();
+                            table.lines().iter().for_each(|line| out.line(line));
+                            out.blank_line();
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Text of each paragraph in a part, with the id of the footnote or endnote it belongs to
+fn word_paragraphs(xml: &str) -> AppResult<Vec<(Option<String>, String)>> {
+    let mut reader = Reader::from_str(xml);
+    let mut paragraphs = Vec::new();
+    let mut note: Option<String> = None;
+    let mut skipped = 0;
+    let mut in_text = false;
+    let mut paragraph = String::new();
+
+    loop {
+        let event = reader.read_event().map_err(office_error)?;
+        if skipped > 0 {
+            match event {
+                Event::Start(_) => skipped += 1,
+                Event::End(_) => skipped -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            continue;
+        }
+
+        match &event {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                name if matches!(event, Event::Start(_)) && WORD_SKIPPED.contains(&name) => skipped = 1,
+                // Separator notes hold the rule above the notes, not text
+                b"footnote" | b"endnote" => note = attr(e, b"id").filter(|_| attr(e, b"type").is_none()),
+                b"t" => in_text = matches!(event, Event::Start(_)),
+                b"tab" | b"br" | b"cr" => paragraph.push(' '),
+                _ => {}
+            },
+            Event::Text(t) if in_text => paragraph.push_str(&t.unescape().map_err(office_error)?),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"t" => in_text = false,
+                b"p" => {
+                    let line = collapse_whitespace(&std::mem::take(&mut paragraph));
+                    if !line.is_empty() {
+                        paragraphs.push((note.clone(), line));
+                    }
+                }
+                b"footnote" | b"endnote" => note = None,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(paragraphs)
+}
+
+/// Footnotes or endnotes in document order, each with its paragraphs joined
+fn word_notes(xml: &str) -> AppResult<Vec<(String, String)>> {
+    let mut notes: Vec<(String, String)> = Vec::new();
+    for (id, line) in word_paragraphs(xml)? {
+        let Some(id) = id else { continue };
+        match notes.last_mut() {
+            Some((last, text)) if *last == id => {
+                text.push(' ');
+                text.push_str(&line);
+            }
+            _ => notes.push((id, line)),
+        }
+    }
+    Ok(notes)
+}
+
+/// Header and footer parts referenced by the document
+fn header_footer_paths(package: &mut ZipPackage) -> Vec<String> {
+    let Some(rels) = package
+        .read("word/_rels/document.xml.rels")
+        .ok()
+        .and_then(|xml| relationships(&xml, "word").ok())
+    else {
+        return Vec::new();
+    };
+    let mut paths: Vec<String> = rels
+        .into_values()
+        .filter(|(kind, _)| kind.ends_with("/header") || kind.ends_with("/footer"))
+        .map(|(_, path)| path)
+        .collect();
+    // Relationships aren't ordered; keep headers before footers and numbering stable
+    paths.sort_by_key(|path| (path.contains("footer"), path.clone()));
+    paths
+}
+
+// ============ SPREADSHEETS ============
+
+/// Convert an XLSX or ODS workbook into text, one section per sheet
+///
+/// The first non-empty row of each sheet is taken as its header and every
+/// following row is written as a `Header: value` record. `title_part` is the
+/// package part holding the document title.
+pub fn spreadsheet_to_text(data: &[u8], title_part: &str) -> AppResult<ExtractedText> {
+    // calamine opens the zip itself, trusting the sizes checked before extraction
+    ZipPackage::open(data)?.verify_sizes()?;
+    let mut workbook = open_workbook_auto_from_rs(Cursor::new(data)).map_err(office_error)?;
+    let mut out = TextBuilder::default();
+
+    for name in workbook.sheet_names() {
+        let range = workbook.worksheet_range(&name).map_err(office_error)?;
+        let mut rows = range
+            .rows()
+            .map(|row| row.iter().map(cell_text).collect::<Vec<_>>())
+            .filter(|cells| cells.iter().any(|c| !c.is_empty()));
+        let Some(header) = rows.next() else { continue };
+
+        out.heading(1, &format!("Sheet: {}", name));
+        let mut records = 0;
+        for cells in rows {
+            out.line(&labelled_row(&header, &cells));
+            records += 1;
+        }
+        // A lone row is data, not a header
+        if records == 0 {
+            out.line(&header.join(" | "));
+        }
+    }
+
+    let title = ZipPackage::open(data)
+        .ok()
+        .and_then(|mut package| package_title(&mut package, title_part));
+    Ok(out.finish(DocumentMetadata { title, ..Default::default() }))
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(value) => match value.as_datetime() {
+            Some(dt) if dt.num_seconds_from_midnight() == 0 => dt.date().to_string(),
+            Some(dt) => dt.to_string(),
+            None => value.to_string(),
+        },
+        _ => collapse_whitespace(&cell.to_string()),
+    }
+}
+
+// ============ OPENDOCUMENT ============
+
+/// Convert an ODT text document into text, keeping headings, lists and tables
+pub fn odt_to_text(data: &[u8]) -> AppResult<ExtractedText> {
+    let mut package = ZipPackage::open(data)?;
+    let content = package.read("content.xml")?;
+
+    let mut reader = Reader::from_str(&content);
+    let mut out = TextBuilder::default();
+    let mut paragraph = String::new();
+    let mut heading_level: u8 = 1;
+    // Depth inside footnotes and annotations, which are left out
+    let mut skipped = 0;
+    let mut list_depth = 0;
+    let mut item_started = false;
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut header_rows = 0;
+    let mut in_header = false;
+    let mut row: Option<Vec<String>> = None;
+    let mut cell: Option<String> = None;
+
+    loop {
+        let event = reader.read_event().map_err(office_error)?;
+        if skipped > 0 {
+            match event {
+                Event::Start(_) => skipped += 1,
+                Event::End(_) => skipped -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            continue;
+        }
+
+        match &event {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"note" | b"annotation" | b"tracked-changes" => skipped = 1,
+                b"h" => {
+                    heading_level = attr(e, b"outline-level").and_then(|l| l.parse().ok()).unwrap_or(1);
+                    paragraph.clear();
+                }
+                b"p" => paragraph.clear(),
+                b"list" => list_depth += 1,
+                b"list-item" => item_started = true,
+                b"table" => {
+                    rows.clear();
+                    header_rows = 0;
+                }
+                b"table-header-rows" => in_header = true,
+                b"table-row" => row = Some(Vec::new()),
+                b"table-cell" => cell = Some(String::new()),
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"s" | b"tab" | b"line-break" => paragraph.push(' '),
+                b"table-cell" => row.iter_mut().for_each(|r| r.push(String::new())),
+                _ => {}
+            },
+            Event::Text(t) => paragraph.push_str(&t.unescape().map_err(office_error)?),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"h" => out.heading(heading_level, &std::mem::take(&mut paragraph)),
+                b"p" => {
+                    let line = collapse_whitespace(&std::mem::take(&mut paragraph));
+                    if let Some(cell) = cell.as_mut() {
+                        if !cell.is_empty() && !line.is_empty() {
+                            cell.push(' ');
+                        }
+                        cell.push_str(&line);
+                    } else if list_depth > 0 {
+                        let indent = "  ".repeat(list_depth - 1);
+                        let marker = if std::mem::take(&mut item_started) { "- " } else { "  " };
+                        out.line(&format!("{}{}{}", indent, marker, line));
+                    } else {
+                        out.line(&line);
+                        out.blank_line();
+                    }
+                }
+                b"list" => {
+                    list_depth -= 1;
+                    if list_depth == 0 {
+                        out.blank_line();
+                    }
+                }
+                b"table-cell" => {
+                    if let (Some(row), Some(cell)) = (row.as_mut(), cell.take()) {
+                        row.push(cell);
+                    }
+                }
+                b"table-row" => {
+                    if let Some(row) = row.take().filter(|r| r.iter().any(|c| !c.is_empty())) {
+                        header_rows += in_header as usize;
+                        rows.push(row);
+                    }
+                }
+                b"table-header-rows" => in_header = false,
+                b"table" => {
+                    out.blank_line();
+                    match header_rows {
+                        0 => rows.iter().for_each(|row| out.line(&row.join(" | "))),
+                        _ => {
+                            for row in &rows[header_rows..] {
+                                out.line(&labelled_row(&rows[0], row));
+                            }
+                        }
+                    }
+                    out.blank_line();
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let title = package_title(&mut package, "meta.xml");
+    Ok(out.finish(DocumentMetadata { title, ..Default::default() }))
+}
+
+/// Convert an ODP presentation into text, one section per slide with its notes
+pub fn odp_to_text(data: &[u8]) -> AppResult<ExtractedText> {
+    let mut package = ZipPackage::open(data)?;
+    let content = package.read("content.xml")?;
+
+    let mut reader = Reader::from_str(&content);
+    let mut out = TextBuilder::default();
+    let mut number = 0;
+    let mut slide = SlideText::default();
+    let mut notes = Vec::new();
+    let mut in_notes = false;
+    let mut frame_class = String::new();
+    let mut paragraph = String::new();
+
+    loop {
+        match reader.read_event().map_err(office_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"page" => number += 1,
+                b"notes" => in_notes = true,
+                b"frame" => frame_class = attr(&e, b"class").unwrap_or_default(),
+                b"p" | b"h" => paragraph.clear(),
+                _ => {}
+            },
+            Event::Empty(e) if matches!(e.local_name().as_ref(), b"s" | b"tab" | b"line-break") => {
+                paragraph.push(' ')
+            }
+            Event::Text(t) => paragraph.push_str(&t.unescape().map_err(office_error)?),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"p" | b"h" => {
+                    let line = collapse_whitespace(&std::mem::take(&mut paragraph));
+                    if line.is_empty() || FURNITURE_PLACEHOLDERS.contains(&frame_class.as_str()) {
+                        continue;
+                    }
+                    match (in_notes, frame_class.as_str()) {
+                        (true, _) => notes.push(line),
+                        (false, "title") => slide.title.push(line),
+                        (false, _) => slide.body.push(line),
+                    }
+                }
+                b"frame" => frame_class.clear(),
+                b"notes" => in_notes = false,
+                b"page" => render_slide(&mut out, number, std::mem::take(&mut slide), std::mem::take(&mut notes)),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let title = package_title(&mut package, "meta.xml");
+    Ok(out.finish(DocumentMetadata { title, ..Default::default() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn build_package(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    const P: &str = r#"xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships""#;
+
+    #[test]
+    fn test_pptx_to_text() {
+        let presentation = format!(
+            r#"<p:presentation {P}><p:sldIdLst><p:sldId id="256" r:id="rId3"/><p:sldId id="257" r:id="rId2"/></p:sldIdLst></p:presentation>"#
+        );
+        let rels = r#"<Relationships>
+            <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide" Target="slides/slide1.xml"/>
+            <Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide" Target="slides/slide2.xml"/>
+        </Relationships>"#;
+        let intro = format!(
+            r#"<p:sld {P}><p:cSld><p:spTree>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>Quarterly Review</a:t></a:r></a:p></p:txBody></p:sp>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph type="body"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>Revenue </a:t></a:r><a:r><a:t>up 12%</a:t></a:r></a:p></p:txBody></p:sp>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph type="sldNum"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:fld><a:t>1</a:t></a:fld></a:p></p:txBody></p:sp>
+            </p:spTree></p:cSld></p:sld>"#
+        );
+        let closing = format!(
+            r#"<p:sld {P}><p:cSld><p:spTree><p:sp><p:txBody><a:p><a:r><a:t>Questions?</a:t></a:r></a:p></p:txBody></p:sp></p:spTree></p:cSld></p:sld>"#
+        );
+        let intro_rels = r#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesSlide" Target="../notesSlides/notesSlide1.xml"/></Relationships>"#;
+        let notes = format!(
+            r#"<p:notes {P}><p:cSld><p:spTree><p:sp><p:nvSpPr><p:nvPr><p:ph type="body"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>Mention the new region.</a:t></a:r></a:p></p:txBody></p:sp></p:spTree></p:cSld></p:notes>"#
+        );
+        let core = r#"<cp:coreProperties xmlns:cp="cp" xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Q3 Review</dc:title></cp:coreProperties>"#;
+
+        let pptx = build_package(&[
+            ("ppt/presentation.xml", &presentation),
+            ("ppt/_rels/presentation.xml.rels", rels),
+            ("ppt/slides/slide1.xml", &closing),
+            ("ppt/slides/slide2.xml", &intro),
+            ("ppt/slides/_rels/slide2.xml.rels", intro_rels),
+            ("ppt/notesSlides/notesSlide1.xml", &notes),
+            ("docProps/core.xml", core),
+        ]);
+
+        let extracted = pptx_to_text(&pptx).unwrap();
+        assert_eq!(
+            extracted.text,
+            "# Slide 1: Quarterly Review\n\n\
+             Revenue up 12%\n\n\
+             Speaker notes:\n\
+             Mention the new region.\n\n\
+             # Slide 2\n\n\
+             Questions?"
+        );
+        assert_eq!(extracted.metadata.title.as_deref(), Some("Q3 Review"));
+        let sections: Vec<_> = extracted.locations.iter().filter_map(|l| l.section.as_deref()).collect();
+        assert_eq!(sections, vec!["Slide 1: Quarterly Review", "Slide 2"]);
+    }
+
+    #[test]
+    fn test_docx_to_text() {
+        const W: &str = r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships""#;
+
+        let document = format!(
+            r#"<w:document {W}><w:body>
+                <w:p><w:pPr><w:pStyle w:val="Title"/></w:pPr><w:r><w:t>Handbook</w:t></w:r></w:p>
+                <w:p><w:r><w:t xml:space="preserve">See the </w:t></w:r><w:hyperlink r:id="rId9"><w:r><w:t>wiki</w:t></w:r></w:hyperlink><w:r><w:t>.</w:t></w:r><w:r><w:footnoteReference w:id="1"/></w:r></w:p>
+                <w:p><w:pPr><w:pStyle w:val="Step"/></w:pPr><w:r><w:t>Install</w:t></w:r></w:p>
+                <w:p><w:pPr><w:numPr><w:ilvl w:val="0"/><w:numId w:val="3"/></w:numPr></w:pPr><w:r><w:t>Download</w:t></w:r></w:p>
+                <w:p><w:pPr><w:numPr><w:ilvl w:val="1"/><w:numId w:val="3"/></w:numPr></w:pPr><w:r><w:fldChar w:fldCharType="begin"/></w:r><w:r><w:instrText>PAGE</w:instrText></w:r><w:r><w:t>Verify</w:t></w:r></w:p>
+                <w:tbl>
+                    <w:tr><w:trPr><w:tblHeader/></w:trPr><w:tc><w:p><w:r><w:t>OS</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>Size</w:t></w:r></w:p></w:tc></w:tr>
+                    <w:tr><w:tc><w:p><w:r><w:t>Linux</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>80 MB</w:t></w:r></w:p></w:tc></w:tr>
+                </w:tbl>
+                <w:p><w:r><w:t>Done</w:t></w:r><w:del><w:r><w:delText>!</w:delText></w:r></w:del></w:p>
+            </w:body></w:document>"#
+        );
+        let styles = format!(
+            r#"<w:styles {W}>
+                <w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:pPr><w:outlineLvl w:val="0"/></w:pPr></w:style>
+                <w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Heading1"/><w:pPr><w:outlineLvl w:val="1"/></w:pPr></w:style>
+                <w:style w:type="paragraph" w:styleId="Step"><w:name w:val="Step"/><w:basedOn w:val="Heading2"/></w:style>
+            </w:styles>"#
+        );
+        let footnotes = format!(
+            r#"<w:footnotes {W}>
+                <w:footnote w:type="separator" w:id="-1"><w:p><w:r><w:separator/></w:r></w:p></w:footnote>
+                <w:footnote w:id="1"><w:p><w:r><w:footnoteRef/></w:r><w:r><w:t>Internal only.</w:t></w:r></w:p></w:footnote>
+            </w:footnotes>"#
+        );
+        let rels = r#"<Relationships>
+            <Relationship Id="rId7" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/footer" Target="footer1.xml"/>
+            <Relationship Id="rId8" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/header" Target="header1.xml"/>
+            <Relationship Id="rId9" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com" TargetMode="External"/>
+        </Relationships>"#;
+        let header = format!(r#"<w:hdr {W}><w:p><w:r><w:t>ACME Corp</w:t></w:r></w:p></w:hdr>"#);
+        let footer = format!(r#"<w:ftr {W}><w:p><w:r><w:t>Confidential</w:t></w:r></w:p></w:ftr>"#);
+
+        let docx = build_package(&[
+            ("word/document.xml", &document),
+            ("word/styles.xml", &styles),
+            ("word/footnotes.xml", &footnotes),
+            ("word/_rels/document.xml.rels", rels),
+            ("word/header1.xml", &header),
+            ("word/footer1.xml", &footer),
+        ]);
+
+        let extracted = docx_to_text(&docx).unwrap();
+        assert_eq!(
+            extracted.text,
+            "# Handbook\n\n\
+             See the wiki.[^1]\n\n\
+             ## Install\n\n\
+             - Download\n  \
+             - Verify\n\n\
+             OS: Linux; Size: 80 MB\n\n\
+             Done\n\n\
+             # Footnotes\n\n\
+             [^1]: Internal only.\n\n\
+             # Headers and footers\n\n\
+             ACME Corp\n\
+             Confidential"
+        );
+        let sections: Vec<_> = extracted.locations.iter().filter_map(|l| l.section.as_deref()).collect();
+        assert_eq!(sections, vec!["Handbook", "Handbook > Install", "Footnotes", "Headers and footers"]);
+    }
+
+    #[test]
+    fn test_spreadsheet_to_text() {
+        let workbook = r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <sheets><sheet name="Inventory" sheetId="1" r:id="rId1"/></sheets></workbook>"#;
+        let rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/></Relationships>"#;
+        let sheet = r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>
+            <row r="1"><c r="A1" t="inlineStr"><is><t>Item</t></is></c><c r="B1" t="inlineStr"><is><t>Count</t></is></c></row>
+            <row r="2"><c r="A2" t="inlineStr"><is><t>Bolts</t></is></c><c r="B2"><v>40</v></c></row>
+            <row r="3"><c r="A3" t="inlineStr"><is><t>Nuts</t></is></c></row>
+        </sheetData></worksheet>"#;
+        let content_types = r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+            <Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>
+            <Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/></Types>"#;
+
+        let xlsx = build_package(&[
+            ("[Content_Types].xml", content_types),
+            ("xl/workbook.xml", workbook),
+            ("xl/_rels/workbook.xml.rels", rels),
+            ("xl/worksheets/sheet1.xml", sheet),
+        ]);
+
+        let extracted = spreadsheet_to_text(&xlsx, "docProps/core.xml").unwrap();
+        assert_eq!(extracted.text, "# Sheet: Inventory\n\nItem: Bolts; Count: 40\nItem: Nuts");
+        assert_eq!(extracted.locations[0].section.as_deref(), Some("Sheet: Inventory"));
+    }
+
+    #[test]
+    fn test_opendocument_to_text() {
+        const NS: &str = r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:draw="urn:oasis:names:tc:opendocument:xmlns:drawing:1.0" xmlns:presentation="urn:oasis:names:tc:opendocument:xmlns:presentation:1.0""#;
+
+        let odt = format!(
+            r#"<office:document-content {NS}><office:body><office:text>
+                <text:h text:outline-level="1">Guide</text:h>
+                <text:p>Read<text:s/>this first.<text:note><text:note-body><text:p>A footnote</text:p></text:note-body></text:note></text:p>
+                <text:h text:outline-level="2">Steps</text:h>
+                <text:list><text:list-item><text:p>Unpack</text:p></text:list-item><text:list-item><text:p>Assemble</text:p></text:list-item></text:list>
+                <table:table><table:table-header-rows><table:table-row><table:table-cell><text:p>Part</text:p></table:table-cell><table:table-cell><text:p>Qty</text:p></table:table-cell></table:table-row></table:table-header-rows>
+                <table:table-row><table:table-cell><text:p>Screw</text:p></table:table-cell><table:table-cell><text:p>8</text:p></table:table-cell></table:table-row></table:table>
+            </office:text></office:body></office:document-content>"#
+        );
+        let extracted = odt_to_text(&build_package(&[("content.xml", &odt)])).unwrap();
+        assert_eq!(
+            extracted.text,
+            "# Guide\n\nRead this first.\n\n## Steps\n\n- Unpack\n- Assemble\n\nPart: Screw; Qty: 8"
+        );
+        let sections: Vec<_> = extracted.locations.iter().filter_map(|l| l.section.as_deref()).collect();
+        assert_eq!(sections, vec!["Guide", "Guide > Steps"]);
+
+        let odp = format!(
+            r#"<office:document-content {NS}><office:body><office:presentation>
+                <draw:page draw:name="page1">
+                    <draw:frame presentation:class="title"><draw:text-box><text:p>Roadmap</text:p></draw:text-box></draw:frame>
+                    <draw:frame presentation:class="outline"><draw:text-box><text:list><text:list-item><text:p>Beta in May</text:p></text:list-item></text:list></draw:text-box></draw:frame>
+                    <draw:frame presentation:class="page-number"><draw:text-box><text:p>1</text:p></draw:text-box></draw:frame>
+                    <presentation:notes><draw:frame presentation:class="notes"><draw:text-box><text:p>Keep it short.</text:p></draw:text-box></draw:frame></presentation:notes>
+                </draw:page>
+            </office:presentation></office:body></office:document-content>"#
+        );
+        let meta = r#"<office:document-meta xmlns:office="o" xmlns:dc="http://purl.org/dc/elements/1.1/"><office:meta><dc:title>Plans</dc:title></office:meta></office:document-meta>"#;
+        let extracted = odp_to_text(&build_package(&[("content.xml", &odp), ("meta.xml", meta)])).unwrap();
+        assert_eq!(extracted.text, "# Slide 1: Roadmap\n\nBeta in May\n\nSpeaker notes:\nKeep it short.");
+        assert_eq!(extracted.metadata.title.as_deref(), Some("Plans"));
+    }
+}
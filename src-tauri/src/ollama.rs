@@ -47,6 +47,10 @@ pub struct GenerateResponse {
 }
 
 /// Service for interacting with Ollama
+///
+/// Clones share the HTTP connection pool, so long jobs can work on a clone
+/// instead of holding the shared service's lock.
+#[derive(Clone)]
 pub struct OllamaService {
     base_url: String,
     client: Client,
@@ -177,8 +181,6 @@ impl OllamaService {
         prompt: &str,
         context: Option<Vec<String>>,
     ) -> AppResult<String> {
-        let url = format!("{}/api/generate", self.base_url);
-
         // Build the full prompt with context if provided
         let full_prompt = if let Some(ctx) = context {
             format!("Context:\n{}\n\nQuestion: {}", ctx.join("\n"), prompt)
@@ -186,18 +188,35 @@ impl OllamaService {
             prompt.to_string()
         };
 
-        let request = GenerateRequest {
-            model: model.to_string(),
-            prompt: full_prompt,
-            stream: false,
-            context: None,
-            options: Some(GenerateOptions {
+        self.generate_with_options(
+            model,
+            &full_prompt,
+            GenerateOptions {
                 temperature: Some(0.7),
                 top_p: Some(0.9),
                 top_k: Some(40),
                 num_predict: Some(256),  // Limit max tokens for faster responses
                 num_ctx: Some(2048),     // Smaller context window for faster processing
-            }),
+            },
+        )
+        .await
+    }
+
+    /// Generate a response with explicit sampling options
+    pub async fn generate_with_options(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: GenerateOptions,
+    ) -> AppResult<String> {
+        let url = format!("{}/api/generate", self.base_url);
+
+        let request = GenerateRequest {
+            model: model.to_string(),
+            prompt: prompt.to_string(),
+            stream: false,
+            context: None,
+            options: Some(options),
         };
 
         let response = self.client
@@ -0,0 +1,232 @@
+use crate::error::{AppResult, ExtractionError};
+use crate::file_processor::{DocumentMetadata, ExtractedText, PageText, TextLocation};
+use chrono::{FixedOffset, NaiveDate, TimeZone};
+use lopdf::Document;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Extract a PDF page by page, with its Info dictionary as metadata
+///
+/// Pages are separated by a blank line and each one starts a page marker.
+/// A page whose content can't be decoded is kept, empty, so the page
+/// numbers of the rest still line up. Documents over `max_pages` are refused
+/// before any text is read.
+pub fn pdf_to_text(data: &[u8], max_pages: u32) -> AppResult<ExtractedText> {
+    let mut doc = Document::load_mem(data).map_err(|e| ExtractionError::malformed("PDF", e))?;
+
+    // Most encrypted PDFs only restrict permissions and open with an empty password
+    if doc.is_encrypted() {
+        doc.decrypt("")
+            .map_err(|_| ExtractionError::Encrypted("PDF".to_string()))?;
+    }
+
+    let page_numbers: Vec<u32> = doc.get_pages().into_keys().collect();
+    if page_numbers.len() > max_pages as usize {
+        return Err(ExtractionError::TooManyPages {
+            pages: page_numbers.len(),
+            max_pages,
+        }
+        .into());
+    }
+    let mut extracted = ExtractedText {
+        metadata: metadata(&doc, page_numbers.len()),
+        ..Default::default()
+    };
+    let mut graphemes = 0;
+
+    for number in page_numbers {
+        let text = doc
+            .extract_text(&[number])
+            .map(|text| tidy_page(&text))
+            .unwrap_or_default();
+
+        if !text.is_empty() {
+            if !extracted.text.is_empty() {
+                extracted.text.push_str("\n\n");
+                graphemes += 2;
+            }
+            extracted.locations.push(TextLocation {
+                char_start: graphemes,
+                page: Some(number),
+                ..Default::default()
+            });
+            graphemes += text.graphemes(true).count();
+            extracted.text.push_str(&text);
+        }
+        extracted.pages.push(PageText { number, text });
+    }
+
+    Ok(extracted)
+}
+
+/// Drop trailing spaces and blank lines around a page's text
+fn tidy_page(text: &str) -> String {
+    text.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Title, author and creation date from the document's Info dictionary
+fn metadata(doc: &Document, page_count: usize) -> DocumentMetadata {
+    let info = doc
+        .trailer
+        .get(b"Info")
+        .and_then(|info| doc.dereference(info))
+        .and_then(|(_, info)| info.as_dict())
+        .ok();
+    let field = |key: &[u8]| {
+        let (_, value) = doc.dereference(info?.get(key).ok()?).ok()?;
+        Some(text_string(value.as_str().ok()?)).filter(|s| !s.is_empty())
+    };
+
+    DocumentMetadata {
+        title: field(b"Title"),
+        author: field(b"Author"),
+        created: field(b"CreationDate").and_then(|date| pdf_date(&date)),
+        page_count: Some(page_count as u32),
+        ..Default::default()
+    }
+}
+
+/// Decode a PDF text string: UTF-16 or UTF-8 with a byte order mark,
+/// otherwise PDFDocEncoding (which matches Latin-1 for printable text)
+fn text_string(bytes: &[u8]) -> String {
+    let text = if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        String::from_utf8_lossy(utf8).into_owned()
+    } else {
+        bytes.iter().map(|&b| b as char).collect()
+    };
+    text.trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string()
+}
+
+/// Convert a PDF date such as "D:20230115103000+01'00'" to RFC 3339
+///
+/// Everything after the year is optional; a date without a time zone is
+/// taken as UTC.
+fn pdf_date(value: &str) -> Option<String> {
+    let value = value.trim().trim_start_matches("D:");
+    let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+    let part = |from: usize, default: u32| match digits.get(from..from + 2) {
+        Some(part) => part.parse().ok(),
+        None => Some(default),
+    };
+
+    let year = digits.get(0..4)?.parse().ok()?;
+    let time = NaiveDate::from_ymd_opt(year, part(4, 1)?, part(6, 1)?)?
+        .and_hms_opt(part(8, 0)?, part(10, 0)?, part(12, 0)?)?;
+
+    let zone = &value[digits.len()..];
+    let offset: String = zone.chars().filter(char::is_ascii_digit).collect();
+    let seconds = |from: usize, unit: i32| -> Option<i32> {
+        match offset.get(from..from + 2) {
+            Some(part) => part.parse::<i32>().ok().map(|n| n * unit),
+            None => Some(0),
+        }
+    };
+    let east = seconds(0, 3600)? + seconds(2, 60)?;
+    let offset = FixedOffset::east_opt(if zone.starts_with('-') { -east } else { east })?;
+
+    Some(offset.from_local_datetime(&time).single()?.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+
+    /// A PDF with one page of text per entry (empty for a blank page)
+    fn sample_pdf(pages: &[&str]) -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+
+        let kids: Vec<Object> = pages
+            .iter()
+            .map(|text| {
+                let content = Content {
+                    operations: vec![
+                        Operation::new("BT", vec![]),
+                        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                        Operation::new("Tj", vec![Object::string_literal(*text)]),
+                        Operation::new("ET", vec![]),
+                    ],
+                };
+                let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                })
+                .into()
+            })
+            .collect();
+
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info_id = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Field Guide"),
+            "Author" => Object::String(vec![0xFE, 0xFF, 0x00, b'J', 0x00, 0xF6], lopdf::StringFormat::Literal),
+            "CreationDate" => Object::string_literal("D:20230115103000+01'00'"),
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_pdf_pages_and_metadata() {
+        let pdf = sample_pdf(&["Birds of the coast", "", "Index"]);
+        let extracted = pdf_to_text(&pdf, 10).unwrap();
+
+        let pages: Vec<_> = extracted.pages.iter().map(|p| (p.number, p.text.as_str())).collect();
+        assert_eq!(pages, vec![(1, "Birds of the coast"), (2, ""), (3, "Index")]);
+        assert_eq!(extracted.text, "Birds of the coast\n\nIndex");
+
+        let markers: Vec<_> = extracted.locations.iter().map(|l| (l.char_start, l.page)).collect();
+        assert_eq!(markers, vec![(0, Some(1)), (20, Some(3))]);
+
+        let metadata = &extracted.metadata;
+        assert_eq!(metadata.title.as_deref(), Some("Field Guide"));
+        assert_eq!(metadata.author.as_deref(), Some("Jö"));
+        assert_eq!(metadata.created.as_deref(), Some("2023-01-15T10:30:00+01:00"));
+        assert_eq!(metadata.page_count, Some(3));
+
+        assert!(matches!(
+            pdf_to_text(&pdf, 2),
+            Err(AppError::Extraction(ExtractionError::TooManyPages { pages: 3, max_pages: 2 }))
+        ));
+    }
+
+    #[test]
+    fn test_pdf_date() {
+        assert_eq!(pdf_date("D:19990209153925-08'00'").as_deref(), Some("1999-02-09T15:39:25-08:00"));
+        assert_eq!(pdf_date("D:2021").as_deref(), Some("2021-01-01T00:00:00+00:00"));
+        assert_eq!(pdf_date("D:20210631"), None);
+        assert_eq!(pdf_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::error::{AppError, AppResult};
+use crate::retrieval::cosine_similarity;
+use crate::table_metadata::DistanceMetric;
+use arrow_array::{Array, ArrayRef, FixedSizeListArray, Float16Array, Float32Array, Int8Array, RecordBatch};
+use arrow_schema::{DataType, Field, SchemaRef};
+use half::f16;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Column holding the per-vector scale of int8 embeddings
+pub const SCALE_COLUMN: &str = "embedding_scale";
+
+/// Element type of stored embeddings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorFormat {
+    /// 32-bit floats, exactly as the embedding model returned them
+    #[default]
+    F32,
+    /// Half-precision floats, searched natively by LanceDB
+    F16,
+    /// Scalar-quantized bytes with one scale per vector, searched by an exact scan
+    Int8,
+}
+
+/// How a table stores its embeddings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct StoragePrecision {
+    #[serde(default)]
+    pub format: VectorFormat,
+    /// Keep only the leading dimensions (for Matryoshka embedding models)
+    #[serde(default)]
+    pub truncate_to: Option<i32>,
+}
+
+/// Recall measured for a candidate storage precision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrecisionComparison {
+    pub current: StoragePrecision,
+    pub candidate: StoragePrecision,
+    /// Vectors in the table
+    pub vectors: usize,
+    /// Stored vectors used as queries
+    pub sample_size: usize,
+    pub k: usize,
+    /// Average share of the current top `k` still found at the candidate precision
+    pub recall: f32,
+    pub current_bytes_per_vector: usize,
+    pub candidate_bytes_per_vector: usize,
+}
+
+impl StoragePrecision {
+    /// Reject settings that can't describe a table
+    pub fn validate(&self) -> AppResult<()> {
+        match self.truncate_to {
+            Some(dimensions) if dimensions <= 0 => Err(AppError::LanceDB(format!(
+                "Cannot truncate embeddings to {} dimensions",
+                dimensions
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of dimensions stored for vectors with `dimension` dimensions
+    pub fn stored_dimension(&self, dimension: i32) -> i32 {
+        self.truncate_to.map_or(dimension, |t| t.min(dimension))
+    }
+
+    /// Arrow type of the embedding column
+    pub fn embedding_type(&self, stored_dimension: i32) -> DataType {
+        let item = match self.format {
+            VectorFormat::F32 => DataType::Float32,
+            VectorFormat::F16 => DataType::Float16,
+            VectorFormat::Int8 => DataType::Int8,
+        };
+        DataType::FixedSizeList(Arc::new(Field::new("item", item, true)), stored_dimension)
+    }
+
+    /// Bytes taken by one stored vector of a `dimension`-dimensional model
+    pub fn bytes_per_vector(&self, dimension: i32) -> usize {
+        let stored = self.stored_dimension(dimension) as usize;
+        match self.format {
+            VectorFormat::F32 => stored * 4,
+            VectorFormat::F16 => stored * 2,
+            VectorFormat::Int8 => stored + 4,
+        }
+    }
+
+    /// Cut a vector down to the stored dimensions
+    ///
+    /// Truncated vectors of a normalizing model are rescaled to unit length so
+    /// cosine and dot distances stay comparable. Queries go through the same
+    /// projection as stored vectors.
+    pub fn project(&self, vector: &[f32], normalize: bool) -> Vec<f32> {
+        let stored = self.stored_dimension(vector.len() as i32) as usize;
+        let mut projected = vector[..stored].to_vec();
+
+        if normalize && stored < vector.len() {
+            let norm = projected.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                projected.iter_mut().for_each(|x| *x /= norm);
+            }
+        }
+
+        projected
+    }
+
+    /// Build the embedding column, plus the scale column for int8, from projected vectors
+    pub fn encode(&self, vectors: &[Vec<f32>], stored_dimension: i32) -> AppResult<(ArrayRef, Option<ArrayRef>)> {
+        let DataType::FixedSizeList(item, _) = self.embedding_type(stored_dimension) else {
+            unreachable!("embedding type is always a fixed-size list");
+        };
+
+        let (values, scales): (ArrayRef, Option<ArrayRef>) = match self.format {
+            VectorFormat::F32 => (Arc::new(Float32Array::from(vectors.concat())), None),
+            VectorFormat::F16 => (
+                Arc::new(Float16Array::from(
+                    vectors.iter().flatten().map(|x| f16::from_f32(*x)).collect::<Vec<_>>(),
+                )),
+                None,
+            ),
+            VectorFormat::Int8 => {
+                let mut codes = Vec::with_capacity(vectors.len() * stored_dimension as usize);
+                let mut scales = Vec::with_capacity(vectors.len());
+                for vector in vectors {
+                    let (c, scale) = quantize_int8(vector);
+                    codes.extend(c);
+                    scales.push(scale);
+                }
+                (
+                    Arc::new(Int8Array::from(codes)),
+                    Some(Arc::new(Float32Array::from(scales)) as ArrayRef),
+                )
+            }
+        };
+
+        let list = FixedSizeListArray::try_new(item, stored_dimension, values, None)
+            .map_err(|e| AppError::LanceDB(format!("Failed to create embedding array: {}", e)))?;
+
+        Ok((Arc::new(list), scales))
+    }
+
+    /// A vector as it reads back after being stored with this precision
+    pub fn round_trip(&self, vector: &[f32], normalize: bool) -> Vec<f32> {
+        let projected = self.project(vector, normalize);
+        match self.format {
+            VectorFormat::F32 => projected,
+            VectorFormat::F16 => projected.iter().map(|x| f16::from_f32(*x).to_f32()).collect(),
+            VectorFormat::Int8 => {
+                let (codes, scale) = quantize_int8(&projected);
+                dequantize_int8(&codes, scale)
+            }
+        }
+    }
+}
+
+/// Quantize a vector to int8 codes and the scale that restores it
+pub fn quantize_int8(vector: &[f32]) -> (Vec<i8>, f32) {
+    let max = vector.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+    if max == 0.0 {
+        return (vec![0; vector.len()], 0.0);
+    }
+
+    let scale = max / 127.0;
+    let codes = vector
+        .iter()
+        .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+        .collect();
+    (codes, scale)
+}
+
+/// Restore an int8-quantized vector
+pub fn dequantize_int8(codes: &[i8], scale: f32) -> Vec<f32> {
+    codes.iter().map(|c| *c as f32 * scale).collect()
+}
+
+/// Read a batch's embeddings as 32-bit floats, whatever their stored format
+pub fn decode_embeddings(batch: &RecordBatch) -> AppResult<Vec<Vec<f32>>> {
+    let list = batch
+        .column_by_name("embedding")
+        .ok_or_else(|| AppError::LanceDB("Missing embedding column".to_string()))?
+        .as_any()
+        .downcast_ref::<FixedSizeListArray>()
+        .ok_or_else(|| AppError::LanceDB("Invalid embedding type".to_string()))?;
+    let dim = list.value_length() as usize;
+    let values = list.values();
+    let row = |i: usize| list.value_offset(i) as usize..list.value_offset(i) as usize + dim;
+
+    if let Some(values) = values.as_any().downcast_ref::<Float32Array>() {
+        return Ok((0..list.len()).map(|i| values.values()[row(i)].to_vec()).collect());
+    }
+
+    if let Some(values) = values.as_any().downcast_ref::<Float16Array>() {
+        return Ok((0..list.len())
+            .map(|i| values.values()[row(i)].iter().map(|x| x.to_f32()).collect())
+            .collect());
+    }
+
+    if let Some(values) = values.as_any().downcast_ref::<Int8Array>() {
+        let scales = batch
+            .column_by_name(SCALE_COLUMN)
+            .and_then(|c| c.as_any().downcast_ref::<Float32Array>())
+            .ok_or_else(|| AppError::LanceDB("Missing embedding scale column".to_string()))?;
+        return Ok((0..list.len())
+            .map(|i| dequantize_int8(&values.values()[row(i)], scales.value(i)))
+            .collect());
+    }
+
+    Err(AppError::LanceDB("Unsupported embedding values".to_string()))
+}
+
+/// Replace a batch's embeddings with ones stored at `precision`
+///
+/// Columns are matched by name, so batches from tables whose migrated columns
+/// follow the embedding are rewritten in the order of `schema`.
+pub fn recode_batch(
+    batch: &RecordBatch,
+    precision: &StoragePrecision,
+    normalize: bool,
+    schema: SchemaRef,
+) -> AppResult<RecordBatch> {
+    let vectors: Vec<Vec<f32>> = decode_embeddings(batch)?
+        .iter()
+        .map(|v| precision.project(v, normalize))
+        .collect();
+    let stored_dimension = match schema.field_with_name("embedding").map(|f| f.data_type()) {
+        Ok(DataType::FixedSizeList(_, dimension)) => *dimension,
+        _ => return Err(AppError::LanceDB("Invalid embedding type".to_string())),
+    };
+    if vectors.iter().any(|v| v.len() as i32 != stored_dimension) {
+        return Err(AppError::LanceDB(
+            "Dimensions removed by truncation cannot be restored; re-embed the knowledge base instead".to_string(),
+        ));
+    }
+    let (embeddings, scales) = precision.encode(&vectors, stored_dimension)?;
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| match field.name().as_str() {
+            "embedding" => Ok(embeddings.clone()),
+            SCALE_COLUMN => scales
+                .clone()
+                .ok_or_else(|| AppError::LanceDB("Missing embedding scales".to_string())),
+            name => batch
+                .column_by_name(name)
+                .cloned()
+                .ok_or_else(|| AppError::LanceDB(format!("Missing {} column", name))),
+        })
+        .collect::<AppResult<Vec<ArrayRef>>>()?;
+
+    RecordBatch::try_new(schema, columns)
+        .map_err(|e| AppError::LanceDB(format!("Failed to create record batch: {}", e)))
+}
+
+/// Distance between two vectors, on the scale LanceDB reports for `metric`
+pub fn distance(metric: DistanceMetric, a: &[f32], b: &[f32]) -> f32 {
+    match metric {
+        DistanceMetric::L2 => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+        DistanceMetric::Cosine => 1.0 - cosine_similarity(a, b),
+        DistanceMetric::Dot => 1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>(),
+    }
+}
+
+/// Indices of the `k` vectors nearest to `query`, skipping `exclude`
+fn nearest(query: &[f32], vectors: &[Vec<f32>], metric: DistanceMetric, k: usize, exclude: usize) -> Vec<usize> {
+    let mut scored: Vec<(usize, f32)> = vectors
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != exclude)
+        .map(|(i, v)| (i, distance(metric, query, v)))
+        .collect();
+    scored.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.into_iter().take(k).map(|(i, _)| i).collect()
+}
+
+/// Average recall@k of searching `vectors` stored at `precision`
+///
+/// A spread-out sample of the vectors serves as queries. For each one the
+/// exact top `k` neighbours (excluding itself) are compared with the top `k`
+/// found after storing and querying at `precision`.
+pub fn recall_at_k(
+    vectors: &[Vec<f32>],
+    precision: &StoragePrecision,
+    metric: DistanceMetric,
+    normalize: bool,
+    sample_size: usize,
+    k: usize,
+) -> f32 {
+    if vectors.len() < 2 || sample_size == 0 || k == 0 {
+        return 1.0;
+    }
+
+    let stored: Vec<Vec<f32>> = vectors.iter().map(|v| precision.round_trip(v, normalize)).collect();
+    let sample_size = sample_size.min(vectors.len());
+    let step = vectors.len() as f32 / sample_size as f32;
+
+    let total: f32 = (0..sample_size)
+        .map(|s| {
+            let q = (s as f32 * step) as usize;
+            let exact = nearest(&vectors[q], vectors, metric, k, q);
+            let approx = nearest(&precision.project(&vectors[q], normalize), &stored, metric, k, q);
+            let found = approx.iter().filter(|i| exact.contains(i)).count();
+            found as f32 / exact.len() as f32
+        })
+        .sum();
+
+    total / sample_size as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_schema::Schema;
+
+    #[test]
+    fn test_int8_round_trip() {
+        let vector = vec![0.5, -1.0, 0.25, 0.0];
+        let (codes, scale) = quantize_int8(&vector);
+        assert_eq!(codes, vec![64, -127, 32, 0]);
+
+        let restored = dequantize_int8(&codes, scale);
+        for (a, b) in vector.iter().zip(&restored) {
+            assert!((a - b).abs() < 0.01);
+        }
+        assert_eq!(quantize_int8(&[0.0, 0.0]), (vec![0, 0], 0.0));
+    }
+
+    #[test]
+    fn test_encode_decode_and_truncate() {
+        let precision = StoragePrecision {
+            format: VectorFormat::Int8,
+            truncate_to: Some(2),
+        };
+        let projected = precision.project(&[3.0, 4.0, 12.0], true);
+        assert_eq!(projected, vec![0.6, 0.8]);
+        assert_eq!(precision.bytes_per_vector(1024), 6);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("embedding", precision.embedding_type(2), false),
+            Field::new(SCALE_COLUMN, DataType::Float32, false),
+        ]));
+        let (embeddings, scales) = precision.encode(&[projected], 2).unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![embeddings, scales.unwrap()]).unwrap();
+
+        let decoded = decode_embeddings(&batch).unwrap();
+        assert!((decoded[0][0] - 0.6).abs() < 0.01 && (decoded[0][1] - 0.8).abs() < 0.01);
+
+        // Truncated dimensions can't be brought back
+        let wider = Arc::new(Schema::new(vec![
+            Field::new("embedding", DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)), 3), false),
+        ]));
+        assert!(recode_batch(&batch, &StoragePrecision::default(), true, wider).is_err());
+    }
+
+    #[test]
+    fn test_recall_at_k() {
+        let vectors: Vec<Vec<f32>> = (0..40)
+            .map(|i| {
+                let angle = i as f32 * 0.15;
+                vec![angle.cos(), angle.sin(), (angle * 3.0).sin() * 0.1]
+            })
+            .collect();
+
+        let f16 = StoragePrecision {
+            format: VectorFormat::F16,
+            truncate_to: None,
+        };
+        assert_eq!(recall_at_k(&vectors, &StoragePrecision::default(), DistanceMetric::Cosine, false, 10, 3), 1.0);
+        assert!(recall_at_k(&vectors, &f16, DistanceMetric::Cosine, false, 10, 3) > 0.9);
+    }
+}
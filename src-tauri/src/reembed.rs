@@ -0,0 +1,387 @@
+use crate::error::{AppError, AppResult};
+use crate::lancedb::{shadow_model_id, sql_quote, DocumentChunk, LanceDBService, StoredChunkKey};
+use crate::ollama::OllamaService;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Number of chunks embedded per Ollama round trip
+const EMBED_BATCH_SIZE: usize = 32;
+
+/// Phase of a re-embedding migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReembedState {
+    Running,
+    Swapping,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Progress of a re-embedding migration, emitted as `reembed-progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReembedProgress {
+    pub model_id: String,
+    pub from_embedding_model: Option<String>,
+    pub to_embedding_model: String,
+    pub state: ReembedState,
+    pub total_chunks: usize,
+    pub embedded_chunks: usize,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Moves a model's knowledge base to a different embedding model
+///
+/// Stored chunk text is re-embedded into a shadow table while searches keep
+/// using the current table; the tables are swapped once the shadow is complete.
+pub struct ReembedService {
+    lancedb: Arc<LanceDBService>,
+    ollama: Arc<Mutex<OllamaService>>,
+    jobs: Mutex<HashMap<String, ReembedProgress>>,
+    cancelled: Mutex<HashSet<String>>,
+}
+
+impl ReembedService {
+    /// Create a new re-embedding service
+    pub fn new(lancedb: Arc<LanceDBService>, ollama: Arc<Mutex<OllamaService>>) -> Self {
+        Self {
+            lancedb,
+            ollama,
+            jobs: Mutex::new(HashMap::new()),
+            cancelled: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Get the progress of the latest migration for a model
+    pub async fn status(&self, model_id: &str) -> Option<ReembedProgress> {
+        self.jobs.lock().await.get(model_id).cloned()
+    }
+
+    /// Ask a running migration to stop; the shadow table is discarded
+    pub async fn cancel(&self, model_id: &str) {
+        self.cancelled.lock().await.insert(model_id.to_string());
+    }
+
+    /// Register a new migration, refusing if one is already running
+    ///
+    /// Also refused when the table couldn't be swapped at the end: while an
+    /// old version is checked out or versions are tagged.
+    pub async fn begin(&self, model_id: &str, embedding_model: &str) -> AppResult<ReembedProgress> {
+        self.lancedb.ensure_replaceable(model_id)?;
+
+        let from_embedding_model = self
+            .lancedb
+            .embedding_provenance(model_id)
+            .await?
+            .map(|p| p.model);
+
+        if from_embedding_model.as_deref() == Some(embedding_model) {
+            return Err(AppError::LanceDB(format!(
+                "Knowledge base already uses '{}'",
+                embedding_model
+            )));
+        }
+
+        let mut jobs = self.jobs.lock().await;
+        if matches!(
+            jobs.get(model_id).map(|p| p.state),
+            Some(ReembedState::Running) | Some(ReembedState::Swapping)
+        ) {
+            return Err(AppError::LanceDB(
+                "A re-embedding migration is already running for this model".to_string(),
+            ));
+        }
+
+        let progress = ReembedProgress {
+            model_id: model_id.to_string(),
+            from_embedding_model,
+            to_embedding_model: embedding_model.to_string(),
+            state: ReembedState::Running,
+            total_chunks: 0,
+            embedded_chunks: 0,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+            error: None,
+        };
+        jobs.insert(model_id.to_string(), progress.clone());
+        self.cancelled.lock().await.remove(model_id);
+
+        Ok(progress)
+    }
+
+    /// Run a migration registered with `begin` to completion
+    pub async fn run<F>(
+        &self,
+        model_id: &str,
+        encrypted: bool,
+        password: Option<String>,
+        on_progress: F,
+    ) -> ReembedProgress
+    where
+        F: Fn(&ReembedProgress),
+    {
+        let mut progress = match self.status(model_id).await {
+            Some(progress) => progress,
+            None => {
+                return ReembedProgress {
+                    model_id: model_id.to_string(),
+                    from_embedding_model: None,
+                    to_embedding_model: String::new(),
+                    state: ReembedState::Failed,
+                    total_chunks: 0,
+                    embedded_chunks: 0,
+                    started_at: chrono::Utc::now().to_rfc3339(),
+                    finished_at: None,
+                    error: Some("Migration was not started".to_string()),
+                }
+            }
+        };
+
+        let shadow_id = shadow_model_id(model_id);
+        let result = self
+            .migrate(
+                model_id,
+                &shadow_id,
+                encrypted,
+                password.as_deref(),
+                &mut progress,
+                &on_progress,
+            )
+            .await;
+
+        progress.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        match result {
+            Ok(()) => progress.state = ReembedState::Completed,
+            Err(e) => {
+                // Searches never saw the shadow table, so dropping it is enough to roll back
+                self.lancedb.delete_model_data(&shadow_id).await.ok();
+                if self.cancelled.lock().await.remove(model_id) {
+                    progress.state = ReembedState::Cancelled;
+                } else {
+                    println!("⚠️  Re-embedding failed for {}: {}", model_id, e);
+                    progress.state = ReembedState::Failed;
+                    progress.error = Some(e.to_string());
+                }
+            }
+        }
+
+        self.report(&progress, &on_progress).await;
+        progress
+    }
+
+    async fn migrate<F>(
+        &self,
+        model_id: &str,
+        shadow_id: &str,
+        encrypted: bool,
+        password: Option<&str>,
+        progress: &mut ReembedProgress,
+        on_progress: &F,
+    ) -> AppResult<()>
+    where
+        F: Fn(&ReembedProgress),
+    {
+        let embedding_model = progress.to_embedding_model.clone();
+
+        // 1. Snapshot the current chunks (reusing their stored text)
+        let chunks = self
+            .lancedb
+            .read_chunks(model_id, None, encrypted, password)
+            .await?;
+        progress.total_chunks = chunks.len();
+        self.report(progress, on_progress).await;
+
+        // The new table keeps the model's storage precision
+        let precision = self.lancedb.storage_precision(model_id)?;
+        self.lancedb
+            .set_storage_precision(shadow_id, precision)
+            .await?;
+
+        // 2. Embed into the shadow table, releasing locks between batches
+        for batch in chunks.chunks(EMBED_BATCH_SIZE) {
+            self.embed_into_shadow(
+                model_id,
+                shadow_id,
+                &embedding_model,
+                batch,
+                encrypted,
+                password,
+            )
+            .await?;
+            progress.embedded_chunks += batch.len();
+            self.report(progress, on_progress).await;
+        }
+
+        // 3. Catch up with uploads and deletions made during the migration,
+        //    holding the model's write lock so nothing changes before the swap
+        progress.state = ReembedState::Swapping;
+        self.report(progress, on_progress).await;
+
+        let _write_guard = self.lancedb.write_lock(model_id).await;
+        let lancedb = &self.lancedb;
+        // A version checked out or tagged since the start would block the swap
+        lancedb.ensure_replaceable(model_id)?;
+        let key = |k: &StoredChunkKey| {
+            (
+                k.training_data_id.clone(),
+                k.chunk_index,
+                k.content_hash.clone(),
+            )
+        };
+        let source_keys = lancedb.list_all_chunk_keys(model_id).await?;
+        let shadow_keys = lancedb.list_all_chunk_keys(shadow_id).await?;
+        let source_set: HashSet<_> = source_keys.iter().map(key).collect();
+        let shadow_set: HashSet<_> = shadow_keys.iter().map(key).collect();
+
+        let missing_ids: Vec<String> = source_keys
+            .iter()
+            .filter(|k| !shadow_set.contains(&key(*k)))
+            .map(|k| k.id.clone())
+            .collect();
+        let extra_ids: Vec<String> = shadow_keys
+            .iter()
+            .filter(|k| !source_set.contains(&key(*k)))
+            .map(|k| k.id.clone())
+            .collect();
+
+        lancedb.delete_chunks(shadow_id, &extra_ids).await?;
+
+        for ids in missing_ids.chunks(EMBED_BATCH_SIZE) {
+            let id_list = ids
+                .iter()
+                .map(|id| format!("'{}'", sql_quote(id)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let missing = lancedb
+                .read_chunks(
+                    model_id,
+                    Some(&format!("id IN ({})", id_list)),
+                    encrypted,
+                    password,
+                )
+                .await?;
+
+            let texts: Vec<String> = missing.iter().map(DocumentChunk::embedding_text).collect();
+            let ollama = self.ollama.lock().await.clone();
+            let embeddings = ollama.generate_embeddings_batch(&embedding_model, texts).await?;
+            lancedb
+                .store_embeddings(
+                    shadow_id,
+                    &embedding_model,
+                    missing,
+                    embeddings,
+                    encrypted,
+                    password,
+                )
+                .await?;
+        }
+
+        if self.cancelled.lock().await.contains(model_id) {
+            return Err(AppError::LanceDB("Migration cancelled".to_string()));
+        }
+
+        // 4. Point the model at the shadow table
+        lancedb.swap_in_table(model_id, shadow_id).await?;
+        progress.total_chunks = source_keys.len();
+        progress.embedded_chunks = source_keys.len();
+
+        Ok(())
+    }
+
+    /// Embed one batch of chunks and store it in the shadow table
+    async fn embed_into_shadow(
+        &self,
+        model_id: &str,
+        shadow_id: &str,
+        embedding_model: &str,
+        batch: &[DocumentChunk],
+        encrypted: bool,
+        password: Option<&str>,
+    ) -> AppResult<()> {
+        if self.cancelled.lock().await.contains(model_id) {
+            return Err(AppError::LanceDB("Migration cancelled".to_string()));
+        }
+
+        let texts: Vec<String> = batch.iter().map(DocumentChunk::embedding_text).collect();
+        // Work on a clone so chat and reranking aren't blocked for the whole batch
+        let ollama = self.ollama.lock().await.clone();
+        let embeddings = ollama.generate_embeddings_batch(embedding_model, texts).await?;
+
+        self.lancedb
+            .store_embeddings(
+                shadow_id,
+                embedding_model,
+                batch.to_vec(),
+                embeddings,
+                encrypted,
+                password,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Publish progress to the status map and the caller
+    async fn report<F>(&self, progress: &ReembedProgress, on_progress: &F)
+    where
+        F: Fn(&ReembedProgress),
+    {
+        self.jobs
+            .lock()
+            .await
+            .insert(progress.model_id.clone(), progress.clone());
+        on_progress(progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_reembed_refuses_checked_out_version() {
+        let temp_dir = env::temp_dir().join("mydistinctai_reembed_test_checkout");
+        let lancedb = Arc::new(LanceDBService::new(temp_dir.clone()));
+        let ollama = Arc::new(Mutex::new(OllamaService::new("http://localhost:11434".to_string())));
+        let service = ReembedService::new(lancedb.clone(), ollama);
+        let model_id = "test_model_reembed_checkout";
+
+        let mut first = 0;
+        for i in 0..2 {
+            let chunk = DocumentChunk {
+                id: uuid::Uuid::new_v4().to_string(),
+                model_id: model_id.to_string(),
+                chunk_text: format!("chunk {}", i),
+                chunk_index: i,
+                file_name: "notes.txt".to_string(),
+                training_data_id: "notes".to_string(),
+                ..Default::default()
+            };
+            lancedb
+                .store_embeddings(model_id, "old-embed", vec![chunk], vec![vec![0.5; 4]], false, None)
+                .await
+                .unwrap();
+            if i == 0 {
+                first = lancedb.list_versions(model_id).await.unwrap().last().unwrap().version;
+            }
+        }
+
+        // Re-embedding the older snapshot would drop the chunk added after it
+        lancedb.checkout_version(model_id, first).await.unwrap();
+        assert!(service.begin(model_id, "new-embed").await.is_err());
+        assert!(lancedb.swap_in_table(model_id, &shadow_model_id(model_id)).await.is_err());
+
+        lancedb.checkout_latest(model_id).await.unwrap();
+        assert!(service.begin(model_id, "new-embed").await.is_ok());
+        assert_eq!(lancedb.get_stats(model_id).await.unwrap().total_chunks, 2);
+
+        // Cleanup
+        lancedb.delete_model_data(model_id).await.ok();
+        std::fs::remove_dir_all(temp_dir).ok();
+    }
+}
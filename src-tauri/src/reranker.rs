@@ -0,0 +1,238 @@
+use crate::error::{AppError, AppResult};
+use crate::lancedb::SearchResult;
+use crate::ollama::{GenerateOptions, OllamaService};
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+/// How query/chunk pairs are scored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RerankMode {
+    /// A dedicated reranker model served by Ollama that answers with a 0-1 score
+    Model,
+    /// A general chat model asked to rate relevance from 0 to 10
+    LlmJudge,
+}
+
+/// Second-stage reranking settings for a model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_mode")]
+    pub mode: RerankMode,
+    /// Ollama model used for scoring
+    #[serde(default = "default_model")]
+    pub model: String,
+    /// Number of vector search hits to rescore
+    #[serde(default = "default_candidate_pool")]
+    pub candidate_pool: usize,
+    /// Give up and keep vector order after this long
+    #[serde(default = "default_latency_budget_ms")]
+    pub latency_budget_ms: u64,
+}
+
+fn default_mode() -> RerankMode {
+    RerankMode::LlmJudge
+}
+
+fn default_model() -> String {
+    "mistral:7b".to_string()
+}
+
+fn default_candidate_pool() -> usize {
+    20
+}
+
+fn default_latency_budget_ms() -> u64 {
+    3000
+}
+
+impl Default for RerankSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: default_mode(),
+            model: default_model(),
+            candidate_pool: default_candidate_pool(),
+            latency_budget_ms: default_latency_budget_ms(),
+        }
+    }
+}
+
+/// Build the scoring prompt for one query/chunk pair
+fn build_prompt(mode: RerankMode, query: &str, chunk: &str) -> String {
+    match mode {
+        RerankMode::Model => format!("Query: {}\nDocument: {}\nRelevance:", query, chunk),
+        RerankMode::LlmJudge => format!(
+            "Rate how relevant the passage is to the question on a scale from 0 (unrelated) \
+             to 10 (answers it directly). Reply with the number only.\n\n\
+             Question: {}\n\nPassage:\n{}\n\nRating:",
+            query, chunk
+        ),
+    }
+}
+
+/// Parse a model reply into a 0-1 relevance score
+fn parse_score(mode: RerankMode, reply: &str) -> Option<f32> {
+    let number: String = reply
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let value: f32 = number.trim_end_matches('.').parse().ok()?;
+
+    let scale = match mode {
+        RerankMode::Model => 1.0,
+        RerankMode::LlmJudge => 10.0,
+    };
+    Some((value / scale).clamp(0.0, 1.0))
+}
+
+/// Score one chunk against the query
+async fn score(
+    ollama: &OllamaService,
+    settings: &RerankSettings,
+    query: &str,
+    chunk: &str,
+) -> AppResult<f32> {
+    let options = GenerateOptions {
+        temperature: Some(0.0),
+        num_predict: Some(8),
+        ..Default::default()
+    };
+    let reply = ollama
+        .generate_with_options(&settings.model, &build_prompt(settings.mode, query, chunk), options)
+        .await?;
+
+    parse_score(settings.mode, &reply)
+        .ok_or_else(|| AppError::Ollama(format!("Reranker returned no score: {:?}", reply)))
+}
+
+/// Run scoring futures concurrently, keeping whatever finishes within `budget`
+///
+/// Entries line up with `scoring`; those still pending at the deadline are None.
+async fn scores_within<F>(scoring: Vec<F>, budget: Duration) -> Vec<Option<AppResult<f32>>>
+where
+    F: Future<Output = AppResult<f32>>,
+{
+    let mut scores: Vec<Option<AppResult<f32>>> = scoring.iter().map(|_| None).collect();
+    let mut pending: FuturesUnordered<_> = scoring
+        .into_iter()
+        .enumerate()
+        .map(|(i, future)| async move { (i, future.await) })
+        .collect();
+
+    let deadline = tokio::time::Instant::now() + budget;
+    while let Ok(Some((i, score))) = tokio::time::timeout_at(deadline, pending.next()).await {
+        scores[i] = Some(score);
+    }
+    scores
+}
+
+/// Rescore search results and keep the best `top_n`
+///
+/// Results are ordered by rerank score, with chunks that could not be scored
+/// after the scored ones in vector order. Chunks still being scored when the
+/// latency budget runs out count as unscored, so a slow reply only costs its
+/// own chunk.
+pub async fn rerank(
+    ollama: &OllamaService,
+    settings: &RerankSettings,
+    query: &str,
+    mut results: Vec<SearchResult>,
+    top_n: usize,
+) -> Vec<SearchResult> {
+    let scoring = results
+        .iter()
+        .map(|r| score(ollama, settings, query, &r.chunk_text))
+        .collect();
+    let scores = scores_within(scoring, Duration::from_millis(settings.latency_budget_ms)).await;
+
+    let mut timed_out = 0;
+    for (result, score) in results.iter_mut().zip(scores) {
+        match score {
+            Some(Ok(score)) => result.rerank_score = Some(score),
+            Some(Err(e)) => println!("⚠️  Reranking failed for {}: {}", result.file_name, e),
+            None => timed_out += 1,
+        }
+    }
+    if timed_out > 0 {
+        println!(
+            "⚠️  Reranking exceeded {}ms, {} chunks left unscored",
+            settings.latency_budget_ms, timed_out
+        );
+    }
+    sort_by_rerank_score(&mut results);
+
+    results.truncate(top_n);
+    results
+}
+
+/// Order by rerank score, falling back to vector similarity for unscored results
+fn sort_by_rerank_score(results: &mut [SearchResult]) {
+    results.sort_by(|a, b| {
+        let key = |r: &SearchResult| (r.rerank_score.is_some(), r.rerank_score.unwrap_or(0.0), r.similarity);
+        key(b)
+            .partial_cmp(&key(a))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(file_name: &str, similarity: f32, rerank_score: Option<f32>) -> SearchResult {
+        SearchResult {
+            chunk_text: String::new(),
+            similarity,
+            file_name: file_name.to_string(),
+            training_data_id: file_name.to_string(),
+            chunk_index: 0,
+            location: Default::default(),
+            rerank_score,
+            model_id: String::new(),
+            normalized_score: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_score() {
+        assert_eq!(parse_score(RerankMode::LlmJudge, "8"), Some(0.8));
+        assert_eq!(parse_score(RerankMode::LlmJudge, " Rating: 10."), Some(1.0));
+        assert_eq!(parse_score(RerankMode::Model, "0.25"), Some(0.25));
+        assert_eq!(parse_score(RerankMode::LlmJudge, "not relevant"), None);
+    }
+
+    #[test]
+    fn test_sort_by_rerank_score() {
+        let mut results = vec![
+            result("a", 0.9, Some(0.2)),
+            result("b", 0.8, None),
+            result("c", 0.7, Some(0.9)),
+        ];
+        sort_by_rerank_score(&mut results);
+
+        let order: Vec<&str> = results.iter().map(|r| r.file_name.as_str()).collect();
+        assert_eq!(order, vec!["c", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_scores_within_keeps_partial_scores() {
+        let delays = [0u64, 20, 10_000];
+        let scoring = delays
+            .iter()
+            .map(|&ms| async move {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Ok(ms as f32)
+            })
+            .collect();
+
+        let scores = scores_within(scoring, Duration::from_millis(200)).await;
+        let scores: Vec<Option<f32>> = scores.into_iter().map(|s| s.map(|s| s.unwrap())).collect();
+        assert_eq!(scores, vec![Some(0.0), Some(20.0), None]);
+    }
+}
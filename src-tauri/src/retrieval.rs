@@ -0,0 +1,288 @@
+//! Re-ranking helpers applied to vector search results
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Rough characters-per-token ratio used for token budgets
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Shortest text overlap trusted when chunk offsets are unknown
+const MIN_TEXT_OVERLAP: usize = 16;
+
+/// Optional post-processing for a vector search
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchOptions {
+    /// Diversify results with Maximal Marginal Relevance
+    #[serde(default)]
+    pub mmr: Option<MmrOptions>,
+}
+
+/// Maximal Marginal Relevance settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MmrOptions {
+    /// Trade-off between relevance (1.0) and diversity (0.0)
+    #[serde(default = "default_lambda")]
+    pub lambda: f32,
+    /// Number of nearest neighbours to choose from (defaults to 4x the limit)
+    #[serde(default)]
+    pub candidate_pool: Option<usize>,
+}
+
+fn default_lambda() -> f32 {
+    0.5
+}
+
+impl Default for MmrOptions {
+    fn default() -> Self {
+        Self {
+            lambda: default_lambda(),
+            candidate_pool: None,
+        }
+    }
+}
+
+impl MmrOptions {
+    /// Number of candidates to fetch for a search returning `limit` results
+    pub fn pool_size(&self, limit: usize) -> usize {
+        self.candidate_pool.unwrap_or(limit * 4).max(limit)
+    }
+}
+
+/// How RAG context is assembled from search hits
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContextOptions {
+    /// Neighbouring chunks (`chunk_index ± window`) to merge into each hit
+    #[serde(default)]
+    pub window: usize,
+    /// Total characters of passage text
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    /// Total tokens of passage text (estimated from characters)
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+}
+
+impl ContextOptions {
+    /// Character budget implied by `max_chars` and `max_tokens`
+    pub fn char_budget(&self) -> Option<usize> {
+        let from_tokens = self.max_tokens.map(|t| t * CHARS_PER_TOKEN);
+        match (self.max_chars, from_tokens) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// A stored chunk taking part in a merged passage
+#[derive(Debug, Clone)]
+pub struct WindowChunk {
+    pub chunk_index: i32,
+    pub text: String,
+    pub char_start: i32,
+    pub char_end: i32,
+}
+
+impl WindowChunk {
+    fn has_offsets(&self) -> bool {
+        self.char_end > self.char_start
+    }
+}
+
+/// Join consecutive chunks of one file into a single passage
+///
+/// Overlap between neighbours is removed using their stored offsets, or by
+/// matching the end of one chunk against the start of the next when offsets
+/// are unknown.
+pub fn merge_chunks(chunks: &[WindowChunk]) -> String {
+    let mut sorted: Vec<&WindowChunk> = chunks.iter().collect();
+    sorted.sort_by_key(|c| c.chunk_index);
+
+    let mut merged = String::new();
+    let mut prev: Option<&WindowChunk> = None;
+
+    for chunk in sorted {
+        let Some(p) = prev else {
+            merged.push_str(&chunk.text);
+            prev = Some(chunk);
+            continue;
+        };
+
+        if p.has_offsets() && chunk.has_offsets() {
+            let overlap = (p.char_end - chunk.char_start).max(0) as usize;
+            let rest: String = chunk.text.graphemes(true).skip(overlap).collect();
+            if chunk.char_start > p.char_end {
+                merged.push('\n');
+            }
+            merged.push_str(&rest);
+        } else {
+            let overlap = text_overlap(&merged, &chunk.text);
+            if overlap == 0 {
+                merged.push('\n');
+            }
+            merged.push_str(&chunk.text[overlap..]);
+        }
+        prev = Some(chunk);
+    }
+
+    merged
+}
+
+/// Byte length of the longest suffix of `a` that is a prefix of `b`
+fn text_overlap(a: &str, b: &str) -> usize {
+    let boundaries: Vec<usize> = b
+        .grapheme_indices(true)
+        .map(|(i, _)| i)
+        .skip(MIN_TEXT_OVERLAP)
+        .chain(std::iter::once(b.len()))
+        .collect();
+
+    boundaries
+        .into_iter()
+        .rev()
+        .find(|&end| a.ends_with(&b[..end]))
+        .unwrap_or(0)
+}
+
+/// Rescale scores to 0-1 within one result list
+///
+/// Lists from different tables can then be merged on a common scale; when all
+/// scores are equal they map to 1.
+pub fn min_max_normalize(scores: &[f32]) -> Vec<f32> {
+    let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+    scores
+        .iter()
+        .map(|s| if max > min { (s - min) / (max - min) } else { 1.0 })
+        .collect()
+}
+
+/// Cosine similarity of two vectors (0 when either has zero length)
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let mut dot = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+
+    for (x, y) in a.iter().zip(b.iter()) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// Pick `k` candidates by Maximal Marginal Relevance
+///
+/// Each step takes the candidate maximising
+/// `lambda * sim(query, c) - (1 - lambda) * max(sim(c, selected))`,
+/// so `lambda = 1` is plain relevance order and lower values favour diversity.
+/// Returns indices into `candidates` in selection order.
+pub fn mmr_select(query: &[f32], candidates: &[Vec<f32>], k: usize, lambda: f32) -> Vec<usize> {
+    let lambda = lambda.clamp(0.0, 1.0);
+    let relevance: Vec<f32> = candidates
+        .iter()
+        .map(|c| cosine_similarity(query, c))
+        .collect();
+
+    let mut selected: Vec<usize> = Vec::with_capacity(k.min(candidates.len()));
+    // Highest similarity of each candidate to anything selected so far
+    let mut redundancy = vec![f32::NEG_INFINITY; candidates.len()];
+
+    while selected.len() < k.min(candidates.len()) {
+        let best = (0..candidates.len())
+            .filter(|i| !selected.contains(i))
+            .map(|i| {
+                let penalty = if selected.is_empty() { 0.0 } else { redundancy[i] };
+                (i, lambda * relevance[i] - (1.0 - lambda) * penalty)
+            })
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, _)| i);
+
+        let Some(best) = best else { break };
+        selected.push(best);
+
+        for i in 0..candidates.len() {
+            let sim = cosine_similarity(&candidates[i], &candidates[best]);
+            if sim > redundancy[i] {
+                redundancy[i] = sim;
+            }
+        }
+    }
+
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_min_max_normalize() {
+        assert_eq!(min_max_normalize(&[0.25, 0.75, 0.5]), vec![0.0, 1.0, 0.5]);
+        assert_eq!(min_max_normalize(&[0.3, 0.3]), vec![1.0, 1.0]);
+        assert!(min_max_normalize(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_mmr_skips_near_duplicates() {
+        let query = vec![1.0, 0.0];
+        let candidates = vec![
+            vec![1.0, 0.05],  // most relevant
+            vec![1.0, 0.06],  // near-duplicate of the first
+            vec![0.7, -0.7],  // less relevant but different
+        ];
+
+        // Pure relevance keeps the duplicate
+        assert_eq!(mmr_select(&query, &candidates, 2, 1.0), vec![0, 1]);
+        // Diversity-weighted selection prefers the different chunk
+        assert_eq!(mmr_select(&query, &candidates, 2, 0.5), vec![0, 2]);
+    }
+
+    fn window_chunk(chunk_index: i32, text: &str, char_start: i32, char_end: i32) -> WindowChunk {
+        WindowChunk {
+            chunk_index,
+            text: text.to_string(),
+            char_start,
+            char_end,
+        }
+    }
+
+    #[test]
+    fn test_merge_chunks_removes_overlap() {
+        // "The quick brown fox jumps" chunked with a 5 character overlap
+        let chunks = vec![
+            window_chunk(1, "brown fox jumps", 10, 25),
+            window_chunk(0, "The quick brown", 0, 15),
+        ];
+        assert_eq!(merge_chunks(&chunks), "The quick brown fox jumps");
+
+        // Without offsets the overlap is found in the text
+        let chunks = vec![
+            window_chunk(0, "Installation requires Rust 1.75 or newer", 0, 0),
+            window_chunk(1, "Rust 1.75 or newer and a C compiler", 0, 0),
+        ];
+        assert_eq!(
+            merge_chunks(&chunks),
+            "Installation requires Rust 1.75 or newer and a C compiler"
+        );
+    }
+
+    #[test]
+    fn test_mmr_handles_small_pools() {
+        let query = vec![1.0, 0.0];
+        assert!(mmr_select(&query, &[], 3, 0.5).is_empty());
+        assert_eq!(mmr_select(&query, &[vec![0.5, 0.5]], 3, 0.5), vec![0]);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::error::{AppError, AppResult};
+use crate::quantization::{StoragePrecision, VectorFormat, SCALE_COLUMN};
+use crate::table_metadata::EmbeddingProvenance;
+use crate::vector_schema::CURRENT_SCHEMA_VERSION;
+use arrow_array::{ArrayRef, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, DataType, Schema, SchemaRef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Version of the export file layout written by this build
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// Schema metadata key holding the export manifest
+const MANIFEST_KEY: &str = "mydistinctai.export";
+
+/// Description of an exported vector table, stored in the Arrow IPC file's schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub format_version: u32,
+    /// Model the table belonged to on the exporting machine
+    pub source_model_id: String,
+    pub exported_at: String,
+    /// Vector table schema version of the exported rows
+    pub schema_version: u32,
+    /// Embedding model, dimension and metric (None for tables that predate provenance tracking)
+    pub embedding: Option<EmbeddingProvenance>,
+    /// How the exported vectors are stored
+    #[serde(default)]
+    pub precision: StoragePrecision,
+    pub row_count: usize,
+}
+
+/// Result of exporting a model's table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub manifest: ExportManifest,
+}
+
+/// Result of importing an export file into a model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub model_id: String,
+    pub source_model_id: String,
+    pub row_count: usize,
+    pub embedding: Option<EmbeddingProvenance>,
+}
+
+impl ExportManifest {
+    /// Copy of `schema` carrying this manifest in its metadata
+    pub fn attach(&self, schema: &Schema) -> AppResult<SchemaRef> {
+        let mut metadata = schema.metadata().clone();
+        metadata.insert(MANIFEST_KEY.to_string(), serde_json::to_string(self)?);
+        Ok(Arc::new(schema.clone().with_metadata(metadata)))
+    }
+
+    /// Read the manifest from an export file's schema
+    pub fn from_schema(schema: &Schema) -> AppResult<Self> {
+        let json = schema.metadata().get(MANIFEST_KEY).ok_or_else(|| {
+            AppError::IncompatibleExport("file is not a knowledge base export".to_string())
+        })?;
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Check that this build can import rows with `schema`
+    ///
+    /// Exports from newer builds are refused, as are files whose embedding
+    /// column doesn't match the recorded dimension.
+    pub fn validate(&self, schema: &Schema) -> AppResult<()> {
+        if self.format_version > EXPORT_FORMAT_VERSION {
+            return Err(AppError::IncompatibleExport(format!(
+                "export format v{} is newer than this app supports (v{})",
+                self.format_version, EXPORT_FORMAT_VERSION
+            )));
+        }
+        if self.schema_version > CURRENT_SCHEMA_VERSION {
+            return Err(AppError::IncompatibleExport(format!(
+                "table schema v{} is newer than this app supports (v{})",
+                self.schema_version, CURRENT_SCHEMA_VERSION
+            )));
+        }
+
+        for column in ["id", "model_id", "chunk_text", "chunk_index", "file_name"] {
+            if schema.field_with_name(column).is_err() {
+                return Err(AppError::IncompatibleExport(format!("missing column '{}'", column)));
+            }
+        }
+
+        let (item, dimension) = match schema.field_with_name("embedding").map(|f| f.data_type()) {
+            Ok(DataType::FixedSizeList(item, dimension)) => (item.data_type().clone(), *dimension),
+            _ => {
+                return Err(AppError::IncompatibleExport(
+                    "embedding column must be a fixed-size list".to_string(),
+                ))
+            }
+        };
+        let expected_dimension = self
+            .embedding
+            .as_ref()
+            .map_or(dimension, |p| self.precision.stored_dimension(p.dimension));
+        if expected_dimension != dimension
+            || !matches!(
+                (self.precision.format, &item),
+                (VectorFormat::F32, DataType::Float32)
+                    | (VectorFormat::F16, DataType::Float16)
+                    | (VectorFormat::Int8, DataType::Int8)
+            )
+        {
+            return Err(AppError::IncompatibleExport(format!(
+                "manifest records {} dimensions stored as {:?} but the embedding column holds {} {} values",
+                expected_dimension, self.precision.format, dimension, item
+            )));
+        }
+        if self.precision.format == VectorFormat::Int8 && schema.field_with_name(SCALE_COLUMN).is_err() {
+            return Err(AppError::IncompatibleExport(format!("missing column '{}'", SCALE_COLUMN)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Schema of imported rows: the file's schema without the export manifest
+pub fn import_schema(schema: &Schema) -> SchemaRef {
+    Arc::new(schema.clone().with_metadata(HashMap::new()))
+}
+
+/// Rewrite the `model_id` column so imported rows belong to `model_id`
+pub fn remap_model_id(batch: RecordBatch, schema: SchemaRef, model_id: &str) -> Result<RecordBatch, ArrowError> {
+    let index = schema.index_of("model_id")?;
+    let mut columns: Vec<ArrayRef> = batch.columns().to_vec();
+    columns[index] = Arc::new(StringArray::from(vec![model_id; batch.num_rows()]));
+    RecordBatch::try_new(schema, columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table_metadata::DistanceMetric;
+    use crate::vector_schema::table_schema;
+    use arrow_array::Array;
+
+    fn manifest(dimension: i32) -> ExportManifest {
+        ExportManifest {
+            format_version: EXPORT_FORMAT_VERSION,
+            source_model_id: "laptop-model".to_string(),
+            exported_at: "2024-01-01T00:00:00Z".to_string(),
+            schema_version: CURRENT_SCHEMA_VERSION,
+            embedding: Some(EmbeddingProvenance {
+                model: "nomic-embed-text".to_string(),
+                dimension,
+                normalized: true,
+                distance_metric: DistanceMetric::Cosine,
+            }),
+            precision: StoragePrecision::default(),
+            row_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_manifest_round_trip_and_validation() {
+        let schema = manifest(4).attach(&table_schema(4, &Default::default())).unwrap();
+        let read = ExportManifest::from_schema(&schema).unwrap();
+        assert_eq!(read, manifest(4));
+        assert!(read.validate(&schema).is_ok());
+        assert!(import_schema(&schema).metadata().is_empty());
+
+        // Dimension recorded in the manifest must match the vectors
+        assert!(manifest(8).validate(&schema).is_err());
+
+        // Files from newer builds are refused
+        let newer = ExportManifest {
+            schema_version: CURRENT_SCHEMA_VERSION + 1,
+            ..manifest(4)
+        };
+        assert!(newer.validate(&schema).is_err());
+
+        // Plain Arrow files are not exports
+        assert!(ExportManifest::from_schema(&table_schema(4, &Default::default())).is_err());
+    }
+
+    #[test]
+    fn test_remap_model_id() {
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("id", DataType::Utf8, false),
+            arrow_schema::Field::new("model_id", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(StringArray::from(vec!["laptop-model", "laptop-model"])),
+            ],
+        )
+        .unwrap();
+
+        let remapped = remap_model_id(batch, schema, "desktop-model").unwrap();
+        let model_ids = remapped
+            .column_by_name("model_id")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            .unwrap();
+        assert_eq!(model_ids.value(0), "desktop-model");
+        assert_eq!(model_ids.value(1), "desktop-model");
+    }
+}
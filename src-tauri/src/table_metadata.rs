@@ -0,0 +1,211 @@
+use crate::error::{AppError, AppResult};
+use crate::quantization::StoragePrecision;
+use arrow_schema::{Schema, SchemaRef};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Arrow schema metadata key holding a table's embedding provenance
+const PROVENANCE_KEY: &str = "mydistinctai.embedding";
+
+/// Distance used for vector search on a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceMetric {
+    L2,
+    Cosine,
+    Dot,
+}
+
+/// Which embedding model produced a table's vectors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingProvenance {
+    /// Ollama model name, e.g. "nomic-embed-text"
+    pub model: String,
+    pub dimension: i32,
+    /// Whether stored vectors have unit length
+    pub normalized: bool,
+    pub distance_metric: DistanceMetric,
+}
+
+impl EmbeddingProvenance {
+    /// Copy of `schema` carrying this provenance in its metadata
+    ///
+    /// Stamped into a table's schema when it is created, so the provenance
+    /// travels with the table through imports and re-embedding swaps.
+    pub fn attach(&self, schema: &Schema) -> AppResult<SchemaRef> {
+        let mut metadata = schema.metadata().clone();
+        metadata.insert(PROVENANCE_KEY.to_string(), serde_json::to_string(self)?);
+        Ok(Arc::new(schema.clone().with_metadata(metadata)))
+    }
+
+    /// Read the provenance stamped into a table's schema, if any
+    pub fn from_schema(schema: &Schema) -> Option<Self> {
+        serde_json::from_str(schema.metadata().get(PROVENANCE_KEY)?).ok()
+    }
+}
+
+/// Bookkeeping for a model's vector table that LanceDB doesn't track itself
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableMetadata {
+    /// RFC 3339 time of the last successful `store_embeddings`
+    #[serde(default)]
+    pub last_ingested_at: Option<String>,
+    /// Version that reads are pinned to, if the user checked one out
+    #[serde(default)]
+    pub checked_out_version: Option<u64>,
+    /// User-defined labels for table versions, e.g. "before Q3 import"
+    #[serde(default)]
+    pub tags: BTreeMap<String, u64>,
+    /// Embedding model the table was built with (None for tables created before it was recorded)
+    ///
+    /// Tables created by this app also carry it in their schema, which wins when the two differ.
+    #[serde(default)]
+    pub embedding: Option<EmbeddingProvenance>,
+    /// Physical table currently serving this model, when it differs from the
+    /// default name (set when a re-embedding migration swaps tables)
+    #[serde(default)]
+    pub active_table: Option<String>,
+    /// Vector table schema version (0 for tables created before versioning)
+    #[serde(default)]
+    pub schema_version: u32,
+    /// How embeddings are stored (full 32-bit floats unless opted out)
+    #[serde(default)]
+    pub precision: StoragePrecision,
+}
+
+/// JSON sidecar files stored next to the LanceDB tables
+pub struct TableMetadataStore {
+    dir: PathBuf,
+    /// Serializes writers so concurrent updates don't lose each other's changes
+    write_lock: Mutex<()>,
+}
+
+impl TableMetadataStore {
+    /// Create a store rooted in the LanceDB directory
+    pub fn new(db_path: &Path) -> Self {
+        Self {
+            dir: db_path.join("_meta"),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Get the sidecar path for a table
+    fn get_file_path(&self, table_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", table_name))
+    }
+
+    /// Load metadata for a table (defaults if none has been written yet)
+    pub fn load(&self, table_name: &str) -> AppResult<TableMetadata> {
+        let file_path = self.get_file_path(table_name);
+
+        if !file_path.exists() {
+            return Ok(TableMetadata::default());
+        }
+
+        let data = fs::read_to_string(&file_path)
+            .map_err(|e| AppError::Storage(format!("Failed to read table metadata: {}", e)))?;
+
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// Save metadata for a table
+    pub fn save(&self, table_name: &str, metadata: &TableMetadata) -> AppResult<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.write(table_name, metadata)
+    }
+
+    fn write(&self, table_name: &str, metadata: &TableMetadata) -> AppResult<()> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| AppError::Storage(format!("Failed to create metadata directory: {}", e)))?;
+
+        // Write to a temp file and rename so readers never see a partial file
+        let file_path = self.get_file_path(table_name);
+        let tmp_path = file_path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(metadata)?)
+            .map_err(|e| AppError::Storage(format!("Failed to write table metadata: {}", e)))?;
+        fs::rename(&tmp_path, &file_path)
+            .map_err(|e| AppError::Storage(format!("Failed to write table metadata: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Load, modify and save metadata for a table
+    pub fn update<F>(&self, table_name: &str, f: F) -> AppResult<TableMetadata>
+    where
+        F: FnOnce(&mut TableMetadata),
+    {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut metadata = self.load(table_name)?;
+        f(&mut metadata);
+        self.write(table_name, &metadata)?;
+        Ok(metadata)
+    }
+
+    /// Delete metadata for a table
+    pub fn remove(&self, table_name: &str) -> AppResult<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let file_path = self.get_file_path(table_name);
+
+        if file_path.exists() {
+            fs::remove_file(&file_path)
+                .map_err(|e| AppError::Storage(format!("Failed to delete table metadata: {}", e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete metadata for every table
+    pub fn clear(&self) -> AppResult<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir)
+                .map_err(|e| AppError::Storage(format!("Failed to clear table metadata: {}", e)))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_save_and_load_metadata() {
+        let temp_dir = env::temp_dir().join("mydistinctai_table_metadata_test");
+        let store = TableMetadataStore::new(&temp_dir);
+
+        assert!(store.load("embeddings_x").unwrap().last_ingested_at.is_none());
+
+        store
+            .update("embeddings_x", |m| m.last_ingested_at = Some("2024-01-01T00:00:00Z".to_string()))
+            .unwrap();
+        let loaded = store.load("embeddings_x").unwrap();
+        assert_eq!(loaded.last_ingested_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+
+        store.remove("embeddings_x").unwrap();
+        assert!(store.load("embeddings_x").unwrap().last_ingested_at.is_none());
+
+        // Cleanup
+        fs::remove_dir_all(temp_dir).ok();
+    }
+
+    #[test]
+    fn test_provenance_in_schema() {
+        let provenance = EmbeddingProvenance {
+            model: "nomic-embed-text".to_string(),
+            dimension: 4,
+            normalized: true,
+            distance_metric: DistanceMetric::Cosine,
+        };
+        let schema = crate::vector_schema::table_schema(4, &StoragePrecision::default());
+        assert!(EmbeddingProvenance::from_schema(&schema).is_none());
+
+        let stamped = provenance.attach(&schema).unwrap();
+        assert_eq!(EmbeddingProvenance::from_schema(&stamped), Some(provenance));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::quantization::{StoragePrecision, VectorFormat, SCALE_COLUMN};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use std::sync::Arc;
+
+/// Schema version written by this build
+pub const CURRENT_SCHEMA_VERSION: u32 = 6;
+
+/// Columns added by one schema version
+pub struct SchemaMigration {
+    pub version: u32,
+    pub description: &'static str,
+    /// Column name and the SQL expression that fills it for existing rows
+    pub columns: &'static [(&'static str, &'static str)],
+}
+
+/// Upgrades from the original (version 1) table layout, in order
+pub const MIGRATIONS: &[SchemaMigration] = &[
+    SchemaMigration {
+        version: 2,
+        description: "training data IDs and content hashes",
+        columns: &[
+            ("training_data_id", "file_name"),
+            // Unknown for these rows (and not computable for encrypted text), so
+            // the first re-ingest of each file re-embeds it in full
+            ("content_hash", "''"),
+            ("file_hash", "''"),
+        ],
+    },
+    SchemaMigration {
+        version: 3,
+        description: "chunk character offsets",
+        columns: &[
+            ("char_start", "CAST(0 AS INT)"),
+            ("char_end", "CAST(0 AS INT)"),
+        ],
+    },
+    SchemaMigration {
+        version: 4,
+        description: "byte offsets, pages and sections",
+        columns: &[
+            ("byte_start", "CAST(0 AS INT)"),
+            ("byte_end", "CAST(0 AS INT)"),
+            ("page_start", "CAST(NULL AS INT)"),
+            ("page_end", "CAST(NULL AS INT)"),
+            ("section", "CAST(NULL AS VARCHAR)"),
+        ],
+    },
+    SchemaMigration {
+        version: 5,
+        description: "source line ranges",
+        columns: &[
+            ("line_start", "CAST(NULL AS INT)"),
+            ("line_end", "CAST(NULL AS INT)"),
+        ],
+    },
+    SchemaMigration {
+        version: 6,
+        description: "code languages and symbols",
+        columns: &[
+            ("language", "CAST(NULL AS VARCHAR)"),
+            ("symbol", "CAST(NULL AS VARCHAR)"),
+        ],
+    },
+];
+
+/// Arrow schema of a model's vector table
+///
+/// Columns added after version 1 are nullable so tables upgraded in place and
+/// tables created fresh accept the same record batches. The embedding column
+/// follows the table's storage precision; int8 tables also store a scale.
+pub fn table_schema(embedding_dim: i32, precision: &StoragePrecision) -> SchemaRef {
+    let mut fields = vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("model_id", DataType::Utf8, false),
+        Field::new("chunk_text", DataType::Utf8, false),
+        Field::new("chunk_index", DataType::Int32, false),
+        Field::new("file_name", DataType::Utf8, false),
+        Field::new("training_data_id", DataType::Utf8, true),
+        Field::new("content_hash", DataType::Utf8, true),
+        Field::new("file_hash", DataType::Utf8, true),
+        Field::new("char_start", DataType::Int32, true),
+        Field::new("char_end", DataType::Int32, true),
+        Field::new("byte_start", DataType::Int32, true),
+        Field::new("byte_end", DataType::Int32, true),
+        Field::new("page_start", DataType::Int32, true),
+        Field::new("page_end", DataType::Int32, true),
+        Field::new("section", DataType::Utf8, true),
+        Field::new("line_start", DataType::Int32, true),
+        Field::new("line_end", DataType::Int32, true),
+        Field::new("language", DataType::Utf8, true),
+        Field::new("symbol", DataType::Utf8, true),
+        Field::new(
+            "embedding",
+            precision.embedding_type(precision.stored_dimension(embedding_dim)),
+            false,
+        ),
+    ];
+    if precision.format == VectorFormat::Int8 {
+        fields.push(Field::new(SCALE_COLUMN, DataType::Float32, false));
+    }
+
+    Arc::new(Schema::new(fields))
+}
+
+/// Migrations a table still needs, given the columns it already has
+///
+/// Columns are checked individually, so tables without a recorded version and
+/// partially upgraded tables are handled the same way.
+pub fn pending_migrations<'a>(existing_columns: &[String]) -> Vec<(&'a SchemaMigration, Vec<(String, String)>)> {
+    MIGRATIONS
+        .iter()
+        .filter_map(|migration| {
+            let missing: Vec<(String, String)> = migration
+                .columns
+                .iter()
+                .filter(|(name, _)| !existing_columns.iter().any(|c| c == name))
+                .map(|(name, expr)| (name.to_string(), expr.to_string()))
+                .collect();
+            (!missing.is_empty()).then_some((migration, missing))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_cover_schema() {
+        assert_eq!(MIGRATIONS.last().map(|m| m.version), Some(CURRENT_SCHEMA_VERSION));
+
+        // A fresh table has every migrated column
+        let columns: Vec<String> = table_schema(4, &StoragePrecision::default()).fields().iter().map(|f| f.name().clone()).collect();
+        assert!(pending_migrations(&columns).is_empty());
+    }
+
+    #[test]
+    fn test_pending_migrations_for_legacy_table() {
+        let legacy: Vec<String> = ["id", "model_id", "chunk_text", "chunk_index", "file_name", "embedding"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+
+        let pending = pending_migrations(&legacy);
+        let versions: Vec<u32> = pending.iter().map(|(m, _)| m.version).collect();
+        assert_eq!(versions, vec![2, 3, 4, 5, 6]);
+        assert_eq!(pending[0].1[0], ("training_data_id".to_string(), "file_name".to_string()));
+    }
+}
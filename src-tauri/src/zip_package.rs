@@ -0,0 +1,163 @@
+use crate::error::{AppError, AppResult, ExtractionError};
+use quick_xml::events::BytesStart;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+/// A zip container of XML parts (EPUB, Office Open XML, OpenDocument)
+pub struct ZipPackage<'a> {
+    archive: ZipArchive<Cursor<&'a [u8]>>,
+}
+
+impl<'a> ZipPackage<'a> {
+    pub fn open(data: &'a [u8]) -> AppResult<Self> {
+        let archive = ZipArchive::new(Cursor::new(data)).map_err(package_error)?;
+        Ok(Self { archive })
+    }
+
+    /// Read a part as text
+    ///
+    /// A part that expands past the size recorded for it is refused, so the
+    /// recorded sizes checked by [`check_expanded_size`] hold.
+    pub fn read(&mut self, path: &str) -> AppResult<String> {
+        let entry = self.archive.by_name(path).map_err(package_error)?;
+        let size = entry.size();
+        let mut bytes = Vec::new();
+        entry.take(size + 1).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > size {
+            return Err(package_error(format!("{} is larger than recorded", path)));
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Refuse the package if any part expands past the size recorded for it
+    ///
+    /// For packages handed to a library that opens the zip itself, which
+    /// would otherwise trust the recorded sizes. Parts are decompressed into
+    /// a sink, so nothing is buffered.
+    pub fn verify_sizes(&mut self) -> AppResult<()> {
+        for i in 0..self.archive.len() {
+            let entry = self.archive.by_index(i).map_err(package_error)?;
+            let (name, size) = (entry.name().to_string(), entry.size());
+            let expanded = std::io::copy(&mut entry.take(size + 1), &mut std::io::sink())?;
+            if expanded > size {
+                return Err(package_error(format!("{} is larger than recorded", name)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.archive.index_for_name(path).is_some()
+    }
+
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.archive.file_names()
+    }
+}
+
+/// Refuse an archive whose parts would expand past `max_bytes` in total
+pub fn check_expanded_size(data: &[u8], max_bytes: u64) -> AppResult<()> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(package_error)?;
+    let mut total: u64 = 0;
+    for i in 0..archive.len() {
+        total = total.saturating_add(archive.by_index_raw(i).map_err(package_error)?.size());
+        if total > max_bytes {
+            return Err(ExtractionError::DecompressedTooLarge { max_bytes }.into());
+        }
+    }
+    Ok(())
+}
+
+fn package_error(e: impl std::fmt::Display) -> AppError {
+    ExtractionError::malformed("document package", e).into()
+}
+
+/// Attribute value by local name, ignoring any namespace prefix
+pub fn attr(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|a| a.key.local_name().as_ref() == name)
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+/// Directory part of a package path, without the trailing slash
+pub fn parent_dir(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// Resolve an href relative to `base_dir`, dropping any fragment
+pub fn resolve_href(base_dir: &str, href: &str) -> String {
+    let href = percent_decode(href.split('#').next().unwrap_or(""));
+    let mut parts: Vec<&str> = base_dir.split('/').filter(|p| !p.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_href() {
+        assert_eq!(resolve_href("OEBPS/toc", "../text/ch4.xhtml#start"), "OEBPS/text/ch4.xhtml");
+        assert_eq!(resolve_href("", "Chapter%20One.xhtml"), "Chapter One.xhtml");
+        assert_eq!(resolve_href("ppt/slides", "../notesSlides/notesSlide1.xml"), "ppt/notesSlides/notesSlide1.xml");
+        assert_eq!(parent_dir("OEBPS/content.opf"), "OEBPS");
+    }
+
+    #[test]
+    fn test_check_expanded_size() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("zeros.txt", zip::write::SimpleFileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut writer, &[b'0'; 10_000]).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        assert!(data.len() < 1_000);
+        assert!(check_expanded_size(&data, 10_000).is_ok());
+        assert!(matches!(
+            check_expanded_size(&data, 9_999),
+            Err(AppError::Extraction(ExtractionError::DecompressedTooLarge { max_bytes: 9_999 }))
+        ));
+        assert_eq!(ZipPackage::open(&data).unwrap().read("zeros.txt").unwrap().len(), 10_000);
+
+        // Understate the expanded size in the local and central headers
+        let mut forged = data.clone();
+        for (signature, offset) in [(b"PK\x03\x04", 22), (b"PK\x01\x02", 24)] {
+            let at = forged.windows(4).position(|w| w == signature).unwrap() + offset;
+            forged[at..at + 4].copy_from_slice(&10u32.to_le_bytes());
+        }
+        assert!(check_expanded_size(&forged, 9_999).is_ok());
+        assert!(ZipPackage::open(&data).unwrap().verify_sizes().is_ok());
+        assert!(ZipPackage::open(&forged).unwrap().verify_sizes().is_err());
+    }
+}
@@ -67,7 +67,7 @@ export default function DesktopDataPage() {
                 multiple: false,
                 filters: [{
                     name: 'Documents',
-                    extensions: ['pdf', 'txt', 'docx', 'md']
+                    extensions: ['pdf', 'txt', 'docx', 'md', 'html', 'htm', 'csv', 'epub', 'pptx', 'xlsx', 'odt', 'ods', 'odp', 'rs', 'py', 'ts', 'tsx', 'js', 'jsx', 'go', 'java', 'kt', 'cs', 'c', 'h', 'cpp', 'hpp', 'swift', 'scala', 'php']
                 }]
             })
 
@@ -122,7 +122,8 @@ export default function DesktopModelsPage() {
             const selected = await open({
                 multiple: true,
                 filters: [
-                    { name: 'Documents', extensions: ['txt', 'pdf', 'docx', 'md'] }
+                    { name: 'Documents', extensions: ['txt', 'pdf', 'docx', 'md', 'html', 'htm', 'csv', 'epub', 'pptx', 'xlsx', 'odt', 'ods', 'odp'] },
+                    { name: 'Source code', extensions: ['rs', 'py', 'ts', 'tsx', 'js', 'jsx', 'go', 'java', 'kt', 'cs', 'c', 'h', 'cpp', 'hpp', 'swift', 'scala', 'php'] }
                 ]
             })
 
@@ -18,18 +18,51 @@ export interface NewChatSession {
     title: string
 }
 
+export interface MessageSource {
+    file_name: string
+    training_data_id: string
+    chunk_index: number
+    char_start: number
+    char_end: number
+    byte_start: number
+    byte_end: number
+    page_start: number | null
+    page_end: number | null
+    section: string | null
+    line_start: number | null
+    line_end: number | null
+    language: string | null
+    symbol: string | null
+    score: number
+}
+
+export interface ContextPassage extends MessageSource {
+    first_chunk: number
+    last_chunk: number
+    similarity: number
+    rerank_score: number | null
+    text: string
+}
+
+export interface ContextResult {
+    passages: ContextPassage[]
+    prompt: string
+}
+
 export interface ChatMessage {
     id: string
     session_id: string
     role: 'user' | 'assistant' | 'system'
     content: string
     created_at: string
+    sources: MessageSource[]
 }
 
 export interface NewChatMessage {
     session_id: string
     role: 'user' | 'assistant' | 'system'
     content: string
+    sources?: MessageSource[]
 }
 
 // ============ CHAT SESSIONS ============
@@ -132,7 +165,7 @@ export async function sendChatMessage(params: {
         })
 
         // 2. Get RAG context if enabled
-        let context: string | null = null
+        let context: ContextResult | null = null
         if (params.useRag) {
             try {
                 // Generate embedding for user message
@@ -142,9 +175,11 @@ export async function sendChatMessage(params: {
                 })
 
                 // Get relevant context from vector database
-                context = await invoke<string>('get_rag_context', {
+                context = await invoke<ContextResult>('get_rag_context', {
                     modelId: params.modelId,
+                    embeddingModel: 'nomic-embed-text',
                     queryEmbedding: embedding,
+                    query: params.userMessage,
                     maxChunks: 5,
                     encrypted: false,
                     password: null,
@@ -155,8 +190,8 @@ export async function sendChatMessage(params: {
         }
 
         // 3. Generate AI response
-        const prompt = context
-            ? `Context from training data:\n\n${context}\n\nUser question: ${params.userMessage}`
+        const prompt = context?.prompt
+            ? `Context from training data:\n\n${context.prompt}\n\nUser question: ${params.userMessage}`
             : params.userMessage
 
         const aiResponse = await invoke<string>('generate_response', {
@@ -170,6 +205,7 @@ export async function sendChatMessage(params: {
             session_id: params.sessionId,
             role: 'assistant',
             content: aiResponse,
+            sources: context?.passages ?? [],
         })
 
         return {
@@ -15,6 +15,21 @@ export interface TrainingData {
     created_at: string
 }
 
+/** How uploads are cut into chunks (overrides the model's setting) */
+export type ChunkingConfig =
+    | { strategy: 'fixed_width'; chunk_size: number; overlap?: number }
+    | { strategy: 'recursive'; chunk_size: number; overlap?: number }
+    | { strategy: 'sentence_window'; window: number; overlap?: number }
+    | { strategy: 'token_count'; max_tokens: number; overlap?: number }
+
+/** Bounds on extracting one file; omitted fields use the defaults */
+export interface ExtractionLimits {
+    max_file_size_mb?: number
+    max_decompressed_bytes?: number
+    max_pages?: number
+    timeout_secs?: number
+}
+
 export interface NewTrainingData {
     model_id: string
     file_name: string
@@ -35,6 +50,18 @@ export async function addTrainingData(data: NewTrainingData): Promise<TrainingDa
     }
 }
 
+/**
+ * Get the training data record of a file, adding it on first upload
+ */
+export async function getOrAddTrainingData(data: NewTrainingData): Promise<TrainingData> {
+    try {
+        return await invoke('db_get_or_add_training_data', { data })
+    } catch (error) {
+        console.error('Failed to get training data:', error)
+        throw new Error(error as string)
+    }
+}
+
 /**
  * List all training data for a model
  */
@@ -62,6 +89,9 @@ export async function deleteTrainingData(id: string): Promise<void> {
 /**
  * Process and store a file (complete workflow)
  * Extract text, chunk it, generate embeddings, store in LanceDB
+ *
+ * The file's training data record is reused when it was uploaded before,
+ * so re-uploading a file updates it instead of adding a duplicate.
  */
 export async function processAndStoreFile(params: {
     modelId: string
@@ -70,14 +100,32 @@ export async function processAndStoreFile(params: {
     embeddingModel: string
     chunkSize?: number
     overlap?: number
+    chunking?: ChunkingConfig
+    limits?: ExtractionLimits
     encrypt?: boolean
     password?: string | null
 }): Promise<{
     chunks_processed: number
     chunks_stored: number
     total_chars: number
+    metadata: {
+        title: string | null
+        author: string | null
+        created: string | null
+        page_count: number | null
+        language: string | null
+    }
 }> {
+    let record: TrainingData | null = null
     try {
+        record = await getOrAddTrainingData({
+            model_id: params.modelId,
+            file_name: params.fileName,
+            file_path: params.filePath,
+            file_type: params.fileName.split('.').pop() || 'unknown',
+            chunks_count: 0,
+        })
+
         const result = await invoke('process_and_store_file', {
             modelId: params.modelId,
             filePath: params.filePath,
@@ -85,21 +133,19 @@ export async function processAndStoreFile(params: {
             embeddingModel: params.embeddingModel,
             chunkSize: params.chunkSize || 500,
             overlap: params.overlap || 50,
+            chunking: params.chunking || null,
+            limits: params.limits || null,
             encrypt: params.encrypt || false,
             password: params.password || null,
-        })
-
-        // After successful processing, add record to database
-        await addTrainingData({
-            model_id: params.modelId,
-            file_name: params.fileName,
-            file_path: params.filePath,
-            file_type: params.fileName.split('.').pop() || 'unknown',
-            chunks_count: (result as any).chunks_processed,
+            trainingDataId: record.id,
         })
 
         return result as any
     } catch (error) {
+        // Don't leave a record behind for a first upload that never stored anything
+        if (record && record.chunks_count === 0) {
+            await deleteTrainingData(record.id).catch(() => {})
+        }
         console.error('Failed to process and store file:', error)
         throw new Error(error as string)
     }